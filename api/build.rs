@@ -0,0 +1,4 @@
+// Embeds new migrations in the binary, `sqlx::migrate!()` doesn't watch them.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,271 @@
+//! `#[derive(ApiSchema)]` for the request and response types of the API,
+//! describing them for the OpenAPI document from their fields, doc comments,
+//! `serde` attributes and `validator` constraints.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Fields, Lit, Meta, MetaNameValue, NestedMeta,
+};
+
+#[proc_macro_derive(ApiSchema)]
+pub fn derive_api_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let result = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(struct_schema(&input, fields)),
+            _ => Err(syn::Error::new_spanned(
+                &input.ident,
+                "ApiSchema needs named fields",
+            )),
+        },
+        Data::Enum(data) => enum_schema(&input, data),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &input.ident,
+            "ApiSchema can't describe unions",
+        )),
+    };
+
+    result.unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+fn struct_schema(input: &DeriveInput, fields: &syn::FieldsNamed) -> TokenStream2 {
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let description = option_tokens(doc_comment(&input.attrs));
+    let rename_all = serde_value(&input.attrs, "rename_all");
+
+    let properties = fields.named.iter().filter_map(|field| {
+        // A field that only goes one way isn't part of the shared schema.
+        if serde_flag(&field.attrs, "skip")
+            || serde_flag(&field.attrs, "skip_deserializing")
+            || serde_flag(&field.attrs, "skip_serializing")
+        {
+            return None;
+        }
+
+        let ty = &field.ty;
+        if serde_flag(&field.attrs, "flatten") {
+            return Some(quote! {
+                object.flatten(<#ty as crate::openapi::ApiSchema>::schema(components));
+            });
+        }
+
+        let field_name = serde_value(&field.attrs, "rename").unwrap_or_else(|| {
+            let name = field.ident.as_ref().unwrap().to_string();
+            rename(&name, rename_all.as_deref())
+        });
+        let description = option_tokens(doc_comment(&field.attrs));
+        let has_default = serde_flag(&field.attrs, "default")
+            || serde_value(&field.attrs, "default").is_some()
+            || serde_value(&field.attrs, "skip_serializing_if").is_some();
+        let (required_by_validator, constraints) = validator_constraints(&field.attrs);
+
+        Some(quote! {
+            object.property(
+                #field_name,
+                <#ty as crate::openapi::ApiSchema>::schema(components),
+                #required_by_validator || !(<#ty as crate::openapi::ApiSchema>::OPTIONAL || #has_default),
+                #description,
+                &[#(#constraints),*],
+            );
+        })
+    });
+
+    quote! {
+        impl crate::openapi::ApiSchema for #ident {
+            fn schema(components: &mut crate::openapi::Components) -> serde_json::Value {
+                if !components.contains_key(#name) {
+                    // Claims the name first so that recursive types terminate.
+                    components.insert(#name.to_string(), serde_json::Value::Null);
+                    let mut object = crate::openapi::ObjectSchema::new(#description);
+                    #(#properties)*
+                    components.insert(#name.to_string(), object.into_value());
+                }
+
+                crate::openapi::reference(#name)
+            }
+        }
+    }
+}
+
+fn enum_schema(input: &DeriveInput, data: &syn::DataEnum) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let description = option_tokens(doc_comment(&input.attrs));
+    let rename_all = serde_value(&input.attrs, "rename_all");
+
+    let mut values = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "ApiSchema only describes enums of unit variants",
+            ));
+        }
+
+        values.push(
+            serde_value(&variant.attrs, "rename")
+                .unwrap_or_else(|| rename(&variant.ident.to_string(), rename_all.as_deref())),
+        );
+    }
+
+    Ok(quote! {
+        impl crate::openapi::ApiSchema for #ident {
+            fn schema(components: &mut crate::openapi::Components) -> serde_json::Value {
+                components.entry(#name).or_insert_with(|| {
+                    crate::openapi::enum_schema(#description, &[#(#values),*])
+                });
+
+                crate::openapi::reference(#name)
+            }
+        }
+    })
+}
+
+/// Whether the field is required by `#[validate(required)]`, and the
+/// constraints of its `length`, `range` and `email` validators.
+fn validator_constraints(attrs: &[Attribute]) -> (bool, Vec<TokenStream2>) {
+    let mut required = false;
+    let mut constraints = Vec::new();
+
+    for nested in attribute_lists(attrs, "validate") {
+        let NestedMeta::Meta(meta) = nested else {
+            continue;
+        };
+
+        match &meta {
+            Meta::Path(path) if path.is_ident("required") => required = true,
+            Meta::Path(path) if path.is_ident("email") => {
+                constraints.push(quote! { ("format", serde_json::json!("email")) })
+            }
+            Meta::List(list) if list.path.is_ident("required") => required = true,
+            Meta::List(list) if list.path.is_ident("email") => {
+                constraints.push(quote! { ("format", serde_json::json!("email")) })
+            }
+            Meta::List(list) if list.path.is_ident("length") || list.path.is_ident("range") => {
+                let kind = if list.path.is_ident("length") {
+                    "length"
+                } else {
+                    "range"
+                };
+
+                for nested in &list.nested {
+                    let NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. })) = nested
+                    else {
+                        continue;
+                    };
+                    let Some(bound) = path.get_ident().map(|i| i.to_string()) else {
+                        continue;
+                    };
+                    if !matches!(bound.as_str(), "min" | "max" | "equal") {
+                        continue;
+                    }
+
+                    let value = match lit {
+                        Lit::Int(i) => quote! { serde_json::json!(#i) },
+                        Lit::Float(f) => quote! { serde_json::json!(#f) },
+                        // Constants are referenced by name, like the
+                        // validator itself does.
+                        Lit::Str(s) => match s.parse::<syn::Path>() {
+                            Ok(path) => quote! { serde_json::json!(#path) },
+                            Err(e) => return (required, vec![e.to_compile_error()]),
+                        },
+                        _ => continue,
+                    };
+                    let key = format!("{}_{}", kind, bound);
+                    constraints.push(quote! { (#key, #value) });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (required, constraints)
+}
+
+fn attribute_lists(attrs: &[Attribute], name: &str) -> Vec<NestedMeta> {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident(name))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested.into_iter()),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+fn serde_flag(attrs: &[Attribute], name: &str) -> bool {
+    attribute_lists(attrs, "serde")
+        .iter()
+        .any(|n| matches!(n, NestedMeta::Meta(Meta::Path(p)) if p.is_ident(name)))
+}
+
+fn serde_value(attrs: &[Attribute], name: &str) -> Option<String> {
+    attribute_lists(attrs, "serde")
+        .iter()
+        .find_map(|n| match n {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(s),
+                ..
+            })) if path.is_ident(name) => Some(s.value()),
+            _ => None,
+        })
+}
+
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path.is_ident("doc"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(s), ..
+            })) => Some(s.value().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join(" "))
+    }
+}
+
+fn option_tokens(value: Option<String>) -> TokenStream2 {
+    match value {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
+    }
+}
+
+/// Applies a `#[serde(rename_all)]` rule to a field or variant name.
+fn rename(name: &str, rule: Option<&str>) -> String {
+    let snake = || {
+        let mut out = String::new();
+        for (i, c) in name.chars().enumerate() {
+            if c.is_uppercase() {
+                if i > 0 && !name.contains('_') {
+                    out.push('_');
+                }
+                out.extend(c.to_lowercase());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    };
+
+    match rule {
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("lowercase") => name.to_lowercase(),
+        Some("UPPERCASE") => name.to_uppercase(),
+        _ => name.to_string(),
+    }
+}
@@ -0,0 +1,7 @@
+ALTER TABLE category
+	DROP CONSTRAINT category_parent_fk;
+
+ALTER TABLE product
+	DROP CONSTRAINT product_discount_fk,
+	DROP CONSTRAINT product_inventory_fk,
+	DROP CONSTRAINT product_category_fk;
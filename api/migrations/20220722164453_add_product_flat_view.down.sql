@@ -0,0 +1 @@
+DROP VIEW product_flat_view;
@@ -0,0 +1,4 @@
+DROP TABLE order_status_history;
+DROP TABLE order_item;
+DROP TABLE customer_order;
+DROP TYPE order_status;
@@ -0,0 +1,65 @@
+CREATE TYPE order_status AS ENUM (
+	'pending',
+	'paid',
+	'picking',
+	'shipped',
+	'delivered',
+	'cancelled',
+	'refunded'
+);
+
+CREATE TABLE customer_order (
+	id bigserial PRIMARY KEY,
+	status order_status NOT NULL DEFAULT 'pending',
+	total decimal(12, 2) NOT NULL DEFAULT 0,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE order_item (
+	id bigserial PRIMARY KEY,
+	order_id bigint NOT NULL,
+	product_id bigint,
+	inventory_id bigint,
+	name varchar(128) NOT NULL,
+	sku varchar(128),
+	quantity integer NOT NULL CHECK (quantity > 0),
+	unit_price decimal(12, 2) NOT NULL,
+	created_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE order_status_history (
+	id bigserial PRIMARY KEY,
+	order_id bigint NOT NULL,
+	from_status order_status,
+	to_status order_status NOT NULL,
+	note text,
+	created_at timestamp NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE order_item
+	ADD CONSTRAINT order_item_order_fk FOREIGN KEY (order_id)
+	REFERENCES customer_order(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE order_item
+	ADD CONSTRAINT order_item_product_fk FOREIGN KEY (product_id)
+	REFERENCES product(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+ALTER TABLE order_item
+	ADD CONSTRAINT order_item_inventory_fk FOREIGN KEY (inventory_id)
+	REFERENCES product_inventory(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+ALTER TABLE order_status_history
+	ADD CONSTRAINT order_status_history_order_fk FOREIGN KEY (order_id)
+	REFERENCES customer_order(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+CREATE INDEX order_item_order_id_idx ON order_item(order_id);
+CREATE INDEX order_status_history_order_id_idx ON order_status_history(order_id);
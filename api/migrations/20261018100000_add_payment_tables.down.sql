@@ -0,0 +1,4 @@
+DROP TABLE payment_webhook_event;
+DROP TABLE payment_refund;
+DROP TABLE payment;
+DROP TYPE payment_status;
@@ -0,0 +1,57 @@
+CREATE TYPE payment_status AS ENUM (
+	'authorized',
+	'captured',
+	'partially_refunded',
+	'refunded',
+	'voided',
+	'failed'
+);
+
+CREATE TABLE payment (
+	id bigserial PRIMARY KEY,
+	order_id bigint NOT NULL,
+	provider varchar(64) NOT NULL,
+	reference varchar(128),
+	status payment_status NOT NULL,
+	amount decimal(12, 2) NOT NULL,
+	captured_amount decimal(12, 2) NOT NULL DEFAULT 0,
+	refunded_amount decimal(12, 2) NOT NULL DEFAULT 0,
+	failure_reason text,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW(),
+	UNIQUE (provider, reference)
+);
+
+CREATE TABLE payment_refund (
+	id bigserial PRIMARY KEY,
+	payment_id bigint NOT NULL,
+	reference varchar(128) NOT NULL UNIQUE,
+	idempotency_key varchar(128),
+	amount decimal(12, 2) NOT NULL,
+	reason text,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	UNIQUE (payment_id, idempotency_key)
+);
+
+CREATE TABLE payment_webhook_event (
+	id bigserial PRIMARY KEY,
+	provider varchar(64) NOT NULL,
+	event_id varchar(128) NOT NULL,
+	payload jsonb NOT NULL,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	UNIQUE (provider, event_id)
+);
+
+ALTER TABLE payment
+	ADD CONSTRAINT payment_order_fk FOREIGN KEY (order_id)
+	REFERENCES customer_order(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE payment_refund
+	ADD CONSTRAINT payment_refund_payment_fk FOREIGN KEY (payment_id)
+	REFERENCES payment(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+CREATE INDEX payment_order_id_idx ON payment(order_id);
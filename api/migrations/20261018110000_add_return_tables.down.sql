@@ -0,0 +1,8 @@
+DROP TABLE credit_note;
+DROP TABLE document_sequence;
+DROP TABLE inventory_movement;
+DROP TABLE return_line;
+DROP TABLE order_return;
+DROP TYPE inventory_movement_kind;
+DROP TYPE return_line_condition;
+DROP TYPE return_status;
@@ -0,0 +1,114 @@
+CREATE TYPE return_status AS ENUM (
+	'requested',
+	'approved',
+	'rejected',
+	'received',
+	'refunded'
+);
+
+CREATE TYPE return_line_condition AS ENUM (
+	'restocked',
+	'damaged'
+);
+
+CREATE TYPE inventory_movement_kind AS ENUM (
+	'return_restock',
+	'write_off'
+);
+
+CREATE TABLE order_return (
+	id bigserial PRIMARY KEY,
+	order_id bigint NOT NULL,
+	status return_status NOT NULL DEFAULT 'requested',
+	note text,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE return_line (
+	id bigserial PRIMARY KEY,
+	return_id bigint NOT NULL,
+	order_item_id bigint NOT NULL,
+	quantity integer NOT NULL CHECK (quantity > 0),
+	reason varchar(500) NOT NULL,
+	condition return_line_condition
+);
+
+CREATE TABLE inventory_movement (
+	id bigserial PRIMARY KEY,
+	inventory_id bigint NOT NULL,
+	kind inventory_movement_kind NOT NULL,
+	quantity integer NOT NULL,
+	return_line_id bigint,
+	note text,
+	created_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE document_sequence (
+	kind varchar(16) NOT NULL,
+	year integer NOT NULL,
+	last_number integer NOT NULL,
+	PRIMARY KEY (kind, year)
+);
+
+CREATE TABLE credit_note (
+	id bigserial PRIMARY KEY,
+	number varchar(32) NOT NULL UNIQUE,
+	order_id bigint NOT NULL,
+	return_id bigint,
+	payment_id bigint,
+	amount decimal(12, 2) NOT NULL,
+	created_at timestamp NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE order_return
+	ADD CONSTRAINT order_return_order_fk FOREIGN KEY (order_id)
+	REFERENCES customer_order(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE return_line
+	ADD CONSTRAINT return_line_return_fk FOREIGN KEY (return_id)
+	REFERENCES order_return(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE return_line
+	ADD CONSTRAINT return_line_order_item_fk FOREIGN KEY (order_item_id)
+	REFERENCES order_item(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE inventory_movement
+	ADD CONSTRAINT inventory_movement_inventory_fk FOREIGN KEY (inventory_id)
+	REFERENCES product_inventory(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE inventory_movement
+	ADD CONSTRAINT inventory_movement_return_line_fk FOREIGN KEY (return_line_id)
+	REFERENCES return_line(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+ALTER TABLE credit_note
+	ADD CONSTRAINT credit_note_order_fk FOREIGN KEY (order_id)
+	REFERENCES customer_order(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+ALTER TABLE credit_note
+	ADD CONSTRAINT credit_note_return_fk FOREIGN KEY (return_id)
+	REFERENCES order_return(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+ALTER TABLE credit_note
+	ADD CONSTRAINT credit_note_payment_fk FOREIGN KEY (payment_id)
+	REFERENCES payment(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+CREATE INDEX order_return_order_id_idx ON order_return(order_id);
+CREATE INDEX return_line_return_id_idx ON return_line(return_id);
+CREATE INDEX inventory_movement_inventory_id_idx ON inventory_movement(inventory_id);
@@ -0,0 +1,16 @@
+ALTER TABLE customer_order
+	DROP COLUMN shipping_postcode,
+	DROP COLUMN shipping_country,
+	DROP COLUMN shipping_cost,
+	DROP COLUMN shipping_method_id;
+
+DROP TABLE shipping_method;
+DROP TABLE shipping_zone_rule;
+DROP TABLE shipping_zone;
+DROP TYPE shipping_method_kind;
+
+ALTER TABLE product
+	DROP COLUMN height,
+	DROP COLUMN width,
+	DROP COLUMN length,
+	DROP COLUMN weight;
@@ -0,0 +1,63 @@
+ALTER TABLE product
+	ADD COLUMN weight decimal(10, 3),
+	ADD COLUMN length decimal(10, 2),
+	ADD COLUMN width decimal(10, 2),
+	ADD COLUMN height decimal(10, 2);
+
+CREATE TYPE shipping_method_kind AS ENUM (
+	'flat_rate',
+	'weight_table',
+	'free_over_threshold',
+	'local_pickup'
+);
+
+CREATE TABLE shipping_zone (
+	id bigserial PRIMARY KEY,
+	name varchar(128) NOT NULL
+);
+
+CREATE TABLE shipping_zone_rule (
+	id bigserial PRIMARY KEY,
+	zone_id bigint NOT NULL,
+	country char(2) NOT NULL,
+	postcode_prefix varchar(16)
+);
+
+CREATE TABLE shipping_method (
+	id bigserial PRIMARY KEY,
+	name varchar(128) NOT NULL,
+	kind shipping_method_kind NOT NULL,
+	zone_id bigint,
+	price decimal(12, 2) NOT NULL DEFAULT 0,
+	threshold decimal(12, 2),
+	weight_rates jsonb NOT NULL DEFAULT '[]',
+	active bool NOT NULL DEFAULT true,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE customer_order
+	ADD COLUMN shipping_method_id bigint,
+	ADD COLUMN shipping_cost decimal(12, 2) NOT NULL DEFAULT 0,
+	ADD COLUMN shipping_country char(2),
+	ADD COLUMN shipping_postcode varchar(16);
+
+ALTER TABLE shipping_zone_rule
+	ADD CONSTRAINT shipping_zone_rule_zone_fk FOREIGN KEY (zone_id)
+	REFERENCES shipping_zone(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE shipping_method
+	ADD CONSTRAINT shipping_method_zone_fk FOREIGN KEY (zone_id)
+	REFERENCES shipping_zone(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE customer_order
+	ADD CONSTRAINT customer_order_shipping_method_fk FOREIGN KEY (shipping_method_id)
+	REFERENCES shipping_method(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+CREATE INDEX shipping_zone_rule_country_idx ON shipping_zone_rule(country);
@@ -0,0 +1,20 @@
+ALTER TABLE order_item
+	DROP COLUMN tax_amount,
+	DROP COLUMN net_amount,
+	DROP COLUMN tax_rate,
+	DROP COLUMN tax_class;
+
+ALTER TABLE customer_order
+	DROP COLUMN tax_breakdown,
+	DROP COLUMN tax_total,
+	DROP COLUMN net_total,
+	DROP COLUMN prices_include_tax,
+	DROP COLUMN reverse_charge,
+	DROP COLUMN vat_number,
+	DROP COLUMN tax_region,
+	DROP COLUMN tax_country;
+
+DROP TABLE tax_rate;
+
+ALTER TABLE product
+	DROP COLUMN tax_class;
@@ -0,0 +1,35 @@
+ALTER TABLE product
+	ADD COLUMN tax_class varchar(16) NOT NULL DEFAULT 'standard'
+		CHECK (tax_class IN ('standard', 'reduced', 'zero'));
+
+CREATE TABLE tax_rate (
+	id bigserial PRIMARY KEY,
+	country char(2) NOT NULL,
+	region varchar(16),
+	tax_class varchar(16) NOT NULL CHECK (tax_class IN ('standard', 'reduced', 'zero')),
+	rate decimal(5, 2) NOT NULL CHECK (rate >= 0),
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX tax_rate_country_region_class_idx
+	ON tax_rate(country, COALESCE(region, ''), tax_class);
+
+ALTER TABLE customer_order
+	ADD COLUMN tax_country char(2),
+	ADD COLUMN tax_region varchar(16),
+	ADD COLUMN vat_number varchar(16),
+	ADD COLUMN reverse_charge bool NOT NULL DEFAULT false,
+	ADD COLUMN prices_include_tax bool NOT NULL DEFAULT true,
+	ADD COLUMN net_total decimal(12, 2) NOT NULL DEFAULT 0,
+	ADD COLUMN tax_total decimal(12, 2) NOT NULL DEFAULT 0,
+	ADD COLUMN tax_breakdown jsonb NOT NULL DEFAULT '[]';
+
+ALTER TABLE order_item
+	ADD COLUMN tax_class varchar(16) NOT NULL DEFAULT 'standard',
+	ADD COLUMN tax_rate decimal(5, 2) NOT NULL DEFAULT 0,
+	ADD COLUMN net_amount decimal(12, 2) NOT NULL DEFAULT 0,
+	ADD COLUMN tax_amount decimal(12, 2) NOT NULL DEFAULT 0;
+
+UPDATE order_item SET net_amount = quantity * unit_price;
+UPDATE customer_order SET net_total = total;
@@ -0,0 +1,7 @@
+ALTER TABLE customer_order
+	DROP COLUMN billing_address,
+	DROP COLUMN shipping_address,
+	DROP COLUMN user_id;
+
+DROP TABLE user_address;
+DROP TABLE app_user;
@@ -0,0 +1,58 @@
+CREATE TABLE app_user (
+	id bigserial PRIMARY KEY,
+	email varchar(254) NOT NULL,
+	password_hash varchar(256) NOT NULL,
+	user_role varchar(16)[] NOT NULL DEFAULT '{customer}',
+	first_name varchar(128),
+	last_name varchar(128),
+	phone varchar(32),
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX app_user_email_idx ON app_user(LOWER(email));
+
+CREATE TABLE user_address (
+	id bigserial PRIMARY KEY,
+	user_id bigint NOT NULL,
+	label varchar(64),
+	first_name varchar(128) NOT NULL,
+	last_name varchar(128) NOT NULL,
+	company varchar(128),
+	line1 varchar(256) NOT NULL,
+	line2 varchar(256),
+	city varchar(128) NOT NULL,
+	postcode varchar(16) NOT NULL,
+	region varchar(16),
+	country char(2) NOT NULL,
+	phone varchar(32),
+	default_shipping bool NOT NULL DEFAULT false,
+	default_billing bool NOT NULL DEFAULT false,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE user_address
+	ADD CONSTRAINT user_address_user_fk FOREIGN KEY (user_id)
+	REFERENCES app_user(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+CREATE INDEX user_address_user_id_idx ON user_address(user_id);
+CREATE UNIQUE INDEX user_address_default_shipping_idx
+	ON user_address(user_id) WHERE default_shipping;
+CREATE UNIQUE INDEX user_address_default_billing_idx
+	ON user_address(user_id) WHERE default_billing;
+
+ALTER TABLE customer_order
+	ADD COLUMN user_id bigint,
+	ADD COLUMN shipping_address jsonb,
+	ADD COLUMN billing_address jsonb;
+
+ALTER TABLE customer_order
+	ADD CONSTRAINT customer_order_user_fk FOREIGN KEY (user_id)
+	REFERENCES app_user(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+CREATE INDEX customer_order_user_id_idx ON customer_order(user_id);
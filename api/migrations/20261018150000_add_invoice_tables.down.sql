@@ -0,0 +1,3 @@
+DROP TABLE invoice;
+DROP FUNCTION invoice_immutable();
+DROP TABLE shop_profile;
@@ -0,0 +1,41 @@
+CREATE TABLE shop_profile (
+	id smallint PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+	name varchar(128) NOT NULL,
+	line1 varchar(256),
+	line2 varchar(256),
+	city varchar(128),
+	postcode varchar(16),
+	country char(2) NOT NULL,
+	vat_number varchar(16),
+	email varchar(254),
+	iban varchar(34),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO shop_profile (name, country) VALUES ('Crabbyshop', 'SI');
+
+CREATE TABLE invoice (
+	id bigserial PRIMARY KEY,
+	number varchar(32) NOT NULL UNIQUE,
+	order_id bigint NOT NULL UNIQUE,
+	data jsonb NOT NULL,
+	pdf bytea NOT NULL,
+	xml text NOT NULL,
+	issued_at timestamp NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE invoice
+	ADD CONSTRAINT invoice_order_fk FOREIGN KEY (order_id)
+	REFERENCES customer_order(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+CREATE FUNCTION invoice_immutable() RETURNS trigger AS $$
+BEGIN
+	RAISE EXCEPTION 'invoice % is immutable once issued', OLD.number;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER invoice_immutable
+	BEFORE UPDATE OR DELETE ON invoice
+	FOR EACH ROW EXECUTE FUNCTION invoice_immutable();
@@ -0,0 +1,15 @@
+ALTER TABLE credit_note
+	DROP COLUMN currency;
+
+ALTER TABLE payment
+	DROP COLUMN currency;
+
+ALTER TABLE customer_order
+	DROP COLUMN exchange_rate,
+	DROP COLUMN currency;
+
+ALTER TABLE app_user
+	DROP COLUMN currency;
+
+DROP TABLE product_price;
+DROP TABLE currency;
@@ -0,0 +1,62 @@
+CREATE TABLE currency (
+	code char(3) PRIMARY KEY,
+	name varchar(64) NOT NULL,
+	minor_units smallint NOT NULL DEFAULT 2 CHECK (minor_units BETWEEN 0 AND 4),
+	-- Units of this currency per one unit of the base currency.
+	exchange_rate decimal(18, 8) NOT NULL CHECK (exchange_rate > 0),
+	is_base bool NOT NULL DEFAULT false,
+	active bool NOT NULL DEFAULT true,
+	updated_at timestamp NOT NULL DEFAULT NOW(),
+	CHECK (NOT is_base OR exchange_rate = 1)
+);
+
+CREATE UNIQUE INDEX currency_is_base_idx ON currency(is_base) WHERE is_base;
+
+INSERT INTO currency (code, name, minor_units, exchange_rate, is_base)
+VALUES ('EUR', 'Euro', 2, 1, true);
+
+CREATE TABLE product_price (
+	product_id bigint NOT NULL,
+	currency char(3) NOT NULL,
+	price decimal(19, 4) NOT NULL CHECK (price >= 0),
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (product_id, currency)
+);
+
+ALTER TABLE product_price
+	ADD CONSTRAINT product_price_product_fk FOREIGN KEY (product_id)
+	REFERENCES product(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE product_price
+	ADD CONSTRAINT product_price_currency_fk FOREIGN KEY (currency)
+	REFERENCES currency(code)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE app_user
+	ADD COLUMN currency char(3);
+
+ALTER TABLE app_user
+	ADD CONSTRAINT app_user_currency_fk FOREIGN KEY (currency)
+	REFERENCES currency(code)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+ALTER TABLE customer_order
+	ADD COLUMN currency char(3) NOT NULL DEFAULT 'EUR',
+	ADD COLUMN exchange_rate decimal(18, 8) NOT NULL DEFAULT 1;
+
+ALTER TABLE customer_order
+	ADD CONSTRAINT customer_order_currency_fk FOREIGN KEY (currency)
+	REFERENCES currency(code)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+ALTER TABLE payment
+	ADD COLUMN currency char(3) NOT NULL DEFAULT 'EUR';
+
+ALTER TABLE credit_note
+	ADD COLUMN currency char(3) NOT NULL DEFAULT 'EUR';
@@ -0,0 +1,46 @@
+-- decimal(5, 2) capped prices at 999.99. The flat view depends on the column
+-- and has to be recreated around the type change.
+DROP VIEW product_flat_view;
+
+ALTER TABLE product
+	ALTER COLUMN price TYPE decimal(19, 4);
+
+ALTER TABLE product
+	ADD CONSTRAINT product_price_check CHECK (price >= 0);
+
+CREATE VIEW product_flat_view AS 
+SELECT
+	p.id as "id!", p.name as "name!", p.description, p.sku, p.price,
+	p.category_id, p.inventory_id, p.discount_id,
+	p.created_at as "created_at!", p.updated_at as "updated_at!",
+
+	c.name AS category_name,
+	c.parent_id AS category_parent_id,
+
+	d.name AS discount_name,
+	d.description AS discount_description,
+	d.discount_percent AS discount_percent,
+	d.active  AS discount_active,
+	d.created_at AS discount_created_at,
+	d.updated_at AS discount_updated_at,
+
+	pi.quantity AS "inventory_quantity?",
+	pi.created_at AS "inventory_created_at?",
+	pi.updated_at AS "inventory_updated_at?"
+FROM product p
+LEFT JOIN category c ON c.id = category_id
+LEFT JOIN discount d ON d.id = discount_id
+LEFT JOIN product_inventory pi ON pi.id = inventory_id;
+
+-- Money is handed to clients as {"amount": "12.50", "currency": "EUR"}, the
+-- amount a string rounded to the currency's minor units.
+CREATE FUNCTION money_json(amount numeric, currency_code varchar)
+RETURNS jsonb
+LANGUAGE sql STABLE STRICT AS $$
+	SELECT jsonb_build_object(
+		'amount', ROUND(amount, c.minor_units)::text,
+		'currency', c.code
+	)
+	FROM currency c
+	WHERE c.code = currency_code;
+$$;
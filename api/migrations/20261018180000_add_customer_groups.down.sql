@@ -0,0 +1,6 @@
+DROP TABLE product_price_tier;
+
+ALTER TABLE app_user
+	DROP COLUMN customer_group_id;
+
+DROP TABLE customer_group;
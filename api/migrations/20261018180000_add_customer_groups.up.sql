@@ -0,0 +1,48 @@
+CREATE TABLE customer_group (
+	id bigserial PRIMARY KEY,
+	name varchar(128) NOT NULL UNIQUE,
+	description varchar(500),
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE app_user
+	ADD COLUMN customer_group_id bigint;
+
+ALTER TABLE app_user
+	ADD CONSTRAINT app_user_customer_group_fk FOREIGN KEY (customer_group_id)
+	REFERENCES customer_group(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
+
+-- A price for buying at least min_quantity units, for one customer group or,
+-- without a group, for everyone. Group overrides are tiers starting at 1.
+CREATE TABLE product_price_tier (
+	id bigserial PRIMARY KEY,
+	product_id bigint NOT NULL,
+	customer_group_id bigint,
+	currency char(3) NOT NULL,
+	min_quantity int NOT NULL DEFAULT 1 CHECK (min_quantity >= 1),
+	price decimal(19, 4) NOT NULL CHECK (price >= 0),
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	updated_at timestamp NOT NULL DEFAULT NOW(),
+	UNIQUE NULLS NOT DISTINCT (product_id, customer_group_id, currency, min_quantity)
+);
+
+ALTER TABLE product_price_tier
+	ADD CONSTRAINT product_price_tier_product_fk FOREIGN KEY (product_id)
+	REFERENCES product(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE product_price_tier
+	ADD CONSTRAINT product_price_tier_customer_group_fk FOREIGN KEY (customer_group_id)
+	REFERENCES customer_group(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+ALTER TABLE product_price_tier
+	ADD CONSTRAINT product_price_tier_currency_fk FOREIGN KEY (currency)
+	REFERENCES currency(code)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
@@ -0,0 +1,9 @@
+DROP FUNCTION product_lowest_price(bigint, char(3), int);
+
+DROP TRIGGER product_price_history_record_currency ON product_price;
+DROP FUNCTION product_price_history_record_currency();
+
+DROP TRIGGER product_price_history_record ON product;
+DROP FUNCTION product_price_history_record();
+
+DROP TABLE product_price_history;
@@ -0,0 +1,76 @@
+-- Every price a product has had, for the lowest price of the last 30 days
+-- shown next to discounts (EU Omnibus directive). currency is NULL for the
+-- catalog price in the base currency, set for per-currency prices. A NULL
+-- price means the product had none from then on.
+CREATE TABLE product_price_history (
+	id bigserial PRIMARY KEY,
+	product_id bigint NOT NULL,
+	currency char(3),
+	price decimal(19, 4),
+	changed_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX product_price_history_product_idx
+	ON product_price_history(product_id, currency, changed_at);
+
+ALTER TABLE product_price_history
+	ADD CONSTRAINT product_price_history_product_fk FOREIGN KEY (product_id)
+	REFERENCES product(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
+
+INSERT INTO product_price_history (product_id, currency, price, changed_at)
+SELECT id, NULL, price, updated_at FROM product WHERE price IS NOT NULL;
+
+INSERT INTO product_price_history (product_id, currency, price, changed_at)
+SELECT product_id, currency, price, updated_at FROM product_price;
+
+CREATE FUNCTION product_price_history_record() RETURNS trigger AS $$
+BEGIN
+	IF TG_OP = 'INSERT' OR NEW.price IS DISTINCT FROM OLD.price THEN
+		INSERT INTO product_price_history (product_id, currency, price)
+		VALUES (NEW.id, NULL, NEW.price);
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record
+	AFTER INSERT OR UPDATE OF price ON product
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record();
+
+CREATE FUNCTION product_price_history_record_currency() RETURNS trigger AS $$
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		-- Nothing to record when the product itself is being deleted.
+		INSERT INTO product_price_history (product_id, currency, price)
+		SELECT id, OLD.currency, NULL FROM product WHERE id = OLD.product_id;
+	ELSIF TG_OP = 'INSERT' OR NEW.price IS DISTINCT FROM OLD.price THEN
+		INSERT INTO product_price_history (product_id, currency, price)
+		VALUES (NEW.product_id, NEW.currency, NEW.price);
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record_currency
+	AFTER INSERT OR UPDATE OF price OR DELETE ON product_price
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record_currency();
+
+-- Lowest price of a product over the last days, including the price it
+-- already had when the period started.
+CREATE FUNCTION product_lowest_price(product_id bigint, currency_code char(3), days int)
+RETURNS numeric
+LANGUAGE sql STABLE AS $$
+	SELECT MIN(h.price)
+	FROM product_price_history h
+	WHERE h.product_id = $1
+		AND h.currency IS NOT DISTINCT FROM $2
+		AND h.changed_at >= COALESCE((
+			SELECT MAX(s.changed_at)
+			FROM product_price_history s
+			WHERE s.product_id = $1
+				AND s.currency IS NOT DISTINCT FROM $2
+				AND s.changed_at <= NOW() - make_interval(days => $3)
+		), '-infinity');
+$$;
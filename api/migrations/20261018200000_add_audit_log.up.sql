@@ -0,0 +1,27 @@
+CREATE TYPE audit_action AS ENUM (
+	'create',
+	'update',
+	'delete'
+);
+
+-- before and after hold only the fields that changed, the whole row for
+-- creates and deletes.
+CREATE TABLE audit_log (
+	id bigserial PRIMARY KEY,
+	actor_id bigint,
+	resource_type varchar(64) NOT NULL,
+	resource_id varchar(64) NOT NULL,
+	action audit_action NOT NULL,
+	before jsonb,
+	after jsonb,
+	created_at timestamp NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_log_resource_idx ON audit_log(resource_type, resource_id, created_at);
+CREATE INDEX audit_log_actor_idx ON audit_log(actor_id, created_at);
+
+ALTER TABLE audit_log
+	ADD CONSTRAINT audit_log_actor_fk FOREIGN KEY (actor_id)
+	REFERENCES app_user(id)
+		ON DELETE SET NULL
+		ON UPDATE CASCADE;
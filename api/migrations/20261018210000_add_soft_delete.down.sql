@@ -0,0 +1,28 @@
+-- Enum values can't be dropped, the type is recreated without 'restore'.
+DELETE FROM audit_log WHERE action = 'restore';
+
+ALTER TYPE audit_action RENAME TO audit_action_old;
+
+CREATE TYPE audit_action AS ENUM (
+	'create',
+	'update',
+	'delete'
+);
+
+ALTER TABLE audit_log
+	ALTER COLUMN action TYPE audit_action USING action::text::audit_action;
+
+DROP TYPE audit_action_old;
+
+DROP INDEX discount_deleted_at_idx;
+DROP INDEX category_deleted_at_idx;
+DROP INDEX product_deleted_at_idx;
+
+ALTER TABLE discount
+	DROP COLUMN deleted_at;
+
+ALTER TABLE category
+	DROP COLUMN deleted_at;
+
+ALTER TABLE product
+	DROP COLUMN deleted_at;
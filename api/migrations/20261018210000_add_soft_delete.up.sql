@@ -0,0 +1,16 @@
+-- Deleted catalog records stay in the trash until restored or purged after
+-- the retention period. Deleting a category moves its subtree along.
+ALTER TABLE product
+	ADD COLUMN deleted_at timestamp;
+
+ALTER TABLE category
+	ADD COLUMN deleted_at timestamp;
+
+ALTER TABLE discount
+	ADD COLUMN deleted_at timestamp;
+
+CREATE INDEX product_deleted_at_idx ON product(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX category_deleted_at_idx ON category(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX discount_deleted_at_idx ON discount(deleted_at) WHERE deleted_at IS NOT NULL;
+
+ALTER TYPE audit_action ADD VALUE 'restore';
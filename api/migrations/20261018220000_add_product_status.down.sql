@@ -0,0 +1,9 @@
+DROP INDEX product_unpublish_at_idx;
+DROP INDEX product_publish_at_idx;
+
+ALTER TABLE product
+	DROP COLUMN unpublish_at,
+	DROP COLUMN publish_at,
+	DROP COLUMN status;
+
+DROP TYPE product_status;
@@ -0,0 +1,18 @@
+CREATE TYPE product_status AS ENUM (
+	'draft',
+	'published',
+	'archived'
+);
+
+-- Products created before statuses existed stay visible, new ones start as
+-- drafts.
+ALTER TABLE product
+	ADD COLUMN status product_status NOT NULL DEFAULT 'published',
+	ADD COLUMN publish_at timestamp,
+	ADD COLUMN unpublish_at timestamp;
+
+ALTER TABLE product
+	ALTER COLUMN status SET DEFAULT 'draft';
+
+CREATE INDEX product_publish_at_idx ON product(publish_at) WHERE publish_at IS NOT NULL;
+CREATE INDEX product_unpublish_at_idx ON product(unpublish_at) WHERE unpublish_at IS NOT NULL;
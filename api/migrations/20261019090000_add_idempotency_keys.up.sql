@@ -0,0 +1,24 @@
+-- Responses to POST requests made with an Idempotency-Key header, replayed
+-- when the request is retried until expires_at. A row without a status is
+-- a request still being handled. Guests (user_id NULL) share their keys.
+CREATE TABLE idempotency_key (
+	id bigserial PRIMARY KEY,
+	user_id bigint,
+	idempotency_key text NOT NULL,
+	request_hash text NOT NULL,
+	status_code smallint,
+	content_type text,
+	response_body bytea,
+	created_at timestamp NOT NULL DEFAULT NOW(),
+	expires_at timestamp NOT NULL
+);
+
+CREATE UNIQUE INDEX idempotency_key_key_idx
+	ON idempotency_key((COALESCE(user_id, 0)), idempotency_key);
+CREATE INDEX idempotency_key_expires_at_idx ON idempotency_key(expires_at);
+
+ALTER TABLE idempotency_key
+	ADD CONSTRAINT idempotency_key_user_fk FOREIGN KEY (user_id)
+	REFERENCES app_user(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
@@ -0,0 +1,7 @@
+DELETE FROM payment_refund WHERE status = 'pending';
+
+ALTER TABLE payment_refund
+	DROP COLUMN status,
+	ALTER COLUMN reference SET NOT NULL;
+
+DROP TYPE refund_status;
@@ -0,0 +1,12 @@
+-- Refunds are recorded as pending before the payment provider is asked to
+-- make them, and completed with the provider's reference afterwards.
+CREATE TYPE refund_status AS ENUM (
+	'pending',
+	'succeeded'
+);
+
+ALTER TABLE payment_refund
+	ADD COLUMN status refund_status NOT NULL DEFAULT 'succeeded',
+	ALTER COLUMN reference DROP NOT NULL;
+
+CREATE INDEX payment_refund_pending_idx ON payment_refund(payment_id) WHERE status = 'pending';
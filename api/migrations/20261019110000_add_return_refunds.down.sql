@@ -0,0 +1,5 @@
+ALTER TABLE credit_note
+	DROP COLUMN refund_id;
+
+ALTER TABLE payment_refund
+	DROP COLUMN return_id;
@@ -0,0 +1,21 @@
+-- A return can be refunded in several parts, each refund of a payment
+-- issues its own credit note.
+ALTER TABLE payment_refund
+	ADD COLUMN return_id bigint;
+
+ALTER TABLE credit_note
+	ADD COLUMN refund_id bigint UNIQUE;
+
+ALTER TABLE payment_refund
+	ADD CONSTRAINT payment_refund_return_fk FOREIGN KEY (return_id)
+	REFERENCES order_return(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+ALTER TABLE credit_note
+	ADD CONSTRAINT credit_note_refund_fk FOREIGN KEY (refund_id)
+	REFERENCES payment_refund(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
+
+CREATE INDEX payment_refund_return_id_idx ON payment_refund(return_id);
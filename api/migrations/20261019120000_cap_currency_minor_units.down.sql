@@ -0,0 +1,3 @@
+ALTER TABLE currency
+	DROP CONSTRAINT currency_minor_units_check,
+	ADD CONSTRAINT currency_minor_units_check CHECK (minor_units BETWEEN 0 AND 4);
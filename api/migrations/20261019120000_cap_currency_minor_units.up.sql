@@ -0,0 +1,5 @@
+-- Order, payment and credit note amounts are stored as decimal(12, 2), a
+-- currency with more minor units would lose them.
+ALTER TABLE currency
+	DROP CONSTRAINT currency_minor_units_check,
+	ADD CONSTRAINT currency_minor_units_check CHECK (minor_units BETWEEN 0 AND 2);
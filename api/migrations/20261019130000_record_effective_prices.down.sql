@@ -0,0 +1,61 @@
+CREATE OR REPLACE FUNCTION product_lowest_price(product_id bigint, currency_code char(3), days int)
+RETURNS numeric
+LANGUAGE sql STABLE AS $$
+	SELECT MIN(h.price)
+	FROM product_price_history h
+	WHERE h.product_id = $1
+		AND h.currency IS NOT DISTINCT FROM $2
+		AND h.changed_at >= COALESCE((
+			SELECT MAX(s.changed_at)
+			FROM product_price_history s
+			WHERE s.product_id = $1
+				AND s.currency IS NOT DISTINCT FROM $2
+				AND s.changed_at <= NOW() - make_interval(days => $3)
+		), '-infinity');
+$$;
+
+DROP TRIGGER product_price_history_record_discount ON discount;
+DROP FUNCTION product_price_history_record_discount();
+
+DROP TRIGGER product_price_history_record_currency ON product_price;
+DROP FUNCTION product_price_history_record_currency();
+
+DROP TRIGGER product_price_history_record ON product;
+DROP FUNCTION product_price_history_record();
+
+DROP FUNCTION product_price_history_sync(bigint);
+
+CREATE FUNCTION product_price_history_record() RETURNS trigger AS $$
+BEGIN
+	IF TG_OP = 'INSERT' OR NEW.price IS DISTINCT FROM OLD.price THEN
+		INSERT INTO product_price_history (product_id, currency, price)
+		VALUES (NEW.id, NULL, NEW.price);
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record
+	AFTER INSERT OR UPDATE OF price ON product
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record();
+
+CREATE FUNCTION product_price_history_record_currency() RETURNS trigger AS $$
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		-- Nothing to record when the product itself is being deleted.
+		INSERT INTO product_price_history (product_id, currency, price)
+		SELECT id, OLD.currency, NULL FROM product WHERE id = OLD.product_id;
+	ELSIF TG_OP = 'INSERT' OR NEW.price IS DISTINCT FROM OLD.price THEN
+		INSERT INTO product_price_history (product_id, currency, price)
+		VALUES (NEW.product_id, NEW.currency, NEW.price);
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record_currency
+	AFTER INSERT OR UPDATE OF price OR DELETE ON product_price
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record_currency();
+
+ALTER TABLE product_price_history
+	DROP COLUMN discount_percent;
@@ -0,0 +1,112 @@
+-- The lowest price shown next to a discount has to be the lowest price
+-- shoppers actually paid, so the history records prices with the active
+-- discount applied, and records them again whenever the discount changes.
+ALTER TABLE product_price_history
+	ADD COLUMN discount_percent decimal(5, 2);
+
+DROP TRIGGER product_price_history_record ON product;
+DROP FUNCTION product_price_history_record();
+
+DROP TRIGGER product_price_history_record_currency ON product_price;
+DROP FUNCTION product_price_history_record_currency();
+
+-- Records the current catalog and per-currency prices of a product, with its
+-- active discount applied, where they differ from the last recorded ones.
+CREATE FUNCTION product_price_history_sync(product_id bigint) RETURNS void
+LANGUAGE sql AS $$
+	INSERT INTO product_price_history (product_id, currency, price, discount_percent)
+	SELECT c.product_id, c.currency, c.price * (1 - COALESCE(c.discount_percent, 0) / 100),
+		c.discount_percent
+	FROM (
+		SELECT p.id as product_id, NULL::char(3) as currency, p.price, d.discount_percent
+		FROM product p
+		LEFT JOIN discount d ON d.id = p.discount_id
+			AND d.active AND d.deleted_at IS NULL AND d.discount_percent > 0
+		WHERE p.id = $1
+		UNION ALL
+		SELECT pp.product_id, pp.currency, pp.price, d.discount_percent
+		FROM product_price pp
+		JOIN product p ON p.id = pp.product_id
+		LEFT JOIN discount d ON d.id = p.discount_id
+			AND d.active AND d.deleted_at IS NULL AND d.discount_percent > 0
+		WHERE pp.product_id = $1
+	) c
+	LEFT JOIN LATERAL (
+		SELECT h.price, h.discount_percent
+		FROM product_price_history h
+		WHERE h.product_id = c.product_id AND h.currency IS NOT DISTINCT FROM c.currency
+		ORDER BY h.id DESC
+		LIMIT 1
+	) last ON true
+	WHERE (c.price * (1 - COALESCE(c.discount_percent, 0) / 100), c.discount_percent)
+		IS DISTINCT FROM (last.price, last.discount_percent);
+$$;
+
+CREATE FUNCTION product_price_history_record() RETURNS trigger AS $$
+BEGIN
+	PERFORM product_price_history_sync(NEW.id);
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record
+	AFTER INSERT OR UPDATE OF price, discount_id ON product
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record();
+
+CREATE FUNCTION product_price_history_record_currency() RETURNS trigger AS $$
+BEGIN
+	IF TG_OP = 'DELETE' THEN
+		-- Nothing to record when the product itself is being deleted.
+		INSERT INTO product_price_history (product_id, currency, price)
+		SELECT id, OLD.currency, NULL FROM product WHERE id = OLD.product_id;
+	ELSE
+		PERFORM product_price_history_sync(NEW.product_id);
+	END IF;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record_currency
+	AFTER INSERT OR UPDATE OF price OR DELETE ON product_price
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record_currency();
+
+CREATE FUNCTION product_price_history_record_discount() RETURNS trigger AS $$
+BEGIN
+	PERFORM product_price_history_sync(p.id) FROM product p WHERE p.discount_id = NEW.id;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER product_price_history_record_discount
+	AFTER UPDATE OF active, discount_percent, deleted_at ON discount
+	FOR EACH ROW EXECUTE FUNCTION product_price_history_record_discount();
+
+SELECT product_price_history_sync(id) FROM product;
+
+-- Lowest price of a product over the given days before its current discount
+-- started, including the price it already had when the period started. A
+-- discount that is changed while active keeps its start, later reductions
+-- are compared with the price before the first one.
+CREATE OR REPLACE FUNCTION product_lowest_price(product_id bigint, currency_code char(3), days int)
+RETURNS numeric
+LANGUAGE sql STABLE AS $$
+	WITH history AS (
+		SELECT h.id, h.price, h.discount_percent, h.changed_at
+		FROM product_price_history h
+		WHERE h.product_id = $1 AND h.currency IS NOT DISTINCT FROM $2
+	),
+	discount_start AS (
+		SELECT COALESCE(MIN(h.id), 'infinity'::numeric) as id,
+			COALESCE(MIN(h.changed_at), NOW()::timestamp) as changed_at
+		FROM history h
+		WHERE h.id > COALESCE((SELECT MAX(id) FROM history WHERE discount_percent IS NULL), 0)
+	)
+	SELECT MIN(h.price)
+	FROM history h, discount_start s
+	WHERE h.id < s.id
+		AND h.changed_at >= COALESCE((
+			SELECT MAX(b.changed_at)
+			FROM history b
+			WHERE b.changed_at <= s.changed_at - make_interval(days => $3)
+		), '-infinity');
+$$;
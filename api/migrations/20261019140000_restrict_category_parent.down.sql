@@ -0,0 +1,6 @@
+ALTER TABLE category
+	DROP CONSTRAINT category_parent_fk,
+	ADD CONSTRAINT category_parent_fk FOREIGN KEY (parent_id)
+	REFERENCES category(id)
+		ON DELETE CASCADE
+		ON UPDATE CASCADE;
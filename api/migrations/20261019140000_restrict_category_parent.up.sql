@@ -0,0 +1,7 @@
+-- Purging a trashed category must not take live subcategories with it.
+ALTER TABLE category
+	DROP CONSTRAINT category_parent_fk,
+	ADD CONSTRAINT category_parent_fk FOREIGN KEY (parent_id)
+	REFERENCES category(id)
+		ON DELETE RESTRICT
+		ON UPDATE CASCADE;
@@ -0,0 +1,2 @@
+ALTER TABLE product
+	DROP CONSTRAINT product_schedule_check;
@@ -0,0 +1,3 @@
+-- A product can't be archived before it's published.
+ALTER TABLE product
+	ADD CONSTRAINT product_schedule_check CHECK (publish_at < unpublish_at);
@@ -0,0 +1,2 @@
+ALTER TABLE customer_order
+	DROP COLUMN access_token_hash;
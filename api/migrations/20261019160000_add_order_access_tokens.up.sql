@@ -0,0 +1,4 @@
+-- Guests pay for and return their orders with a secret handed out at
+-- checkout, only its SHA-256 hash is kept.
+ALTER TABLE customer_order
+	ADD COLUMN access_token_hash char(64);
@@ -0,0 +1,57 @@
+-- Demo catalog loaded by `crabbyshop seed`: a few published products with
+-- stock, a running discount, Slovenian VAT rates and shipping.
+INSERT INTO category (name) VALUES ('Apparel'), ('Kitchen'), ('Books');
+
+INSERT INTO category (name, parent_id)
+SELECT 'T-shirts', id FROM category WHERE name = 'Apparel';
+
+INSERT INTO discount (name, description, discount_percent, active)
+VALUES ('Summer sale', 'Ten percent off selected products', 10, true);
+
+DO $$
+DECLARE
+	item record;
+	new_inventory_id bigint;
+BEGIN
+	FOR item IN
+		SELECT * FROM (VALUES
+			('Crab T-shirt', 'Organic cotton T-shirt with the crabbyshop crab.', 'TS-CRAB-M', 'T-shirts', 19.90, 120, 0.180, 'standard', true),
+			('Rusty hoodie', 'Warm hoodie in rust orange.', 'HD-RUST-L', 'Apparel', 49.00, 40, 0.650, 'standard', false),
+			('Ferris mug', 'Ceramic mug, 330 ml.', 'MG-FERRIS', 'Kitchen', 12.50, 200, 0.350, 'standard', true),
+			('Borrow checker apron', 'Apron for cooking without data races.', 'AP-BORROW', 'Kitchen', 24.00, 0, 0.250, 'standard', false),
+			('The crab cookbook', 'Recipes from the crabbyshop kitchen.', 'BK-COOK', 'Books', 29.00, 35, 0.900, 'reduced', false)
+		) AS seed(name, description, sku, category, price, quantity, weight, tax_class, discounted)
+	LOOP
+		INSERT INTO product_inventory (quantity)
+		VALUES (item.quantity)
+		RETURNING id INTO new_inventory_id;
+
+		INSERT INTO product (name, description, sku, category_id, inventory_id, discount_id, price,
+			weight, tax_class, status)
+		VALUES (
+			item.name, item.description, item.sku,
+			(SELECT id FROM category WHERE name = item.category),
+			new_inventory_id,
+			CASE WHEN item.discounted THEN (SELECT id FROM discount WHERE name = 'Summer sale') END,
+			item.price, item.weight, item.tax_class, 'published'
+		);
+	END LOOP;
+END $$;
+
+INSERT INTO tax_rate (country, tax_class, rate)
+VALUES ('SI', 'standard', 22), ('SI', 'reduced', 9.5), ('SI', 'zero', 0)
+ON CONFLICT DO NOTHING;
+
+INSERT INTO shipping_zone (name) VALUES ('Slovenia');
+
+INSERT INTO shipping_zone_rule (zone_id, country)
+SELECT id, 'SI' FROM shipping_zone WHERE name = 'Slovenia';
+
+INSERT INTO shipping_method (name, kind, zone_id, price, threshold)
+SELECT method.name, method.kind::shipping_method_kind, zone.id, method.price, method.threshold
+FROM shipping_zone zone, (VALUES
+	('Parcel', 'free_over_threshold', 4.90, 50),
+	('Express parcel', 'flat_rate', 9.90, NULL),
+	('Pickup in store', 'local_pickup', 0, NULL)
+) AS method(name, kind, price, threshold)
+WHERE zone.name = 'Slovenia';
@@ -1,13 +1,51 @@
-use jsonwebtoken::DecodingKey;
-use rocket::fairing::AdHoc;
-
-pub struct JwtPublicKey(pub DecodingKey);
-
-pub fn stage() -> AdHoc {
-	AdHoc::on_ignite("Authentication stage", |rocket| async {
-		rocket
-			.manage(JwtPublicKey(DecodingKey::from_rsa_pem(include_bytes!("../jwtRSA256-public.pem"))
-					.expect("error extracting JWT public key"))
-			)
-	})
-}
\ No newline at end of file
+use chrono::{Duration, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header};
+use serde::Serialize;
+
+use crate::models::authentication::JwtToken;
+use crate::openapi::ApiSchema;
+
+/// How long an access token issued at login stays valid.
+const TOKEN_LIFETIME_HOURS: i64 = 24;
+
+/// Keys used to sign and verify access tokens.
+#[derive(Clone)]
+pub struct JwtKeys {
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct AccessToken {
+    pub token: String,
+    pub token_type: &'static str,
+    pub expires_at: i64,
+}
+
+impl JwtKeys {
+    pub fn from_secret(secret: &[u8]) -> JwtKeys {
+        JwtKeys {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    pub fn issue(&self, user_id: i64, user_role: Vec<String>) -> Result<AccessToken, String> {
+        let now = Utc::now();
+        let exp = (now + Duration::hours(TOKEN_LIFETIME_HOURS)).timestamp();
+        let claims = JwtToken {
+            sub: user_id,
+            iat: now.timestamp(),
+            exp,
+            user_role,
+        };
+
+        jsonwebtoken::encode(&Header::default(), &claims, &self.encoding)
+            .map(|token| AccessToken {
+                token,
+                token_type: "Bearer",
+                expires_at: exp,
+            })
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,376 @@
+//! The `crabbyshop` command: runs the server and manages its database.
+
+use clap::{CommandFactory, ErrorKind, Parser, Subcommand};
+use sqlx::{Executor, PgPool};
+use std::io::BufRead;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+use crate::config::Config;
+use crate::jobs::Jobs;
+use crate::models::user::User;
+use crate::{logging, migrations};
+
+/// Catalog loaded by `crabbyshop seed`.
+const DEMO_SEED: &str = include_str!("../seeds/demo.sql");
+
+#[derive(Parser, Debug)]
+#[clap(name = "crabbyshop", version, about = "The crabbyshop API server")]
+pub struct Cli {
+    /// Config file of `NAME=value` lines, the environment takes precedence
+    #[clap(long = "config", env = "CRABBYSHOP_CONFIG", default_value = ".env")]
+    pub config_file: PathBuf,
+
+    /// Print the effective configuration, secrets masked, and exit
+    #[clap(long)]
+    pub print_config: bool,
+
+    #[clap(flatten)]
+    pub config: Config,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the API server, the default when no command is given
+    Serve,
+
+    /// Apply, revert or list the database migrations
+    Migrate {
+        #[clap(subcommand)]
+        command: MigrateCommand,
+    },
+
+    /// Load a demo catalog into an empty database
+    Seed,
+
+    /// Create a user with the admin role
+    CreateAdmin {
+        #[clap(long)]
+        email: String,
+
+        /// Read from standard input when not given
+        #[clap(long, env = "ADMIN_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+
+    /// Check the database connection and the schema version, failing when
+    /// migrations are pending
+    Check,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+    /// Apply the pending migrations
+    Up,
+
+    /// Revert the latest migration
+    Down {
+        /// Revert every migration newer than this version instead
+        #[clap(long)]
+        to: Option<i64>,
+    },
+
+    /// List the migrations and whether they're applied
+    Status,
+}
+
+impl Cli {
+    /// Loads the config file into the environment and parses the command
+    /// line, exiting with a usage error when a setting is invalid.
+    pub fn load() -> Cli {
+        let explicit_file = config_file_arg(std::env::args())
+            .or_else(|| std::env::var_os("CRABBYSHOP_CONFIG").map(PathBuf::from));
+        let file = explicit_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(".env"));
+
+        // Variables already in the environment aren't overridden.
+        match dotenv::from_path(&file) {
+            Ok(()) => {}
+            Err(dotenv::Error::Io(e))
+                if e.kind() == std::io::ErrorKind::NotFound && explicit_file.is_none() => {}
+            Err(e) => Cli::command()
+                .error(
+                    ErrorKind::Io,
+                    format!("Can't read config file {}: {}", file.display(), e),
+                )
+                .exit(),
+        }
+
+        let cli = Cli::parse();
+        if let Err(e) = cli.config.validate() {
+            Cli::command().error(ErrorKind::ValueValidation, e).exit();
+        }
+
+        cli
+    }
+
+    pub async fn run(self) -> Result<(), String> {
+        let config = self.config;
+        if self.print_config {
+            print!("{}", config);
+            return Ok(());
+        }
+
+        let exporter = logging::init(&config)?;
+
+        let result = match self.command.unwrap_or(Command::Serve) {
+            Command::Serve => serve(&config).await,
+            Command::Migrate { command } => migrate(&config, command).await,
+            Command::Seed => seed(&config).await,
+            Command::CreateAdmin { email, password } => {
+                create_admin(&config, &email, password).await
+            }
+            Command::Check => check(&config).await,
+        };
+
+        if let Some(exporter) = exporter {
+            exporter.shutdown().await;
+        }
+
+        result
+    }
+}
+
+async fn connect(config: &Config) -> Result<PgPool, String> {
+    config
+        .pool_options()
+        .connect(&config.database_url)
+        .await
+        .map_err(|e| format!("Can't connect to database: {}", e))
+}
+
+async fn pending_migrations(pool: &PgPool) -> Result<usize, String> {
+    Ok(migrations::status(pool)
+        .await?
+        .iter()
+        .filter(|m| !m.applied)
+        .count())
+}
+
+async fn serve(config: &Config) -> Result<(), String> {
+    let pool = connect(config).await?;
+
+    let pending = pending_migrations(&pool).await?;
+    if pending > 0 && !config.auto_migrate {
+        return Err(format!(
+            "{} migrations are pending, apply them with `crabbyshop migrate up`",
+            pending
+        ));
+    }
+    if pending > 0 {
+        migrations::run(&pool).await?;
+        tracing::info!("Applied {} migrations", pending);
+    }
+
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    let jobs = Jobs::spawn(&pool, config, shutdown_rx.clone());
+    let app = crate::create_app(config, pool.clone()).await;
+
+    tracing::debug!("Server is running and listening on: {}", config.bind);
+    let grace_period = Duration::from_secs(config.shutdown_grace_period);
+    let server = axum::Server::bind(&config.bind)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            tracing::info!(
+                "Shutting down, draining requests for up to {}s",
+                grace_period.as_secs()
+            );
+            let _ = shutdown_tx.send(true);
+        });
+
+    // Requests still running after the grace period are dropped with the
+    // server.
+    let result = tokio::select! {
+        result = server => result.map_err(|e| e.to_string()),
+        _ = async {
+            let _ = shutdown_rx.changed().await;
+            tokio::time::sleep(grace_period).await;
+        } => {
+            tracing::warn!("Grace period is over, dropping the remaining connections");
+            Ok(())
+        }
+    };
+
+    jobs.join().await;
+    pool.close().await;
+    tracing::info!("Stopped");
+
+    result
+}
+
+/// Resolves on SIGINT, or SIGTERM where there is one.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Can't listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Can't listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+async fn migrate(config: &Config, command: MigrateCommand) -> Result<(), String> {
+    let pool = connect(config).await?;
+
+    match command {
+        MigrateCommand::Up => {
+            let applied = migrations::run(&pool).await?;
+            println!("Applied {} migrations", applied);
+        }
+        MigrateCommand::Down { to } => {
+            let reverted = migrations::revert(&pool, to).await?;
+            for version in &reverted {
+                println!("Reverted {}", version);
+            }
+            if reverted.is_empty() {
+                println!("Nothing to revert");
+            }
+        }
+        MigrateCommand::Status => {
+            for m in migrations::status(&pool).await? {
+                let state = match (m.applied, m.changed) {
+                    (true, true) => "changed",
+                    (true, false) => "applied",
+                    (false, _) => "pending",
+                };
+                println!("{} {:<8} {}", m.version, state, m.description);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn seed(config: &Config) -> Result<(), String> {
+    let pool = connect(config).await?;
+    if pending_migrations(&pool).await? > 0 {
+        return Err("Apply the pending migrations first".to_string());
+    }
+
+    let products = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM product"#)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if products > 0 {
+        return Err("The catalog already has products, seed an empty database".to_string());
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    (&mut *tx)
+        .execute(DEMO_SEED)
+        .await
+        .map_err(|e| e.to_string())?;
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    println!("Loaded the demo catalog");
+    Ok(())
+}
+
+async fn create_admin(
+    config: &Config,
+    email: &str,
+    password: Option<String>,
+) -> Result<(), String> {
+    if !validator::validate_email(email) {
+        return Err(format!("{} isn't a valid email address", email));
+    }
+
+    let password = match password {
+        Some(password) => password,
+        None => {
+            eprintln!("Password:");
+            let mut line = String::new();
+            std::io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(|e| e.to_string())?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if !(8..=128).contains(&password.chars().count()) {
+        return Err("The password has to be between 8 and 128 characters".to_string());
+    }
+
+    let pool = connect(config).await?;
+    let user = User::create_admin(email, &password, &pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    println!("Created admin {} with id {}", user.email, user.id);
+    Ok(())
+}
+
+async fn check(config: &Config) -> Result<(), String> {
+    let pool = connect(config).await?;
+
+    let version = sqlx::query_scalar!(r#"SELECT current_setting('server_version') as "version!""#)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    println!("Database: PostgreSQL {}", version);
+
+    let status = migrations::status(&pool).await?;
+    match status.iter().rev().find(|m| m.applied) {
+        Some(m) => println!("Schema version: {} ({})", m.version, m.description),
+        None => println!("Schema version: none"),
+    }
+
+    if let Some(m) = status.iter().find(|m| m.changed) {
+        return Err(format!(
+            "Migration {} was changed after it was applied",
+            m.version
+        ));
+    }
+
+    let pending = status.iter().filter(|m| !m.applied).count();
+    if pending > 0 {
+        return Err(format!("{} migrations are pending", pending));
+    }
+
+    println!("Up to date");
+    Ok(())
+}
+
+/// The value of `--config` on the command line, which has to be known
+/// before the rest is parsed since it provides defaults for the rest.
+fn config_file_arg(args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    None
+}
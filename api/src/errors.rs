@@ -1,15 +1,20 @@
 use axum::{http::StatusCode, Json};
 use serde::Serialize;
-use validator::{ValidationError, ValidationErrors};
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
 
-#[derive(Serialize, Debug)]
+use crate::logging;
+use crate::openapi::ApiSchema;
+
+#[derive(Serialize, Debug, ApiSchema)]
 pub struct ApiError {
     pub status: String,
     pub reason: String,
     pub validation_errors: Option<Vec<FieldError>>,
+    /// Echoed in the `X-Request-Id` header and in the server logs.
+    pub request_id: Option<String>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ApiSchema)]
 pub struct FieldError {
     pub field: String,
     pub errors: Vec<String>,
@@ -31,6 +36,30 @@ impl FieldError {
                 .collect(),
         }
     }
+
+    /// Flattens nested struct and list errors into fields like `tax.country`
+    /// and `items[0].quantity`.
+    fn collect(prefix: &str, validation_errors: &ValidationErrors, out: &mut Vec<FieldError>) {
+        for (field, kind) in validation_errors.errors() {
+            let path = if prefix.is_empty() {
+                field.to_string()
+            } else {
+                format!("{}.{}", prefix, field)
+            };
+
+            match kind {
+                ValidationErrorsKind::Field(errors) => {
+                    out.push(FieldError::from_validation_error(&path, errors))
+                }
+                ValidationErrorsKind::Struct(errors) => FieldError::collect(&path, errors, out),
+                ValidationErrorsKind::List(errors) => {
+                    for (index, errors) in errors {
+                        FieldError::collect(&format!("{}[{}]", path, index), errors, out);
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl ApiError {
@@ -39,6 +68,7 @@ impl ApiError {
             status: status.to_string(),
             reason,
             validation_errors: None,
+            request_id: logging::current_request_id(),
         }
     }
 
@@ -47,11 +77,14 @@ impl ApiError {
         reason: String,
         validation_errors: Option<Vec<FieldError>>,
     ) -> Json<ApiError> {
-        Json(ApiError {
-            status: status.to_string(),
-            reason,
-            validation_errors,
-        })
+        let mut error = ApiError::new(status, reason);
+        error.validation_errors = validation_errors;
+
+        Json(error)
+    }
+
+    pub fn with_status(status: StatusCode, reason: &str) -> (StatusCode, Json<ApiError>) {
+        (status, ApiError::new_json(status, reason.to_string(), None))
     }
 
     pub fn internal_server_error(reason: &str) -> (StatusCode, Json<ApiError>) {
@@ -72,22 +105,20 @@ impl ApiError {
         (status, ApiError::new_json(status, reason.to_string(), None))
     }
 
+    pub fn conflict(reason: &str) -> (StatusCode, Json<ApiError>) {
+        let status = StatusCode::CONFLICT;
+
+        (status, ApiError::new_json(status, reason.to_string(), None))
+    }
+
     pub fn validation_error(validation_errors: ValidationErrors) -> (StatusCode, Json<ApiError>) {
         let status = StatusCode::BAD_REQUEST;
+        let mut field_errors = Vec::new();
+        FieldError::collect("", &validation_errors, &mut field_errors);
 
         (
             status,
-            ApiError::new_json(
-                status,
-                String::new(),
-                Some(
-                    validation_errors
-                        .field_errors()
-                        .into_iter()
-                        .map(|field| FieldError::from_validation_error(field.0, field.1))
-                        .collect(),
-                ),
-            ),
+            ApiError::new_json(status, String::new(), Some(field_errors)),
         )
     }
 }
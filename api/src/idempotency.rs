@@ -0,0 +1,185 @@
+//! `Idempotency-Key` support, so that clients can retry a POST without
+//! creating the same record twice.
+
+use axum::body::{boxed, Body, Full};
+use axum::extract::{FromRequest, RequestParts};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderValue, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::authentication::JwtToken;
+use crate::models::idempotency::{Claim, IdempotencyKey, StoredResponse};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Set on responses replayed from an earlier request with the same key.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Handles a POST with an `Idempotency-Key` header once per key and user,
+/// guests' keys have to be UUIDs as they share one namespace: retries get the stored response for `IDEMPOTENCY_TTL_HOURS`, a retry
+/// while the first request is still running gets 409 and a request with a
+/// reused key but another method, path or body gets 422. Failed requests
+/// (5xx) aren't stored so that they can be retried.
+pub async fn idempotent(request: Request<Body>, next: Next<Body>) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        _ => {
+            return ApiError::bad_request(
+                "Idempotency-Key has to be between 1 and 255 visible ASCII characters",
+            )
+            .into_response()
+        }
+    };
+    let (Some(pool), Some(ttl_hours)) = (
+        request.extensions().get::<PgPool>().cloned(),
+        request
+            .extensions()
+            .get::<Config>()
+            .map(|c| c.idempotency_ttl_hours),
+    ) else {
+        return next.run(request).await;
+    };
+
+    let mut parts = RequestParts::new(request);
+    let user_id = JwtToken::from_request(&mut parts).await.ok().map(|t| t.sub);
+    if user_id.is_none() && !is_uuid(&key) {
+        return ApiError::bad_request("Idempotency-Key of a guest has to be a UUID")
+            .into_response();
+    }
+    let (parts, body) = parts
+        .try_into_request()
+        .expect("Body taken before the idempotency check")
+        .into_parts();
+
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => return ApiError::bad_request(&e.to_string()).into_response(),
+    };
+    let request_hash = hex::encode(
+        Sha256::new()
+            .chain_update(parts.method.as_str())
+            .chain_update(parts.uri.path())
+            .chain_update(&body)
+            .finalize(),
+    );
+
+    match IdempotencyKey::claim(user_id, &key, &request_hash, ttl_hours, &pool).await {
+        Ok(Claim::Claimed) => {}
+        Ok(Claim::InProgress) => {
+            return ApiError::conflict("A request with this Idempotency-Key is still being handled")
+                .into_response()
+        }
+        Ok(Claim::Mismatch) => {
+            return ApiError::with_status(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "This Idempotency-Key was used for a different request",
+            )
+            .into_response()
+        }
+        Ok(Claim::Completed(stored)) => return replay(stored),
+        Err(e) => return ApiError::internal_server_error(&e).into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    if response.status().is_server_error() {
+        release(user_id, &key, &pool).await;
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            release(user_id, &key, &pool).await;
+            return ApiError::internal_server_error(&e.to_string()).into_response();
+        }
+    };
+
+    let stored = StoredResponse {
+        status_code: parts.status.as_u16() as i16,
+        content_type: parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        body: body.to_vec(),
+    };
+    if let Err(e) = IdempotencyKey::complete(user_id, &key, &stored, &pool).await {
+        tracing::error!(
+            "Can't store the response for Idempotency-Key {}: {}",
+            key,
+            e
+        );
+    }
+
+    Response::from_parts(parts, boxed(Full::from(body)))
+}
+
+async fn release(user_id: Option<i64>, key: &str, pool: &PgPool) {
+    if let Err(e) = IdempotencyKey::release(user_id, key, pool).await {
+        tracing::error!("Can't release Idempotency-Key {}: {}", key, e);
+    }
+}
+
+/// Whether `key` is a UUID like `123e4567-e89b-42d3-a456-426614174000`,
+/// random enough that guests don't pick each other's keys.
+fn is_uuid(key: &str) -> bool {
+    key.len() == 36
+        && key.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::builder()
+        .status(StatusCode::from_u16(stored.status_code as u16).unwrap_or(StatusCode::OK))
+        .header(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|c| HeaderValue::from_str(&c).ok())
+    {
+        response = response.header(CONTENT_TYPE, content_type);
+    }
+
+    response.body(boxed(Full::from(stored.body))).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_uuid;
+
+    #[test]
+    fn accepts_uuids() {
+        assert!(is_uuid("123e4567-e89b-42d3-a456-426614174000"));
+        assert!(is_uuid("123E4567-E89B-42D3-A456-426614174000"));
+    }
+
+    #[test]
+    fn rejects_other_keys() {
+        for key in [
+            "order-1",
+            "123e4567e89b42d3a456426614174000",
+            "123e4567-e89b-42d3-a456-42661417400",
+            "123e4567-e89b-42d3-a456-4266141740000",
+            "123e4567-e89b-42d3-a456_426614174000",
+            "g23e4567-e89b-42d3-a456-426614174000",
+        ] {
+            assert!(!is_uuid(key), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Renders issued invoices. Both formats are generated in-process from the
+//! invoice snapshot, nothing is sent to an external service.
+
+pub mod pdf;
+pub mod ubl;
+
+#[cfg(test)]
+fn sample_invoice() -> crate::models::invoice::InvoiceData {
+    serde_json::from_value(serde_json::json!({
+        "number": "INV-2026-000042",
+        "order_id": 42,
+        "issue_date": "2026-10-19",
+        "currency": "EUR",
+        "minor_units": 2,
+        "seller": {
+            "name": "Crabby d.o.o.",
+            "line1": "Čopova ulica 1",
+            "line2": null,
+            "city": "Ljubljana",
+            "postcode": "1000",
+            "country": "SI",
+            "vat_number": "SI12345678",
+            "email": "shop@example.com",
+            "iban": "SI56 1910 0000 0123 438",
+            "updated_at": "2026-10-19T00:00:00"
+        },
+        "buyer": {
+            "id": 1,
+            "user_id": 1,
+            "label": null,
+            "first_name": "Đurđa",
+            "last_name": "Kovačević",
+            "company": null,
+            "line1": "Ilica 5 <Kat 2> & co",
+            "line2": null,
+            "city": "Zagreb",
+            "postcode": "10000",
+            "region": null,
+            "country": "HR",
+            "phone": null,
+            "default_shipping": true,
+            "default_billing": true,
+            "created_at": "2026-10-19T00:00:00",
+            "updated_at": "2026-10-19T00:00:00"
+        },
+        "buyer_email": "durda@example.com",
+        "vat_number": null,
+        "reverse_charge": false,
+        "lines": [
+            {
+                "name": "Čokolada (temna) 70%",
+                "sku": "CHOC-70",
+                "quantity": 3,
+                "unit_price": "2.50",
+                "tax_class": "reduced",
+                "tax_rate": "9.5",
+                "net_amount": "7.50",
+                "tax_amount": "0.71"
+            },
+            {
+                "name": "Shipping",
+                "sku": null,
+                "quantity": 1,
+                "unit_price": "4.10",
+                "tax_class": "standard",
+                "tax_rate": "22",
+                "net_amount": "4.10",
+                "tax_amount": "0.90"
+            }
+        ],
+        "tax_breakdown": [
+            { "tax_class": "reduced", "rate": "9.5", "net": "7.50", "tax": "0.71" },
+            { "tax_class": "standard", "rate": "22", "net": "4.10", "tax": "0.90" }
+        ],
+        "net_total": "11.60",
+        "tax_total": "1.61",
+        "total": "13.21"
+    }))
+    .unwrap()
+}
@@ -0,0 +1,735 @@
+//! A minimal PDF 1.4 writer for invoices: A4 pages of text and rules using
+//! the standard Helvetica fonts, so no fonts need to be embedded. Text is
+//! written in WinAnsiEncoding, Latin Extended-A (č, ć, đ, ł, ő, ...) with
+//! a second encoding of the same fonts.
+
+use sqlx::types::Decimal;
+
+use crate::models::invoice::InvoiceData;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const ROW_HEIGHT: f32 = 16.0;
+
+/// Helvetica glyph widths for ASCII 32..=126, in 1/1000 of the font size.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Glyph names of U+0100..=U+017F, the codes 128..=255 of the Latin
+/// Extended-A fonts.
+const LATIN_EXTENDED_A: [&str; 128] = [
+    "Amacron",
+    "amacron",
+    "Abreve",
+    "abreve",
+    "Aogonek",
+    "aogonek",
+    "Cacute",
+    "cacute",
+    "Ccircumflex",
+    "ccircumflex",
+    "Cdotaccent",
+    "cdotaccent",
+    "Ccaron",
+    "ccaron",
+    "Dcaron",
+    "dcaron",
+    "Dcroat",
+    "dcroat",
+    "Emacron",
+    "emacron",
+    "Ebreve",
+    "ebreve",
+    "Edotaccent",
+    "edotaccent",
+    "Eogonek",
+    "eogonek",
+    "Ecaron",
+    "ecaron",
+    "Gcircumflex",
+    "gcircumflex",
+    "Gbreve",
+    "gbreve",
+    "Gdotaccent",
+    "gdotaccent",
+    "Gcommaaccent",
+    "gcommaaccent",
+    "Hcircumflex",
+    "hcircumflex",
+    "Hbar",
+    "hbar",
+    "Itilde",
+    "itilde",
+    "Imacron",
+    "imacron",
+    "Ibreve",
+    "ibreve",
+    "Iogonek",
+    "iogonek",
+    "Idotaccent",
+    "dotlessi",
+    "IJ",
+    "ij",
+    "Jcircumflex",
+    "jcircumflex",
+    "Kcommaaccent",
+    "kcommaaccent",
+    "kgreenlandic",
+    "Lacute",
+    "lacute",
+    "Lcommaaccent",
+    "lcommaaccent",
+    "Lcaron",
+    "lcaron",
+    "Ldot",
+    "ldot",
+    "Lslash",
+    "lslash",
+    "Nacute",
+    "nacute",
+    "Ncommaaccent",
+    "ncommaaccent",
+    "Ncaron",
+    "ncaron",
+    "napostrophe",
+    "Eng",
+    "eng",
+    "Omacron",
+    "omacron",
+    "Obreve",
+    "obreve",
+    "Ohungarumlaut",
+    "ohungarumlaut",
+    "OE",
+    "oe",
+    "Racute",
+    "racute",
+    "Rcommaaccent",
+    "rcommaaccent",
+    "Rcaron",
+    "rcaron",
+    "Sacute",
+    "sacute",
+    "Scircumflex",
+    "scircumflex",
+    "Scedilla",
+    "scedilla",
+    "Scaron",
+    "scaron",
+    "Tcommaaccent",
+    "tcommaaccent",
+    "Tcaron",
+    "tcaron",
+    "Tbar",
+    "tbar",
+    "Utilde",
+    "utilde",
+    "Umacron",
+    "umacron",
+    "Ubreve",
+    "ubreve",
+    "Uring",
+    "uring",
+    "Uhungarumlaut",
+    "uhungarumlaut",
+    "Uogonek",
+    "uogonek",
+    "Wcircumflex",
+    "wcircumflex",
+    "Ycircumflex",
+    "ycircumflex",
+    "Ydieresis",
+    "Zacute",
+    "zacute",
+    "Zdotaccent",
+    "zdotaccent",
+    "Zcaron",
+    "zcaron",
+    "longs",
+];
+
+/// Letters of the same width as the Latin Extended-A glyphs.
+const LATIN_EXTENDED_A_BASE: &[u8; 128] =
+    b"AaAaAaCcCcCcCcDdDdEeEeEeEeEeGgGgGgGgHhHhIiIiIiIiIiUuJjKkkLlLlLlLlLlNnNnNnnNnOoOoOoWmRrRrRrSsSsSsSsTtTtTtUuUuUuUuUuUuWwYyYZzZzZzf";
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    /// Resource name of the font in the WinAnsi or the Latin Extended-A
+    /// encoding.
+    fn resource(&self, extended: bool) -> &'static str {
+        match (self, extended) {
+            (Font::Regular, false) => "F1",
+            (Font::Bold, false) => "F2",
+            (Font::Regular, true) => "F3",
+            (Font::Bold, true) => "F4",
+        }
+    }
+}
+
+/// Columns of the line table, text is right aligned except the description.
+const COLUMNS: [(&str, f32); 6] = [
+    ("Description", MARGIN),
+    ("Qty", 310.0),
+    ("Unit price", 380.0),
+    ("VAT %", 430.0),
+    ("Net", 490.0),
+    ("VAT", PAGE_WIDTH - MARGIN),
+];
+
+struct Page {
+    content: String,
+}
+
+impl Page {
+    fn new() -> Page {
+        Page {
+            content: String::new(),
+        }
+    }
+
+    fn text(&mut self, font: Font, size: f32, x: f32, y: f32, text: &str) {
+        self.content.push_str(&format!("BT {:.2} {:.2} Td", x, y));
+        for (extended, run) in encode(text) {
+            self.content.push_str(&format!(
+                " /{} {} Tf ({}) Tj",
+                font.resource(extended),
+                size,
+                run
+            ));
+        }
+        self.content.push_str(" ET\n");
+    }
+
+    fn text_right(&mut self, font: Font, size: f32, right: f32, y: f32, text: &str) {
+        self.text(font, size, right - text_width(text, size), y, text);
+    }
+
+    fn rule(&mut self, y: f32) {
+        self.content.push_str(&format!(
+            "0.5 w {:.2} {:.2} m {:.2} {:.2} l S\n",
+            MARGIN,
+            y,
+            PAGE_WIDTH - MARGIN,
+            y
+        ));
+    }
+
+    fn table_header(&mut self, y: f32) {
+        for (i, (title, x)) in COLUMNS.iter().enumerate() {
+            if i == 0 {
+                self.text(Font::Bold, 9.0, *x, y, title);
+            } else {
+                self.text_right(Font::Bold, 9.0, *x, y, title);
+            }
+        }
+
+        self.rule(y - 5.0);
+    }
+}
+
+/// Approximate rendered width, Latin Extended-A letters count as their base
+/// letter and other characters outside ASCII as wide ones.
+fn text_width(text: &str, size: f32) -> f32 {
+    let width = |code: u32| HELVETICA_WIDTHS[(code - 32) as usize] as u32;
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => width(code),
+            code @ 0x100..=0x17f => width(LATIN_EXTENDED_A_BASE[(code - 0x100) as usize] as u32),
+            _ => 667,
+        })
+        .sum();
+
+    units as f32 * size / 1000.0
+}
+
+/// Cuts `text` so it fits into `width`, marking the cut with an ellipsis.
+fn truncate(text: &str, size: f32, width: f32) -> String {
+    if text_width(text, size) <= width {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    for c in text.chars() {
+        if text_width(&format!("{}{}...", truncated, c), size) > width {
+            break;
+        }
+        truncated.push(c);
+    }
+
+    format!("{}...", truncated)
+}
+
+/// Splits a string into runs of the WinAnsi and the Latin Extended-A fonts,
+/// each escaped for a PDF literal. Characters neither encoding can represent
+/// are replaced with `?`.
+fn encode(text: &str) -> Vec<(bool, String)> {
+    let mut runs: Vec<(bool, String)> = Vec::new();
+
+    for c in text.chars() {
+        let extended = matches!(c, '\u{100}'..='\u{17f}');
+        if runs.last().map(|(e, _)| *e) != Some(extended) {
+            runs.push((extended, String::new()));
+        }
+        let run = &mut runs.last_mut().unwrap().1;
+
+        let code = match c {
+            '(' | ')' | '\\' => {
+                run.push('\\');
+                run.push(c);
+                continue;
+            }
+            ' '..='~' => {
+                run.push(c);
+                continue;
+            }
+            '\u{100}'..='\u{17f}' => c as u32 - 0x100 + 128,
+            '€' => 0o200,
+            '\u{a0}'..='\u{ff}' => c as u32,
+            _ => '?' as u32,
+        };
+
+        run.push_str(&format!("\\{:03o}", code));
+    }
+
+    runs
+}
+
+fn money(amount: Decimal, minor_units: i16) -> String {
+    format!("{:.*}", minor_units as usize, amount)
+}
+
+pub fn render(invoice: &InvoiceData) -> Vec<u8> {
+    let mut pages = vec![Page::new()];
+    let mut page = pages.last_mut().unwrap();
+
+    page.text(Font::Bold, 20.0, MARGIN, 780.0, "INVOICE");
+    page.text_right(
+        Font::Bold,
+        12.0,
+        PAGE_WIDTH - MARGIN,
+        782.0,
+        &invoice.number,
+    );
+    page.text_right(
+        Font::Regular,
+        9.0,
+        PAGE_WIDTH - MARGIN,
+        766.0,
+        &format!("Issue date: {}", invoice.issue_date),
+    );
+    page.text_right(
+        Font::Regular,
+        9.0,
+        PAGE_WIDTH - MARGIN,
+        754.0,
+        &format!("Order: #{}", invoice.order_id),
+    );
+
+    let seller = &invoice.seller;
+    let mut seller_lines = vec![seller.name.clone()];
+    seller_lines.extend(seller.line1.clone());
+    seller_lines.extend(seller.line2.clone());
+    seller_lines.push(
+        format!(
+            "{} {}",
+            seller.postcode.as_deref().unwrap_or_default(),
+            seller.city.as_deref().unwrap_or_default()
+        )
+        .trim()
+        .to_string(),
+    );
+    seller_lines.push(seller.country.clone());
+    seller_lines.extend(seller.vat_number.as_ref().map(|v| format!("VAT: {}", v)));
+    seller_lines.extend(seller.email.clone());
+
+    let mut buyer_lines = Vec::new();
+    if let Some(buyer) = &invoice.buyer {
+        buyer_lines.push(format!("{} {}", buyer.first_name, buyer.last_name));
+        buyer_lines.extend(buyer.company.clone());
+        buyer_lines.push(buyer.line1.clone());
+        buyer_lines.extend(buyer.line2.clone());
+        buyer_lines.push(format!("{} {}", buyer.postcode, buyer.city));
+        buyer_lines.push(buyer.country.clone());
+    }
+    buyer_lines.extend(invoice.vat_number.as_ref().map(|v| format!("VAT: {}", v)));
+    buyer_lines.extend(invoice.buyer_email.clone());
+
+    page.text(Font::Bold, 9.0, MARGIN, 710.0, "From");
+    page.text(Font::Bold, 9.0, 320.0, 710.0, "Bill to");
+    for (i, line) in seller_lines.iter().enumerate() {
+        page.text(Font::Regular, 10.0, MARGIN, 696.0 - i as f32 * 13.0, line);
+    }
+    for (i, line) in buyer_lines.iter().enumerate() {
+        page.text(Font::Regular, 10.0, 320.0, 696.0 - i as f32 * 13.0, line);
+    }
+
+    let mut y = 696.0 - seller_lines.len().max(buyer_lines.len()) as f32 * 13.0 - 30.0;
+    page.table_header(y);
+    y -= ROW_HEIGHT + 4.0;
+
+    for line in invoice.lines.iter() {
+        if y < MARGIN + 40.0 {
+            pages.push(Page::new());
+            page = pages.last_mut().unwrap();
+            y = PAGE_HEIGHT - MARGIN - 20.0;
+            page.table_header(y);
+            y -= ROW_HEIGHT + 4.0;
+        }
+
+        page.text(
+            Font::Regular,
+            9.0,
+            COLUMNS[0].1,
+            y,
+            &truncate(&line.name, 9.0, COLUMNS[1].1 - COLUMNS[0].1 - 40.0),
+        );
+        let cells = [
+            line.quantity.to_string(),
+            money(line.unit_price, invoice.minor_units),
+            format!("{}", line.tax_rate.normalize()),
+            money(line.net_amount, invoice.minor_units),
+            money(line.tax_amount, invoice.minor_units),
+        ];
+        for (cell, (_, right)) in cells.iter().zip(COLUMNS[1..].iter()) {
+            page.text_right(Font::Regular, 9.0, *right, y, cell);
+        }
+
+        y -= ROW_HEIGHT;
+    }
+
+    // Summary block: tax breakdown, totals and notes.
+    let summary_height = (invoice.tax_breakdown.len() + 6) as f32 * ROW_HEIGHT;
+    if y - summary_height < MARGIN + 20.0 {
+        pages.push(Page::new());
+        page = pages.last_mut().unwrap();
+        y = PAGE_HEIGHT - MARGIN - 20.0;
+    }
+
+    page.rule(y + ROW_HEIGHT - 8.0);
+    y -= 6.0;
+    let right = PAGE_WIDTH - MARGIN;
+
+    for b in invoice.tax_breakdown.iter() {
+        page.text_right(
+            Font::Regular,
+            9.0,
+            COLUMNS[4].1,
+            y,
+            &format!(
+                "VAT {}% of {}",
+                b.rate.normalize(),
+                money(b.net, invoice.minor_units)
+            ),
+        );
+        page.text_right(
+            Font::Regular,
+            9.0,
+            right,
+            y,
+            &money(b.tax, invoice.minor_units),
+        );
+        y -= ROW_HEIGHT;
+    }
+
+    let totals = [
+        ("Net total", invoice.net_total, Font::Regular),
+        ("VAT total", invoice.tax_total, Font::Regular),
+        ("Total", invoice.total, Font::Bold),
+    ];
+    for (label, amount, font) in totals {
+        page.text_right(
+            font,
+            10.0,
+            COLUMNS[4].1,
+            y,
+            &format!("{} ({})", label, invoice.currency),
+        );
+        page.text_right(font, 10.0, right, y, &money(amount, invoice.minor_units));
+        y -= ROW_HEIGHT;
+    }
+
+    y -= ROW_HEIGHT;
+    if invoice.reverse_charge {
+        page.text(
+            Font::Regular,
+            9.0,
+            MARGIN,
+            y,
+            "Reverse charge: VAT to be accounted for by the recipient (Art. 196 Directive 2006/112/EC).",
+        );
+        y -= ROW_HEIGHT;
+    }
+    if let Some(iban) = &seller.iban {
+        page.text(Font::Regular, 9.0, MARGIN, y, &format!("IBAN: {}", iban));
+    }
+
+    let count = pages.len();
+    for (i, page) in pages.iter_mut().enumerate() {
+        page.text_right(
+            Font::Regular,
+            8.0,
+            PAGE_WIDTH - MARGIN,
+            30.0,
+            &format!("{} - page {} of {}", invoice.number, i + 1, count),
+        );
+    }
+
+    assemble(&pages)
+}
+
+/// Writes the document structure: catalog, page tree, the four fonts, the
+/// Latin Extended-A encoding and a page and content stream object per page,
+/// followed by the xref table.
+fn assemble(pages: &[Page]) -> Vec<u8> {
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", 8 + i * 2))
+                .collect::<Vec<String>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding 7 0 R >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding 7 0 R >>".to_string(),
+        format!(
+            "<< /Type /Encoding /BaseEncoding /WinAnsiEncoding /Differences [128 /{}] >>",
+            LATIN_EXTENDED_A.join(" /")
+        ),
+    ];
+
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R /F4 6 0 R >> >> \
+             /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            9 + i * 2
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            page.content.len(),
+            page.content
+        ));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, LATIN_EXTENDED_A};
+    use crate::invoicing::sample_invoice;
+    use crate::models::invoice::InvoiceLine;
+
+    /// Checks the xref table against the objects and returns their bodies.
+    fn objects(pdf: &[u8]) -> Vec<String> {
+        let text = String::from_utf8(pdf.to_vec()).expect("PDF isn't ASCII");
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+
+        let startxref = text.rfind("startxref\n").unwrap();
+        let xref: usize = text[startxref + 10..]
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut lines = text[xref..].lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines
+            .next()
+            .unwrap()
+            .strip_prefix("0 ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+
+        (1..count)
+            .map(|number| {
+                let offset: usize = lines.next().unwrap()[..10].parse().unwrap();
+                let object = &text[offset..];
+                let header = format!("{} 0 obj\n", number);
+                assert!(
+                    object.starts_with(&header),
+                    "object {} isn't at its offset",
+                    number
+                );
+
+                let end = object.find("\nendobj\n").unwrap();
+                object[header.len()..end].to_string()
+            })
+            .collect()
+    }
+
+    /// Content of a stream object, checked against its `/Length`.
+    fn stream(object: &str) -> Option<&str> {
+        let length = object.strip_prefix("<< /Length ")?;
+        let (length, rest) = length.split_once(" >>\nstream\n")?;
+        let length: usize = length.parse().unwrap();
+        assert_eq!(&rest[length..], "endstream");
+
+        Some(&rest[..length])
+    }
+
+    /// Text shown by each `BT ... ET` block, decoded through the encoding of
+    /// its fonts.
+    fn text_blocks(content: &str) -> Vec<String> {
+        let mut blocks = Vec::new();
+
+        for line in content.lines().filter(|l| l.starts_with("BT ")) {
+            let mut chars = line.chars();
+            let mut font = String::new();
+            let mut text = String::new();
+
+            while let Some(c) = chars.next() {
+                match c {
+                    '/' => font = chars.by_ref().take_while(|c| *c != ' ').collect(),
+                    '(' => loop {
+                        let code = match chars.next().unwrap() {
+                            ')' => break,
+                            '\\' => match chars.next().unwrap() {
+                                d @ '0'..='7' => {
+                                    let digits: String =
+                                        [d, chars.next().unwrap(), chars.next().unwrap()]
+                                            .into_iter()
+                                            .collect();
+                                    u32::from_str_radix(&digits, 8).unwrap()
+                                }
+                                escaped => escaped as u32,
+                            },
+                            c => c as u32,
+                        };
+
+                        text.push(match (font.as_str(), code) {
+                            (_, 0..=127) => char::from_u32(code).unwrap(),
+                            ("F3" | "F4", _) => char::from_u32(code - 128 + 0x100).unwrap(),
+                            (_, 0o200) => '€',
+                            _ => char::from_u32(code).unwrap(),
+                        });
+                    },
+                    _ => (),
+                }
+            }
+
+            blocks.push(text);
+        }
+
+        blocks
+    }
+
+    fn pages(pdf: &[u8]) -> Vec<Vec<String>> {
+        objects(pdf)
+            .iter()
+            .filter_map(|o| stream(o))
+            .map(text_blocks)
+            .collect()
+    }
+
+    #[test]
+    fn writes_a_consistent_document() {
+        let objects = objects(&render(&sample_invoice()));
+
+        assert!(objects[1].contains("/Count 1"));
+        assert!(objects[4].contains("/BaseFont /Helvetica /Encoding 7 0 R"));
+
+        let differences = objects[6].split_once("[128 ").unwrap().1;
+        let names: Vec<&str> = differences.trim_end_matches("] >>").split(' ').collect();
+        assert_eq!(names.len(), 128);
+        assert_eq!(names[0x10c - 0x100], "/Ccaron");
+        assert_eq!(LATIN_EXTENDED_A[0x111 - 0x100], "dcroat");
+    }
+
+    #[test]
+    fn renders_central_european_names() {
+        let pages = pages(&render(&sample_invoice()));
+        let text = &pages[0];
+
+        for expected in [
+            "Crabby d.o.o.",
+            "Čopova ulica 1",
+            "Đurđa Kovačević",
+            "Čokolada (temna) 70%",
+            "Ilica 5 <Kat 2> & co",
+            "Total (EUR)",
+            "13.21",
+            "INV-2026-000042 - page 1 of 1",
+        ] {
+            assert!(
+                text.iter().any(|t| t == expected),
+                "{:?} missing from {:?}",
+                expected,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn replaces_characters_without_a_glyph() {
+        let mut invoice = sample_invoice();
+        invoice.lines[0].name = "Čaj 茶".to_string();
+
+        let pages = pages(&render(&invoice));
+        assert!(pages[0].iter().any(|t| t == "Čaj ?"));
+    }
+
+    #[test]
+    fn continues_long_invoices_on_new_pages() {
+        let mut invoice = sample_invoice();
+        invoice.lines = (1..=80)
+            .map(|i| InvoiceLine {
+                name: format!("Line {}", i),
+                ..serde_json::from_value(serde_json::to_value(&invoice.lines[1]).unwrap()).unwrap()
+            })
+            .collect();
+
+        let pages = pages(&render(&invoice));
+        assert!(pages.len() > 1);
+        for (i, page) in pages.iter().enumerate() {
+            let footer = format!("INV-2026-000042 - page {} of {}", i + 1, pages.len());
+            assert!(page.contains(&footer));
+        }
+        assert!(pages.iter().flatten().any(|t| t == "Line 80"));
+    }
+}
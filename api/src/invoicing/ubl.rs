@@ -0,0 +1,383 @@
+//! UBL 2.1 invoices following the EN 16931 core, the format accepted by
+//! Peppol and most European e-invoicing platforms.
+
+use sqlx::types::Decimal;
+
+use crate::models::invoice::InvoiceData;
+use crate::models::tax::TaxClass;
+
+/// UNCL 5305 tax category of a line or breakdown entry.
+fn tax_category(tax_class: TaxClass, rate: Decimal, reverse_charge: bool) -> &'static str {
+    if reverse_charge {
+        "AE"
+    } else if rate.is_zero() || tax_class == TaxClass::Zero {
+        "Z"
+    } else {
+        "S"
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn amount(name: &str, invoice: &InvoiceData, value: Decimal) -> String {
+    format!(
+        "<cbc:{} currencyID=\"{}\">{:.*}</cbc:{}>",
+        name, invoice.currency, invoice.minor_units as usize, value, name
+    )
+}
+
+fn tax_category_xml(element: &str, id: &str, rate: Decimal, reverse_charge: bool) -> String {
+    let mut xml = format!(
+        "<cac:{}><cbc:ID>{}</cbc:ID><cbc:Percent>{:.2}</cbc:Percent>",
+        element, id, rate
+    );
+    if reverse_charge {
+        xml.push_str("<cbc:TaxExemptionReasonCode>VATEX-EU-AE</cbc:TaxExemptionReasonCode>");
+        xml.push_str("<cbc:TaxExemptionReason>Reverse charge</cbc:TaxExemptionReason>");
+    }
+    xml.push_str(&format!(
+        "<cac:TaxScheme><cbc:ID>VAT</cbc:ID></cac:TaxScheme></cac:{}>",
+        element
+    ));
+
+    xml
+}
+
+struct Party<'a> {
+    name: &'a str,
+    street: Option<&'a str>,
+    city: Option<&'a str>,
+    postcode: Option<&'a str>,
+    country: &'a str,
+    vat_number: Option<&'a str>,
+    email: Option<&'a str>,
+}
+
+fn party(element: &str, party: Party) -> String {
+    let Party {
+        name,
+        street,
+        city,
+        postcode,
+        country,
+        vat_number,
+        email,
+    } = party;
+    let mut xml = format!("<cac:{}><cac:Party>", element);
+
+    if let Some(email) = email {
+        xml.push_str(&format!(
+            "<cbc:EndpointID schemeID=\"EM\">{}</cbc:EndpointID>",
+            escape(email)
+        ));
+    }
+
+    xml.push_str("<cac:PostalAddress>");
+    if let Some(street) = street {
+        xml.push_str(&format!(
+            "<cbc:StreetName>{}</cbc:StreetName>",
+            escape(street)
+        ));
+    }
+    if let Some(city) = city {
+        xml.push_str(&format!("<cbc:CityName>{}</cbc:CityName>", escape(city)));
+    }
+    if let Some(postcode) = postcode {
+        xml.push_str(&format!(
+            "<cbc:PostalZone>{}</cbc:PostalZone>",
+            escape(postcode)
+        ));
+    }
+    xml.push_str(&format!(
+        "<cac:Country><cbc:IdentificationCode>{}</cbc:IdentificationCode></cac:Country>",
+        escape(country)
+    ));
+    xml.push_str("</cac:PostalAddress>");
+
+    if let Some(vat_number) = vat_number {
+        xml.push_str(&format!(
+            "<cac:PartyTaxScheme><cbc:CompanyID>{}</cbc:CompanyID>\
+             <cac:TaxScheme><cbc:ID>VAT</cbc:ID></cac:TaxScheme></cac:PartyTaxScheme>",
+            escape(vat_number)
+        ));
+    }
+
+    xml.push_str(&format!(
+        "<cac:PartyLegalEntity><cbc:RegistrationName>{}</cbc:RegistrationName>\
+         </cac:PartyLegalEntity></cac:Party></cac:{}>",
+        escape(name),
+        element
+    ));
+
+    xml
+}
+
+pub fn render(invoice: &InvoiceData) -> String {
+    let currency = invoice.currency.as_str();
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <Invoice xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:Invoice-2\" \
+         xmlns:cac=\"urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2\" \
+         xmlns:cbc=\"urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2\">\
+         <cbc:CustomizationID>urn:cen.eu:en16931:2017</cbc:CustomizationID>",
+    );
+
+    xml.push_str(&format!(
+        "<cbc:ID>{}</cbc:ID><cbc:IssueDate>{}</cbc:IssueDate>\
+         <cbc:InvoiceTypeCode>380</cbc:InvoiceTypeCode>\
+         <cbc:DocumentCurrencyCode>{}</cbc:DocumentCurrencyCode>\
+         <cac:OrderReference><cbc:ID>{}</cbc:ID></cac:OrderReference>",
+        escape(&invoice.number),
+        invoice.issue_date,
+        currency,
+        invoice.order_id
+    ));
+
+    let seller = &invoice.seller;
+    xml.push_str(&party(
+        "AccountingSupplierParty",
+        Party {
+            name: &seller.name,
+            street: seller.line1.as_deref(),
+            city: seller.city.as_deref(),
+            postcode: seller.postcode.as_deref(),
+            country: &seller.country,
+            vat_number: seller.vat_number.as_deref(),
+            email: seller.email.as_deref(),
+        },
+    ));
+
+    let buyer = invoice.buyer.as_ref();
+    let buyer_name = buyer
+        .map(|b| {
+            b.company
+                .clone()
+                .unwrap_or_else(|| format!("{} {}", b.first_name, b.last_name))
+        })
+        .unwrap_or_default();
+    xml.push_str(&party(
+        "AccountingCustomerParty",
+        Party {
+            name: &buyer_name,
+            street: buyer.map(|b| b.line1.as_str()),
+            city: buyer.map(|b| b.city.as_str()),
+            postcode: buyer.map(|b| b.postcode.as_str()),
+            country: buyer.map(|b| b.country.as_str()).unwrap_or(&seller.country),
+            vat_number: invoice.vat_number.as_deref(),
+            email: invoice.buyer_email.as_deref(),
+        },
+    ));
+
+    if let Some(iban) = &seller.iban {
+        xml.push_str(&format!(
+            "<cac:PaymentMeans><cbc:PaymentMeansCode>58</cbc:PaymentMeansCode>\
+             <cac:PayeeFinancialAccount><cbc:ID>{}</cbc:ID></cac:PayeeFinancialAccount>\
+             </cac:PaymentMeans>",
+            escape(iban)
+        ));
+    }
+
+    xml.push_str(&format!(
+        "<cac:TaxTotal>{}",
+        amount("TaxAmount", invoice, invoice.tax_total)
+    ));
+    for b in invoice.tax_breakdown.iter() {
+        xml.push_str(&format!(
+            "<cac:TaxSubtotal>{}{}{}</cac:TaxSubtotal>",
+            amount("TaxableAmount", invoice, b.net),
+            amount("TaxAmount", invoice, b.tax),
+            tax_category_xml(
+                "TaxCategory",
+                tax_category(b.tax_class, b.rate, invoice.reverse_charge),
+                b.rate,
+                invoice.reverse_charge
+            )
+        ));
+    }
+    xml.push_str("</cac:TaxTotal>");
+
+    xml.push_str(&format!(
+        "<cac:LegalMonetaryTotal>{}{}{}{}</cac:LegalMonetaryTotal>",
+        amount("LineExtensionAmount", invoice, invoice.net_total),
+        amount("TaxExclusiveAmount", invoice, invoice.net_total),
+        amount("TaxInclusiveAmount", invoice, invoice.total),
+        amount("PayableAmount", invoice, invoice.total)
+    ));
+
+    for (i, line) in invoice.lines.iter().enumerate() {
+        let mut item = format!("<cac:Item><cbc:Name>{}</cbc:Name>", escape(&line.name));
+        if let Some(sku) = &line.sku {
+            item.push_str(&format!(
+                "<cac:SellersItemIdentification><cbc:ID>{}</cbc:ID>\
+                 </cac:SellersItemIdentification>",
+                escape(sku)
+            ));
+        }
+        item.push_str(&tax_category_xml(
+            "ClassifiedTaxCategory",
+            tax_category(line.tax_class, line.tax_rate, invoice.reverse_charge),
+            line.tax_rate,
+            false,
+        ));
+        item.push_str("</cac:Item>");
+
+        xml.push_str(&format!(
+            "<cac:InvoiceLine><cbc:ID>{}</cbc:ID>\
+             <cbc:InvoicedQuantity unitCode=\"C62\">{}</cbc:InvoicedQuantity>{}{}\
+             <cac:Price>{}</cac:Price></cac:InvoiceLine>",
+            i + 1,
+            line.quantity,
+            amount("LineExtensionAmount", invoice, line.net_amount),
+            item,
+            amount("PriceAmount", invoice, line.unit_price)
+        ));
+    }
+
+    xml.push_str("</Invoice>");
+
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::invoicing::sample_invoice;
+    use roxmltree::{Document, Node};
+
+    const CBC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+    const CAC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+
+    fn child<'a, 'input>(node: Node<'a, 'input>, path: &[(&str, &str)]) -> Node<'a, 'input> {
+        path.iter().fold(node, |node, name| {
+            node.children()
+                .find(|c| c.has_tag_name(*name))
+                .unwrap_or_else(|| panic!("{} has no {}", node.tag_name().name(), name.1))
+        })
+    }
+
+    fn text<'a>(node: Node<'a, '_>, path: &[(&str, &str)]) -> &'a str {
+        child(node, path).text().unwrap_or_default()
+    }
+
+    #[test]
+    fn writes_an_en_16931_invoice() {
+        let xml = render(&sample_invoice());
+        let document = Document::parse(&xml).unwrap();
+        let invoice = document.root_element();
+
+        assert!(invoice.has_tag_name((
+            "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2",
+            "Invoice"
+        )));
+        assert_eq!(text(invoice, &[(CBC, "ID")]), "INV-2026-000042");
+        assert_eq!(text(invoice, &[(CBC, "IssueDate")]), "2026-10-19");
+        assert_eq!(text(invoice, &[(CBC, "DocumentCurrencyCode")]), "EUR");
+
+        let seller = child(invoice, &[(CAC, "AccountingSupplierParty"), (CAC, "Party")]);
+        assert_eq!(
+            text(seller, &[(CAC, "PostalAddress"), (CBC, "StreetName")]),
+            "Čopova ulica 1"
+        );
+        assert_eq!(
+            text(seller, &[(CAC, "PartyTaxScheme"), (CBC, "CompanyID")]),
+            "SI12345678"
+        );
+
+        let buyer = child(invoice, &[(CAC, "AccountingCustomerParty"), (CAC, "Party")]);
+        assert_eq!(
+            text(
+                buyer,
+                &[(CAC, "PartyLegalEntity"), (CBC, "RegistrationName")]
+            ),
+            "Đurđa Kovačević"
+        );
+        assert_eq!(
+            text(buyer, &[(CAC, "PostalAddress"), (CBC, "StreetName")]),
+            "Ilica 5 <Kat 2> & co"
+        );
+
+        let totals = child(invoice, &[(CAC, "LegalMonetaryTotal")]);
+        assert_eq!(text(totals, &[(CBC, "TaxExclusiveAmount")]), "11.60");
+        assert_eq!(text(totals, &[(CBC, "PayableAmount")]), "13.21");
+        assert_eq!(
+            child(totals, &[(CBC, "PayableAmount")]).attribute("currencyID"),
+            Some("EUR")
+        );
+    }
+
+    #[test]
+    fn lists_lines_and_tax_subtotals() {
+        let xml = render(&sample_invoice());
+        let document = Document::parse(&xml).unwrap();
+        let invoice = document.root_element();
+
+        let lines: Vec<Node> = invoice
+            .children()
+            .filter(|c| c.has_tag_name((CAC, "InvoiceLine")))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            text(lines[0], &[(CAC, "Item"), (CBC, "Name")]),
+            "Čokolada (temna) 70%"
+        );
+        assert_eq!(text(lines[0], &[(CBC, "InvoicedQuantity")]), "3");
+        assert_eq!(text(lines[0], &[(CBC, "LineExtensionAmount")]), "7.50");
+        assert_eq!(
+            text(
+                lines[0],
+                &[
+                    (CAC, "Item"),
+                    (CAC, "ClassifiedTaxCategory"),
+                    (CBC, "Percent")
+                ]
+            ),
+            "9.50"
+        );
+
+        let subtotals: Vec<(&str, &str, &str)> = child(invoice, &[(CAC, "TaxTotal")])
+            .children()
+            .filter(|c| c.has_tag_name((CAC, "TaxSubtotal")))
+            .map(|s| {
+                (
+                    text(s, &[(CAC, "TaxCategory"), (CBC, "ID")]),
+                    text(s, &[(CAC, "TaxCategory"), (CBC, "Percent")]),
+                    text(s, &[(CBC, "TaxAmount")]),
+                )
+            })
+            .collect();
+        assert_eq!(
+            subtotals,
+            vec![("S", "9.50", "0.71"), ("S", "22.00", "0.90")]
+        );
+    }
+
+    #[test]
+    fn marks_reverse_charged_invoices() {
+        let mut invoice = sample_invoice();
+        invoice.reverse_charge = true;
+        invoice.vat_number = Some("HR12345678901".to_string());
+
+        let xml = render(&invoice);
+        let document = Document::parse(&xml).unwrap();
+        let category = child(
+            document.root_element(),
+            &[
+                (CAC, "TaxTotal"),
+                (CAC, "TaxSubtotal"),
+                (CAC, "TaxCategory"),
+            ],
+        );
+
+        assert_eq!(text(category, &[(CBC, "ID")]), "AE");
+        assert_eq!(
+            text(category, &[(CBC, "TaxExemptionReasonCode")]),
+            "VATEX-EU-AE"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Interval;
+
+use crate::config::Config;
+use crate::models::idempotency::IdempotencyKey;
+use crate::models::product::Product;
+use crate::models::trash::Trash;
+
+/// The background jobs of the server. They stop between runs once the
+/// shutdown flag they were spawned with turns true.
+pub struct Jobs(Vec<JoinHandle<()>>);
+
+impl Jobs {
+    pub fn spawn(pool: &PgPool, config: &Config, shutdown: watch::Receiver<bool>) -> Jobs {
+        Jobs(vec![
+            spawn_trash_purge(pool.clone(), config.trash_retention_days, shutdown.clone()),
+            spawn_publication_scheduler(pool.clone(), shutdown.clone()),
+            spawn_idempotency_purge(pool.clone(), shutdown),
+        ])
+    }
+
+    /// Waits for every job to stop, letting a run in progress finish.
+    pub async fn join(self) {
+        for handle in self.0 {
+            if let Err(e) = handle.await {
+                tracing::error!("Background job failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Waits for the next run, returning false instead once shutting down.
+async fn tick(interval: &mut Interval, shutdown: &mut watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = interval.tick() => !*shutdown.borrow(),
+        _ = shutdown.changed() => false,
+    }
+}
+
+/// Hard deletes trashed catalog records once they're older than
+/// `retention_days`, checking every hour.
+fn spawn_trash_purge(
+    pool: PgPool,
+    retention_days: i32,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        while tick(&mut interval, &mut shutdown).await {
+            match Trash::purge(retention_days, &pool).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} records from the trash", purged),
+                Err(e) => tracing::error!("Can't purge the trash: {}", e),
+            }
+        }
+    })
+}
+
+/// Publishes and archives products on their schedule, checking every
+/// minute.
+fn spawn_publication_scheduler(
+    pool: PgPool,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        while tick(&mut interval, &mut shutdown).await {
+            match Product::apply_schedules(&pool).await {
+                Ok((0, 0)) => {}
+                Ok((published, archived)) => tracing::info!(
+                    "Published {} and archived {} scheduled products",
+                    published,
+                    archived
+                ),
+                Err(e) => tracing::error!("Can't apply product schedules: {}", e),
+            }
+        }
+    })
+}
+
+/// Deletes the expired `Idempotency-Key` responses, checking every hour.
+fn spawn_idempotency_purge(pool: PgPool, mut shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+        while tick(&mut interval, &mut shutdown).await {
+            match IdempotencyKey::purge_expired(&pool).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} expired idempotency keys", purged),
+                Err(e) => tracing::error!("Can't purge idempotency keys: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,375 @@
+//! Protection against abusive clients: token bucket rate limits per address
+//! and per user, and limits on the size of request bodies.
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, FromRequest, MatchedPath, RequestParts};
+use axum::http::header::{CONTENT_LENGTH, RETRY_AFTER};
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body::{LengthLimitError, Limited};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::errors::ApiError;
+use crate::models::authentication::JwtToken;
+
+/// Buckets kept per limiter before the full ones are dropped.
+const MAX_BUCKETS: usize = 100_000;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Lets every key make a number of requests a minute, in bursts of up to a
+/// minute's worth.
+struct RateLimiter<K> {
+    capacity: f64,
+    /// Tokens added to a bucket a second.
+    refill: f64,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    /// No limiter when `per_minute` is 0.
+    fn per_minute(per_minute: u32) -> Option<RateLimiter<K>> {
+        (per_minute > 0).then(|| RateLimiter {
+            capacity: f64::from(per_minute),
+            refill: f64::from(per_minute) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn tokens(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.refill;
+        (bucket.tokens + refilled).min(self.capacity)
+    }
+
+    /// Takes a token from the bucket of `key`, or tells how long until it
+    /// has one again.
+    fn acquire(&self, key: K) -> Result<(), Duration> {
+        self.acquire_at(key, Instant::now())
+    }
+
+    fn acquire_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // A full bucket is the same as none.
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, b| self.tokens(b, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        bucket.tokens = self.tokens(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.refill))
+        }
+    }
+}
+
+/// The rate limiters of the API, shared as an extension.
+pub struct RateLimits {
+    per_ip: Option<RateLimiter<IpAddr>>,
+    per_user: Option<RateLimiter<i64>>,
+    strict: Option<RateLimiter<IpAddr>>,
+    trust_forwarded_for: bool,
+}
+
+impl RateLimits {
+    pub fn new(config: &Config) -> RateLimits {
+        RateLimits {
+            per_ip: RateLimiter::per_minute(config.rate_limit_per_ip),
+            per_user: RateLimiter::per_minute(config.rate_limit_per_user),
+            strict: RateLimiter::per_minute(config.rate_limit_strict),
+            trust_forwarded_for: config.trust_forwarded_for,
+        }
+    }
+
+    /// The address of the client, from the connection or, behind a proxy,
+    /// the address the proxy appended to `X-Forwarded-For`.
+    fn client_ip<B>(&self, request: &Request<B>) -> Option<IpAddr> {
+        let forwarded = self
+            .trust_forwarded_for
+            .then(|| request.headers().get(X_FORWARDED_FOR))
+            .flatten()
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+
+        forwarded.or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|c| c.0.ip())
+        })
+    }
+}
+
+/// Limits authenticated requests per user and the others per address.
+pub async fn rate_limit(request: Request<Body>, next: Next<Body>) -> Response {
+    let Some(limits) = request.extensions().get::<Arc<RateLimits>>().cloned() else {
+        return next.run(request).await;
+    };
+
+    let mut parts = RequestParts::new(request);
+    let user = JwtToken::from_request(&mut parts).await.ok().map(|t| t.sub);
+    let request = parts
+        .try_into_request()
+        .expect("Body taken by the rate limit");
+
+    let acquired = match (user, &limits.per_user, &limits.per_ip) {
+        (Some(user), Some(limiter), _) => limiter.acquire(user),
+        (Some(_), None, _) => Ok(()),
+        (None, _, Some(limiter)) => limits
+            .client_ip(&request)
+            .map_or(Ok(()), |ip| limiter.acquire(ip)),
+        (None, _, None) => Ok(()),
+    };
+
+    match acquired {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => too_many_requests(retry_after),
+    }
+}
+
+/// The stricter limit per address of endpoints open to guessing, like
+/// login, on top of [`rate_limit`].
+pub async fn strict_rate_limit(request: Request<Body>, next: Next<Body>) -> Response {
+    let Some(limits) = request.extensions().get::<Arc<RateLimits>>().cloned() else {
+        return next.run(request).await;
+    };
+
+    let acquired = match (&limits.strict, limits.client_ip(&request)) {
+        (Some(limiter), Some(ip)) => limiter.acquire(ip),
+        _ => Ok(()),
+    };
+
+    match acquired {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => too_many_requests(retry_after),
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let (status, body) = ApiError::with_status(
+        StatusCode::TOO_MANY_REQUESTS,
+        &format!("Too many requests, retry in {} seconds", seconds),
+    );
+
+    (status, [(RETRY_AFTER, seconds.to_string())], body).into_response()
+}
+
+/// Turns away request bodies larger than `MAX_BODY_SIZE`, or the limit of
+/// the route in `BODY_SIZE_LIMITS`. Has to be a route layer to know the
+/// route.
+pub async fn limit_body(request: Request<Body>, next: Next<Body>) -> Response {
+    let max_size = match request.extensions().get::<Config>() {
+        Some(config) => {
+            let route = request.extensions().get::<MatchedPath>();
+            config
+                .body_size_limits
+                .iter()
+                .find(|l| route.is_some_and(|r| r.as_str() == l.route))
+                .map_or(config.max_body_size, |l| l.max_size)
+        }
+        None => return next.run(request).await,
+    };
+
+    let content_length = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > max_size) {
+        return payload_too_large(max_size);
+    }
+
+    // Read up front, so that bodies without a length stop at the limit too.
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(Limited::new(body, max_size)).await {
+        Ok(body) => body,
+        Err(e) if e.is::<LengthLimitError>() => return payload_too_large(max_size),
+        Err(e) => return ApiError::bad_request(&e.to_string()).into_response(),
+    };
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+fn payload_too_large(max_size: usize) -> Response {
+    ApiError::with_status(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        &format!("The request body is larger than {} bytes", max_size),
+    )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::routing::post;
+    use axum::{middleware, Extension, Router};
+    use clap::Parser;
+    use tower::ServiceExt;
+
+    fn assert_waits(acquired: Result<(), Duration>, seconds: f64) {
+        let waited = acquired.unwrap_err().as_secs_f64();
+        assert!((waited - seconds).abs() < 1e-6, "waits {}s", waited);
+    }
+
+    #[test]
+    fn lets_a_burst_through_then_limits() {
+        let limiter = RateLimiter::per_minute(3).unwrap();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.acquire_at("a", start).is_ok());
+        }
+        // A token comes back every 20 seconds.
+        assert_waits(limiter.acquire_at("a", start), 20.0);
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = RateLimiter::per_minute(3).unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire_at("a", start).unwrap();
+        }
+
+        assert_waits(
+            limiter.acquire_at("a", start + Duration::from_secs(5)),
+            15.0,
+        );
+        assert!(limiter
+            .acquire_at("a", start + Duration::from_secs(20))
+            .is_ok());
+        assert_waits(
+            limiter.acquire_at("a", start + Duration::from_secs(20)),
+            20.0,
+        );
+    }
+
+    #[test]
+    fn refills_up_to_the_capacity() {
+        let limiter = RateLimiter::per_minute(2).unwrap();
+        let start = Instant::now();
+        limiter.acquire_at("a", start).unwrap();
+
+        let later = start + Duration::from_secs(3600);
+        assert!(limiter.acquire_at("a", later).is_ok());
+        assert!(limiter.acquire_at("a", later).is_ok());
+        assert!(limiter.acquire_at("a", later).is_err());
+    }
+
+    #[test]
+    fn keeps_a_bucket_per_key() {
+        let limiter = RateLimiter::per_minute(1).unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.acquire_at("a", start).is_ok());
+        assert!(limiter.acquire_at("a", start).is_err());
+        assert!(limiter.acquire_at("b", start).is_ok());
+    }
+
+    #[test]
+    fn has_no_limiter_without_a_rate() {
+        assert!(RateLimiter::<&str>::per_minute(0).is_none());
+    }
+
+    #[test]
+    fn rounds_retry_after_up_to_whole_seconds() {
+        let retry_after = |wait| {
+            too_many_requests(wait).headers()[RETRY_AFTER]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(retry_after(Duration::from_millis(1500)), "2");
+        assert_eq!(retry_after(Duration::from_secs(20)), "20");
+        assert_eq!(retry_after(Duration::from_millis(10)), "1");
+        assert_eq!(
+            too_many_requests(Duration::from_secs(1)).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    fn app() -> Router {
+        let config = Config::try_parse_from([
+            "crabbyshop",
+            "--database-url",
+            "postgres://localhost/crabbyshop",
+            "--jwt-secret",
+            "limits-test-jwt-secret",
+            "--payment-webhook-secret",
+            "limits-test-webhook-secret",
+            "--max-body-size",
+            "16",
+            "--body-size-limits",
+            "/upload=32",
+        ])
+        .unwrap();
+        let echo = post(|body: Bytes| async move { body.len().to_string() });
+
+        Router::new()
+            .route("/echo", echo.clone())
+            .route("/upload", echo)
+            .route_layer(middleware::from_fn(limit_body))
+            .layer(Extension(config))
+    }
+
+    async fn send(path: &str, body: Vec<u8>, content_length: bool) -> StatusCode {
+        let mut request = Request::post(path);
+        if content_length {
+            request = request.header(CONTENT_LENGTH, body.len());
+        }
+        let request = request.body(Body::from(body)).unwrap();
+
+        app().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn accepts_bodies_up_to_the_limit() {
+        assert_eq!(send("/echo", vec![0; 16], true).await, StatusCode::OK);
+        assert_eq!(send("/echo", vec![0; 16], false).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rejects_larger_bodies() {
+        assert_eq!(
+            send("/echo", vec![0; 17], true).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        // Without a length the body is cut off while it's read.
+        assert_eq!(
+            send("/echo", vec![0; 17], false).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_the_limit_of_the_route() {
+        assert_eq!(send("/upload", vec![0; 32], true).await, StatusCode::OK);
+        assert_eq!(
+            send("/upload", vec![0; 33], false).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Logs of the server, as text or one JSON object per line, and the request
+//! ID that ties together the logs of a request.
+
+use axum::http::{HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use rand_core::{OsRng, RngCore};
+use tracing::Span;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::{Config, LogFormat};
+use crate::telemetry::{self, Exporter};
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// Longest request ID taken over from a client.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The ID of a request, from its `X-Request-Id` header or generated.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Installs the logger, and the trace exporter when an OTLP endpoint is
+/// set. The exporter has to be shut down to send the last traces.
+pub fn init(config: &Config) -> Result<Option<Exporter>, String> {
+    let (text, json) = match config.log_format {
+        LogFormat::Text => (
+            Some(tracing_subscriber::fmt::layer().with_filter(EnvFilter::new(&config.log_level))),
+            None,
+        ),
+        LogFormat::Json => (
+            None,
+            // Every event on its own line, with the fields of its spans.
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_filter(EnvFilter::new(&config.log_level)),
+            ),
+        ),
+    };
+
+    let (otlp, exporter) = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let (layer, exporter) = telemetry::pipeline(endpoint, &config.otel_service_name)?;
+            (
+                Some(layer.with_filter(filter_fn(telemetry::is_exported))),
+                Some(exporter),
+            )
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(text)
+        .with(json)
+        .with(otlp)
+        .init();
+
+    Ok(exporter)
+}
+
+/// The ID of the request being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Takes the request ID over from the client or generates one, and echoes
+/// it in the response.
+pub async fn propagate_request_id<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(generate_request_id);
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(X_REQUEST_ID, value);
+    }
+
+    response
+}
+
+/// Span of a request, `user_id` is filled in once it's authenticated. Traces
+/// continue from the `traceparent` header.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.as_str())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id,
+        user_id = tracing::field::Empty,
+        otel.kind = "server",
+    );
+    telemetry::continue_trace(&span, request);
+
+    span
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+/// A random (version 4) UUID.
+fn generate_request_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
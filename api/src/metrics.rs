@@ -0,0 +1,222 @@
+//! Prometheus metrics of the process, served in the text exposition format
+//! by `GET /metrics` to admin tokens.
+
+use axum::extract::MatchedPath;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Upper bounds of the request latency buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub const fn new() -> Counter {
+        Counter(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub static ORDERS_CREATED: Counter = Counter::new();
+
+/// Orders rejected because an item was out of stock.
+pub static STOCK_OUTS: Counter = Counter::new();
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Requests by method, route and status.
+static REQUESTS: Mutex<BTreeMap<(String, String, u16), u64>> = Mutex::new(BTreeMap::new());
+
+/// Request latency by method and route.
+static LATENCY: Mutex<BTreeMap<(String, String), Histogram>> = Mutex::new(BTreeMap::new());
+
+/// Counts and times every request, by the route left on the response by
+/// [`record_route`]. Requests no route matched share the `unmatched` route.
+pub async fn track_requests<B>(request: Request<B>, next: Next<B>) -> Response {
+    let method = request.method().to_string();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let route = response
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let status = response.status().as_u16();
+
+    *REQUESTS
+        .lock()
+        .unwrap()
+        .entry((method.clone(), route.clone(), status))
+        .or_default() += 1;
+    LATENCY
+        .lock()
+        .unwrap()
+        .entry((method, route))
+        .or_default()
+        .observe(elapsed);
+
+    response
+}
+
+/// Hands the matched route out on the response. Layers outside a nested
+/// router only see the path it's nested at.
+pub async fn record_route<B>(request: Request<B>, next: Next<B>) -> Response {
+    let route = request.extensions().get::<MatchedPath>().cloned();
+
+    let mut response = next.run(request).await;
+    if let Some(route) = route {
+        response.extensions_mut().insert(route);
+    }
+
+    response
+}
+
+pub fn render(pool: &PgPool, max_connections: u32) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "crabbyshop_http_requests_total",
+        "counter",
+        "HTTP requests by method, route and status.",
+    );
+    for ((method, route, status), count) in REQUESTS.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "crabbyshop_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            method,
+            escape(route),
+            status,
+            count
+        );
+    }
+
+    header(
+        &mut out,
+        "crabbyshop_http_request_duration_seconds",
+        "histogram",
+        "HTTP request latency by method and route.",
+    );
+    for ((method, route), histogram) in LATENCY.lock().unwrap().iter() {
+        let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+            let _ = writeln!(
+                out,
+                "crabbyshop_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "crabbyshop_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, histogram.count
+        );
+        let _ = writeln!(
+            out,
+            "crabbyshop_http_request_duration_seconds_sum{{{}}} {}",
+            labels, histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "crabbyshop_http_request_duration_seconds_count{{{}}} {}",
+            labels, histogram.count
+        );
+    }
+
+    let idle = pool.num_idle() as u32;
+    header(
+        &mut out,
+        "crabbyshop_db_pool_connections",
+        "gauge",
+        "Open database connections by state.",
+    );
+    let _ = writeln!(
+        out,
+        "crabbyshop_db_pool_connections{{state=\"idle\"}} {}",
+        idle
+    );
+    let _ = writeln!(
+        out,
+        "crabbyshop_db_pool_connections{{state=\"in_use\"}} {}",
+        pool.size().saturating_sub(idle)
+    );
+
+    header(
+        &mut out,
+        "crabbyshop_db_pool_max_connections",
+        "gauge",
+        "Most connections the database pool opens.",
+    );
+    let _ = writeln!(
+        out,
+        "crabbyshop_db_pool_max_connections {}",
+        max_connections
+    );
+
+    for (name, help, counter) in [
+        (
+            "crabbyshop_orders_created_total",
+            "Orders placed.",
+            &ORDERS_CREATED,
+        ),
+        (
+            "crabbyshop_stock_outs_total",
+            "Orders rejected because an item was out of stock.",
+            &STOCK_OUTS,
+        ),
+    ] {
+        header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{} {}", name, counter.get());
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
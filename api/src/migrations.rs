@@ -0,0 +1,114 @@
+//! The migrations in `migrations/`, embedded in the binary so that it can
+//! set up and upgrade its own database.
+
+use sqlx::migrate::{Migrate, Migration, Migrator};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// A migration of the binary and whether the database has it.
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    /// Applied, but from a file that has been edited since.
+    pub changed: bool,
+    pub reversible: bool,
+}
+
+impl MigrationStatus {
+    fn new(migration: &Migration, applied_checksum: Option<&Vec<u8>>) -> MigrationStatus {
+        MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied_checksum.is_some(),
+            changed: applied_checksum.is_some_and(|c| c[..] != migration.checksum[..]),
+            reversible: MIGRATOR
+                .iter()
+                .any(|d| d.version == migration.version && d.migration_type.is_down_migration()),
+        }
+    }
+}
+
+/// Every migration known to the binary, oldest first.
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, String> {
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+
+    // Read only, a fresh database has no migrations table yet.
+    let has_table =
+        sqlx::query_scalar!(r#"SELECT to_regclass('_sqlx_migrations') IS NOT NULL as "exists!""#)
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+    if !has_table {
+        return Ok(MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| MigrationStatus::new(m, None))
+            .collect());
+    }
+
+    if let Some(version) = conn.dirty_version().await.map_err(|e| e.to_string())? {
+        return Err(format!(
+            "migration {} failed partway and has to be fixed by hand",
+            version
+        ));
+    }
+
+    let applied: HashMap<i64, Vec<u8>> = conn
+        .list_applied_migrations()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect();
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus::new(m, applied.get(&m.version)))
+        .collect())
+}
+
+/// Applies the pending migrations, returning how many there were.
+pub async fn run(pool: &PgPool) -> Result<usize, String> {
+    let pending = status(pool).await?.iter().filter(|m| !m.applied).count();
+    MIGRATOR.run(pool).await.map_err(|e| e.to_string())?;
+
+    Ok(pending)
+}
+
+/// Reverts the applied migrations newer than `target`, by default only the
+/// latest one. Returns the reverted versions.
+pub async fn revert(pool: &PgPool, target: Option<i64>) -> Result<Vec<i64>, String> {
+    let applied: Vec<MigrationStatus> = status(pool)
+        .await?
+        .into_iter()
+        .filter(|m| m.applied)
+        .collect();
+
+    let target = match target {
+        Some(target) => target,
+        None => match applied.len() {
+            0 => return Ok(Vec::new()),
+            n if n >= 2 => applied[n - 2].version,
+            _ => 0,
+        },
+    };
+
+    let reverted: Vec<&MigrationStatus> = applied.iter().filter(|m| m.version > target).collect();
+    if let Some(m) = reverted.iter().find(|m| !m.reversible) {
+        return Err(format!(
+            "migration {} ({}) has no down migration",
+            m.version, m.description
+        ));
+    }
+
+    MIGRATOR
+        .undo(pool, target)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(reverted.iter().rev().map(|m| m.version).collect())
+}
@@ -0,0 +1,415 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use validator::{Validate, ValidationError};
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, Clone, Debug, ApiSchema)]
+pub struct Address {
+    pub id: i64,
+    pub user_id: i64,
+    pub label: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub company: Option<String>,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub postcode: String,
+    pub region: Option<String>,
+    pub country: String,
+    pub phone: Option<String>,
+    pub default_shipping: bool,
+    pub default_billing: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy)]
+pub enum AddressKind {
+    Shipping,
+    Billing,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct AddressInsert {
+    #[validate(length(max = 64, message = "field contains too many characters - max: 64"))]
+    label: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 1,
+            max = 128,
+            message = "field must contain between 1 and 128 characters"
+        )
+    )]
+    first_name: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 1,
+            max = 128,
+            message = "field must contain between 1 and 128 characters"
+        )
+    )]
+    last_name: Option<String>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    company: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 1,
+            max = 256,
+            message = "field must contain between 1 and 256 characters"
+        )
+    )]
+    line1: Option<String>,
+
+    #[validate(length(max = 256, message = "field contains too many characters - max: 256"))]
+    line2: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 1,
+            max = 128,
+            message = "field must contain between 1 and 128 characters"
+        )
+    )]
+    city: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        custom = "validate_postcode"
+    )]
+    postcode: Option<String>,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    region: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        custom = "validate_country"
+    )]
+    country: Option<String>,
+
+    #[validate(length(max = 32, message = "field contains too many characters - max: 32"))]
+    phone: Option<String>,
+
+    default_shipping: Option<bool>,
+    default_billing: Option<bool>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct AddressUpdate {
+    #[validate(length(max = 64, message = "field contains too many characters - max: 64"))]
+    label: Option<String>,
+
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "field must contain between 1 and 128 characters"
+    ))]
+    first_name: Option<String>,
+
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "field must contain between 1 and 128 characters"
+    ))]
+    last_name: Option<String>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    company: Option<String>,
+
+    #[validate(length(
+        min = 1,
+        max = 256,
+        message = "field must contain between 1 and 256 characters"
+    ))]
+    line1: Option<String>,
+
+    #[validate(length(max = 256, message = "field contains too many characters - max: 256"))]
+    line2: Option<String>,
+
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "field must contain between 1 and 128 characters"
+    ))]
+    city: Option<String>,
+
+    #[validate(custom = "validate_postcode")]
+    postcode: Option<String>,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    region: Option<String>,
+
+    #[validate(custom = "validate_country")]
+    country: Option<String>,
+
+    #[validate(length(max = 32, message = "field contains too many characters - max: 32"))]
+    phone: Option<String>,
+
+    default_shipping: Option<bool>,
+    default_billing: Option<bool>,
+}
+
+fn validate_country(country: &str) -> Result<(), ValidationError> {
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        let mut error = ValidationError::new("country");
+        error.message = Some("field must be an ISO 3166-1 alpha-2 country code".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+fn validate_postcode(postcode: &str) -> Result<(), ValidationError> {
+    let postcode = postcode.trim();
+    let valid = (2..=16).contains(&postcode.len())
+        && postcode
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-');
+
+    if !valid {
+        let mut error = ValidationError::new("postcode");
+        error.message = Some("field must be a postcode of 2 to 16 letters and digits".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+impl Address {
+    #[tracing::instrument(name = "Address::find_all", skip_all)]
+    pub async fn find_all(user_id: i64, pool: &PgPool) -> Result<Vec<Address>, String> {
+        sqlx::query_as!(
+            Address,
+            r#"
+				SELECT * FROM user_address
+				WHERE user_id = $1
+				ORDER BY id ASC;
+			"#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Only returns the address if it belongs to `user_id`.
+    #[tracing::instrument(name = "Address::find_by_id", skip_all)]
+    pub async fn find_by_id<'e, E: PgExecutor<'e>>(
+        id: i64,
+        user_id: i64,
+        executor: E,
+    ) -> Result<Option<Address>, String> {
+        sqlx::query_as!(
+            Address,
+            r#"
+				SELECT * FROM user_address
+				WHERE id = $1 AND user_id = $2;
+			"#,
+            id,
+            user_id
+        )
+        .fetch_optional(executor)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// The address picked at checkout when none is given explicitly.
+    #[tracing::instrument(name = "Address::find_default", skip_all)]
+    pub async fn find_default<'e, E: PgExecutor<'e>>(
+        user_id: i64,
+        kind: AddressKind,
+        executor: E,
+    ) -> Result<Option<Address>, String> {
+        sqlx::query_as!(
+            Address,
+            r#"
+				SELECT * FROM user_address
+				WHERE user_id = $1
+					AND CASE WHEN $2 THEN default_shipping ELSE default_billing END;
+			"#,
+            user_id,
+            matches!(kind, AddressKind::Shipping)
+        )
+        .fetch_optional(executor)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Adds an address to the book. The first address becomes the default
+    /// for both shipping and billing.
+    #[tracing::instrument(name = "Address::create", skip_all)]
+    pub async fn create(
+        user_id: i64,
+        input: AddressInsert,
+        pool: &PgPool,
+    ) -> Result<Address, String> {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+        clear_defaults(
+            user_id,
+            input.default_shipping.unwrap_or(false),
+            input.default_billing.unwrap_or(false),
+            &mut tx,
+        )
+        .await?;
+
+        let address = sqlx::query_as!(
+            Address,
+            r#"
+				INSERT INTO user_address (
+					user_id, label, first_name, last_name, company, line1, line2, city, postcode,
+					region, country, phone, default_shipping, default_billing
+				)
+				SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12,
+					COALESCE($13, false) OR NOT EXISTS (SELECT 1 FROM user_address WHERE user_id = $1),
+					COALESCE($14, false) OR NOT EXISTS (SELECT 1 FROM user_address WHERE user_id = $1)
+				RETURNING *;
+			"#,
+            user_id,
+            input.label,
+            input.first_name,
+            input.last_name,
+            input.company,
+            input.line1,
+            input.line2,
+            input.city,
+            input.postcode.map(|p| p.trim().to_uppercase()),
+            input.region,
+            input.country.map(|c| c.to_uppercase()),
+            input.phone,
+            input.default_shipping,
+            input.default_billing
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(address)
+    }
+
+    #[tracing::instrument(name = "Address::update", skip_all)]
+    pub async fn update(
+        id: i64,
+        user_id: i64,
+        input: AddressUpdate,
+        pool: &PgPool,
+    ) -> Result<Option<Address>, String> {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+        if Address::find_by_id(id, user_id, &mut tx).await?.is_none() {
+            return Ok(None);
+        }
+
+        clear_defaults(
+            user_id,
+            input.default_shipping.unwrap_or(false),
+            input.default_billing.unwrap_or(false),
+            &mut tx,
+        )
+        .await?;
+
+        let address = sqlx::query_as!(
+            Address,
+            r#"
+				UPDATE user_address SET
+					label = COALESCE($1, label),
+					first_name = COALESCE($2, first_name),
+					last_name = COALESCE($3, last_name),
+					company = COALESCE($4, company),
+					line1 = COALESCE($5, line1),
+					line2 = COALESCE($6, line2),
+					city = COALESCE($7, city),
+					postcode = COALESCE($8, postcode),
+					region = COALESCE($9, region),
+					country = COALESCE($10, country),
+					phone = COALESCE($11, phone),
+					default_shipping = COALESCE($12, default_shipping),
+					default_billing = COALESCE($13, default_billing),
+					updated_at = NOW()
+				WHERE id = $14 AND user_id = $15
+				RETURNING *;
+			"#,
+            input.label,
+            input.first_name,
+            input.last_name,
+            input.company,
+            input.line1,
+            input.line2,
+            input.city,
+            input.postcode.map(|p| p.trim().to_uppercase()),
+            input.region,
+            input.country.map(|c| c.to_uppercase()),
+            input.phone,
+            input.default_shipping,
+            input.default_billing,
+            id,
+            user_id
+        )
+        .fetch_optional(&mut tx)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(address)
+    }
+
+    #[tracing::instrument(name = "Address::delete", skip_all)]
+    pub async fn delete(id: i64, user_id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM user_address WHERE id = $1 AND user_id = $2;
+			"#,
+            id,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Unsets the current default so a new one can take its place, a user has at
+/// most one default shipping and one default billing address.
+async fn clear_defaults<'e, E: PgExecutor<'e>>(
+    user_id: i64,
+    shipping: bool,
+    billing: bool,
+    executor: E,
+) -> Result<(), String> {
+    sqlx::query!(
+        r#"
+			UPDATE user_address SET
+				default_shipping = default_shipping AND NOT $2,
+				default_billing = default_billing AND NOT $3
+			WHERE user_id = $1 AND ((default_shipping AND $2) OR (default_billing AND $3));
+		"#,
+        user_id,
+        shipping,
+        billing
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
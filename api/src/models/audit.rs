@@ -0,0 +1,249 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+/// A catalog record admins can change, identified by its key.
+#[derive(Clone, Copy)]
+pub enum AuditResource<'a> {
+    Product(i64),
+    Category(i64),
+    Discount(i64),
+    ProductInventory(i64),
+    ProductPrice(i64, &'a str),
+    PriceTier(i64),
+}
+
+impl AuditResource<'_> {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuditResource::Product(_) => "product",
+            AuditResource::Category(_) => "category",
+            AuditResource::Discount(_) => "discount",
+            AuditResource::ProductInventory(_) => "product_inventory",
+            AuditResource::ProductPrice(..) => "product_price",
+            AuditResource::PriceTier(_) => "price_tier",
+        }
+    }
+
+    pub fn id(&self) -> String {
+        match self {
+            AuditResource::Product(id)
+            | AuditResource::Category(id)
+            | AuditResource::Discount(id)
+            | AuditResource::ProductInventory(id)
+            | AuditResource::PriceTier(id) => id.to_string(),
+            AuditResource::ProductPrice(product_id, currency) => {
+                format!("{}/{}", product_id, currency.to_uppercase())
+            }
+        }
+    }
+
+    /// The record as stored, `None` if it doesn't exist.
+    #[tracing::instrument(name = "AuditResource::snapshot", skip_all)]
+    pub async fn snapshot<'e, E: PgExecutor<'e>>(
+        &self,
+        executor: E,
+    ) -> Result<Option<Value>, String> {
+        let row = match *self {
+            AuditResource::Product(id) => sqlx::query_scalar!(
+                r#"
+						SELECT to_jsonb(t.*) as "row!" FROM product t WHERE id = $1;
+					"#,
+                id
+            )
+            .fetch_optional(executor)
+            .await
+            .record_rows(),
+            AuditResource::Category(id) => sqlx::query_scalar!(
+                r#"
+						SELECT to_jsonb(t.*) as "row!" FROM category t WHERE id = $1;
+					"#,
+                id
+            )
+            .fetch_optional(executor)
+            .await
+            .record_rows(),
+            AuditResource::Discount(id) => sqlx::query_scalar!(
+                r#"
+						SELECT to_jsonb(t.*) as "row!" FROM discount t WHERE id = $1;
+					"#,
+                id
+            )
+            .fetch_optional(executor)
+            .await
+            .record_rows(),
+            AuditResource::ProductInventory(id) => sqlx::query_scalar!(
+                r#"
+						SELECT to_jsonb(t.*) as "row!" FROM product_inventory t WHERE id = $1;
+					"#,
+                id
+            )
+            .fetch_optional(executor)
+            .await
+            .record_rows(),
+            AuditResource::ProductPrice(product_id, currency) => sqlx::query_scalar!(
+                r#"
+						SELECT to_jsonb(t.*) as "row!" FROM product_price t
+						WHERE product_id = $1 AND currency = $2;
+					"#,
+                product_id,
+                currency.to_uppercase()
+            )
+            .fetch_optional(executor)
+            .await
+            .record_rows(),
+            AuditResource::PriceTier(id) => sqlx::query_scalar!(
+                r#"
+						SELECT to_jsonb(t.*) as "row!" FROM product_price_tier t WHERE id = $1;
+					"#,
+                id
+            )
+            .fetch_optional(executor)
+            .await
+            .record_rows(),
+        };
+
+        row.map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_id: Option<i64>,
+    pub actor_email: Option<String>,
+    pub resource_type: String,
+    pub resource_id: String,
+    pub action: AuditAction,
+    pub before: Option<Json<Value>>,
+    pub after: Option<Json<Value>>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct AuditParams {
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub actor_id: Option<i64>,
+    /// Defaults to 100, at most 1000.
+    pub limit: Option<i64>,
+}
+
+impl AuditEntry {
+    /// Newest first.
+    #[tracing::instrument(name = "AuditEntry::find", skip_all)]
+    pub async fn find(params: AuditParams, pool: &PgPool) -> Result<Vec<AuditEntry>, String> {
+        sqlx::query_as!(
+            AuditEntry,
+            r#"
+				SELECT a.id, a.actor_id, u.email as "actor_email?", a.resource_type, a.resource_id,
+					a.action as "action: AuditAction",
+					a.before as "before: Json<Value>", a.after as "after: Json<Value>", a.created_at
+				FROM audit_log a
+				LEFT JOIN app_user u ON u.id = a.actor_id
+				WHERE ($1::varchar IS NULL OR a.resource_type = $1)
+					AND ($2::varchar IS NULL OR a.resource_id = $2)
+					AND ($3::bigint IS NULL OR a.actor_id = $3)
+				ORDER BY a.created_at DESC, a.id DESC
+				LIMIT $4;
+			"#,
+            params.resource_type,
+            params.resource_id,
+            params.actor_id,
+            params.limit.unwrap_or(100).clamp(1, 1000)
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Records a change from `before` to `after`, either missing for
+    /// creates and hard deletes. Updates that change nothing aren't
+    /// recorded, moving a record in or out of the trash counts as a delete
+    /// or restore.
+    #[tracing::instrument(name = "AuditEntry::record", skip_all)]
+    pub async fn record<'e, E: PgExecutor<'e>>(
+        actor_id: Option<i64>,
+        resource: &AuditResource<'_>,
+        before: Option<Value>,
+        after: Option<Value>,
+        executor: E,
+    ) -> Result<(), String> {
+        let (action, before, after) = match (before, after) {
+            (None, Some(after)) => (AuditAction::Create, None, Some(after)),
+            (Some(before), None) => (AuditAction::Delete, Some(before), None),
+            (Some(before), Some(after)) => match diff(before, after) {
+                Some((before, after)) => (trash_action(&before, &after), Some(before), Some(after)),
+                None => return Ok(()),
+            },
+            (None, None) => return Ok(()),
+        };
+
+        sqlx::query!(
+            r#"
+				INSERT INTO audit_log (actor_id, resource_type, resource_id, action, before, after)
+				VALUES ($1, $2, $3, $4, $5, $6);
+			"#,
+            actor_id,
+            resource.kind(),
+            resource.id(),
+            action as _,
+            before,
+            after
+        )
+        .execute(executor)
+        .await
+        .record_rows()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Keeps only the fields that differ between two versions of a row,
+/// `updated_at` alone doesn't count as a change.
+fn diff(before: Value, after: Value) -> Option<(Value, Value)> {
+    let (Value::Object(before), Value::Object(after)) = (before, after) else {
+        return None;
+    };
+
+    let mut changed_before = Map::new();
+    let mut changed_after = Map::new();
+
+    for (key, value) in after {
+        let old = before.get(&key).cloned().unwrap_or(Value::Null);
+        if old != value && key != "updated_at" {
+            changed_before.insert(key.clone(), old);
+            changed_after.insert(key, value);
+        }
+    }
+
+    if changed_after.is_empty() {
+        return None;
+    }
+
+    Some((Value::Object(changed_before), Value::Object(changed_after)))
+}
+
+fn trash_action(before: &Value, after: &Value) -> AuditAction {
+    match (before.get("deleted_at"), after.get("deleted_at")) {
+        (Some(Value::Null), Some(deleted_at)) if !deleted_at.is_null() => AuditAction::Delete,
+        (Some(deleted_at), Some(Value::Null)) if !deleted_at.is_null() => AuditAction::Restore,
+        _ => AuditAction::Update,
+    }
+}
@@ -1,58 +1,103 @@
-use jsonwebtoken::{TokenData, DecodingKey, Validation};
-use rocket::http::Status;
-use rocket::Request;
-use rocket::request::{FromRequest, Outcome};
+use axum::extract::{FromRequest, RequestParts};
+use axum::http::{header::AUTHORIZATION, StatusCode};
+use axum::Json;
+use jsonwebtoken::{TokenData, Validation};
 use serde::{Deserialize, Serialize};
-use crate::auth::JwtPublicKey;
+
+use crate::auth::JwtKeys;
+use crate::errors::ApiError;
 
 #[derive(Serialize, Deserialize)]
 pub struct JwtToken {
-	iat: i64,
-	exp: i64,
-	user_role: Vec<String>
+    pub sub: i64,
+    pub iat: i64,
+    pub exp: i64,
+    pub user_role: Vec<String>,
 }
 
 impl JwtToken {
-	pub fn is_admin(&self) -> bool {
-		self.user_role.contains(&"admin".to_string())
-	}
+    pub fn is_admin(&self) -> bool {
+        self.user_role.iter().any(|r| r == "admin")
+    }
+}
+
+#[axum::async_trait]
+impl<B: Send> FromRequest<B> for JwtToken {
+    type Rejection = (StatusCode, Json<ApiError>);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let keys = match request.extensions().get::<JwtKeys>() {
+            Some(k) => k,
+            None => return Err(ApiError::internal_server_error("JWT keys not configured")),
+        };
+
+        let auth_header = match request.headers().get(AUTHORIZATION) {
+            Some(a) => a.to_str().unwrap_or_default(),
+            None => return Err(unauthorized("Authorization header not found")),
+        };
+
+        match auth_header.strip_prefix("Bearer") {
+            Some(token) => match decode_token(token.trim(), keys) {
+                Ok(t) => {
+                    tracing::Span::current().record("user_id", t.claims.sub);
+                    Ok(t.claims)
+                }
+                Err(e) => Err(unauthorized(&e)),
+            },
+            None => Err(unauthorized(
+                "Authentication string does not contain 'Bearer'",
+            )),
+        }
+    }
+}
+
+/// The token of a logged in customer on routes that guests can use too.
+/// Requests without an Authorization header are guests, an invalid token is
+/// rejected with 401 rather than treated as a guest.
+pub struct OptionalJwtToken(pub Option<JwtToken>);
+
+#[axum::async_trait]
+impl<B: Send> FromRequest<B> for OptionalJwtToken {
+    type Rejection = (StatusCode, Json<ApiError>);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        if !request.headers().contains_key(AUTHORIZATION) {
+            return Ok(OptionalJwtToken(None));
+        }
+
+        JwtToken::from_request(request)
+            .await
+            .map(|t| OptionalJwtToken(Some(t)))
+    }
 }
 
-#[rocket::async_trait]
-impl<'r> FromRequest<'r> for JwtToken {
-	type Error = String;
-
-	async fn from_request(request: &'r Request<'_>) ->  Outcome<Self, Self::Error> {
-		let secrets = request.rocket().state::<JwtPublicKey>().unwrap();
-
-		let auth_header = match request.headers().get_one("Authorization") {
-			Some(a) => a,
-			None => return return_outcome("Authorization header not found"),
-		};
-
-		let auth_string = auth_header.to_string();
-		if auth_string.starts_with("Bearer") {
-			let token = auth_string[6..auth_string.len()].trim();
-			match decode_token(token, &secrets.0) {
-				Ok(t) => Outcome::Success(t.claims),
-				Err(e) => return_outcome(&e),
-			}
-		} else {
-			return return_outcome("Authentication string does not containt 'Bearer'")
-		}
-	}
+/// Proof of a token with the admin role, everyone else is turned away with
+/// 403.
+pub struct AdminToken;
+
+#[axum::async_trait]
+impl<B: Send> FromRequest<B> for AdminToken {
+    type Rejection = (StatusCode, Json<ApiError>);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let token = JwtToken::from_request(request).await?;
+
+        if !token.is_admin() {
+            return Err(ApiError::with_status(
+                StatusCode::FORBIDDEN,
+                "Admin role required",
+            ));
+        }
+
+        Ok(AdminToken)
+    }
 }
 
-fn decode_token(token: &str, public_key: &DecodingKey) -> Result<TokenData<JwtToken>, String> {
-	match jsonwebtoken::decode::<JwtToken>(&token, public_key, &Validation::new(jsonwebtoken::Algorithm::RS256)) {
-		Ok(t) => Ok(t),
-		Err(e) => Err(e.to_string()),
-	}
+fn decode_token(token: &str, keys: &JwtKeys) -> Result<TokenData<JwtToken>, String> {
+    jsonwebtoken::decode::<JwtToken>(token, &keys.decoding, &Validation::default())
+        .map_err(|e| e.to_string())
 }
 
-fn return_outcome(reason: &str) -> Outcome<JwtToken, String> {
-	Outcome::Failure((
-		Status::BadRequest,
-		reason.to_string()
-	))
-}
\ No newline at end of file
+fn unauthorized(reason: &str) -> (StatusCode, Json<ApiError>) {
+    ApiError::with_status(StatusCode::UNAUTHORIZED, reason)
+}
@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
 use validator::Validate;
 
-#[derive(Serialize, Deserialize, Clone)]
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
 pub struct Category {
     pub id: i64,
     pub name: String,
@@ -10,45 +14,54 @@ pub struct Category {
     pub children: Vec<Category>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
 pub struct CategoryDb {
     pub id: i64,
     pub name: String,
     pub parent_id: Option<i64>,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
 pub struct CategoryInsert {
     #[validate(
         required(message = "this field is required"),
         length(max = 128, message = "field contains too many characters - max: 128")
     )]
     name: Option<String>,
-    parent_id: Option<i64>,
+    pub parent_id: Option<i64>,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
 pub struct CategoryUpdate {
     #[validate(length(max = 128, message = "CONST:TOO_MANY_CHARACTERS_128"))]
     name: Option<String>,
 
-    parent_id: Option<i64>,
+    pub parent_id: Option<i64>,
+}
+
+/// A category moved in or out of the trash, with its row as it was before.
+pub struct TrashedCategory {
+    pub id: i64,
+    pub before: Value,
 }
 
 impl Category {
+    #[tracing::instrument(name = "Category::find_all", skip_all)]
     pub async fn find_all(pool: &PgPool) -> Result<Vec<Category>, String> {
         sqlx::query_as!(
             CategoryDb,
             r#"
-				SELECT id, name, parent_id FROM category ORDER BY id ASC;
+				SELECT id, name, parent_id FROM category WHERE deleted_at IS NULL ORDER BY id ASC;
 			"#
         )
         .fetch_all(pool)
         .await
-        .map(|mut c| sort_categories(&mut c))
+        .record_rows()
+        .map(|c| sort_categories(&c))
         .map_err(|e| e.to_string())
     }
 
+    #[tracing::instrument(name = "Category::find_by_id", skip_all)]
     pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Vec<Category>, String> {
         sqlx::query_as_unchecked!(
             CategoryDb,
@@ -56,9 +69,9 @@ impl Category {
 				WITH RECURSIVE category_tree AS 
 				(
 					SELECT c1.id, c1.name, c1.parent_id FROM category c1
-					WHERE c1.id = $1
+					WHERE c1.id = $1 AND c1.deleted_at IS NULL
 					
-					UNION ALL
+					UNION
 					
 					SELECT c2.id, c2.name, c2.parent_id FROM category c2
 					JOIN category_tree ct ON ct.parent_id = c2.id 
@@ -69,11 +82,58 @@ impl Category {
         )
         .fetch_all(pool)
         .await
-        .map(|mut c| sort_categories(&mut c))
+        .record_rows()
+        .map(|c| sort_categories(&c))
+        .map_err(|e| e.to_string())
+    }
+
+    /// Whether the category exists and isn't in the trash.
+    #[tracing::instrument(name = "Category::exists", skip_all)]
+    pub async fn exists<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<bool, String> {
+        sqlx::query_scalar!(
+            r#"
+				SELECT EXISTS (SELECT 1 FROM category WHERE id = $1 AND deleted_at IS NULL) as "exists!";
+			"#,
+            id
+        )
+        .fetch_one(executor)
+        .await
         .map_err(|e| e.to_string())
     }
 
-    pub async fn create(input: CategoryInsert, pool: &PgPool) -> Result<Category, String> {
+    /// Whether `id` is `root` or one of its subcategories, trashed ones
+    /// included. Filing `root` under such a category would make a cycle.
+    #[tracing::instrument(name = "Category::is_in_subtree", skip_all)]
+    pub async fn is_in_subtree<'e, E: PgExecutor<'e>>(
+        id: i64,
+        root: i64,
+        executor: E,
+    ) -> Result<bool, String> {
+        sqlx::query_scalar!(
+            r#"
+				WITH RECURSIVE subtree AS (
+					SELECT id FROM category WHERE id = $1
+
+					UNION
+
+					SELECT c.id FROM category c
+					JOIN subtree s ON c.parent_id = s.id
+				)
+				SELECT EXISTS (SELECT 1 FROM subtree WHERE id = $2) as "exists!";
+			"#,
+            root,
+            id
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Category::create", skip_all)]
+    pub async fn create<'e, E: PgExecutor<'e>>(
+        input: CategoryInsert,
+        executor: E,
+    ) -> Result<Category, String> {
         sqlx::query_as!(
             CategoryDb,
             r#"
@@ -83,42 +143,111 @@ impl Category {
             input.name,
             input.parent_id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map(|c| Category::from_db(&c))
         .map_err(|e| e.to_string())
     }
 
-    pub async fn update(id: i64, input: CategoryUpdate, pool: &PgPool) -> Result<Category, String> {
+    #[tracing::instrument(name = "Category::update", skip_all)]
+    pub async fn update<'e, E: PgExecutor<'e>>(
+        id: i64,
+        input: CategoryUpdate,
+        executor: E,
+    ) -> Result<Category, String> {
         sqlx::query_as!(
             CategoryDb,
             r#"
 				UPDATE category SET 
 					name = COALESCE($1, name),
 					parent_id = COALESCE($2, parent_id)
-				WHERE id = $3
+				WHERE id = $3 AND deleted_at IS NULL
 				RETURNING id, name, parent_id;
 			"#,
             input.name,
             input.parent_id,
             id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map(|c| Category::from_db(&c))
         .map_err(|e| e.to_string())
     }
 
-    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
-        sqlx::query!(
+    /// Moves the category and all of its subcategories to the trash.
+    #[tracing::instrument(name = "Category::delete", skip_all)]
+    pub async fn delete<'e, E: PgExecutor<'e>>(
+        id: i64,
+        executor: E,
+    ) -> Result<Vec<TrashedCategory>, String> {
+        sqlx::query_as!(
+            TrashedCategory,
             r#"
-				DELETE FROM category WHERE id = $1;
+				WITH RECURSIVE subtree AS (
+					SELECT id FROM category WHERE id = $1 AND deleted_at IS NULL
+
+					UNION
+
+					SELECT c.id FROM category c
+					JOIN subtree s ON c.parent_id = s.id
+					WHERE c.deleted_at IS NULL
+				)
+				UPDATE category c SET deleted_at = NOW()
+				FROM category before
+				WHERE before.id = c.id AND c.id IN (SELECT id FROM subtree)
+				RETURNING c.id, to_jsonb(before.*) as "before!";
+			"#,
+            id
+        )
+        .fetch_all(executor)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Takes the category out of the trash together with the subcategories
+    /// deleted along with it, and any deleted parents so it's reachable.
+    #[tracing::instrument(name = "Category::restore", skip_all)]
+    pub async fn restore<'e, E: PgExecutor<'e>>(
+        id: i64,
+        executor: E,
+    ) -> Result<Vec<TrashedCategory>, String> {
+        sqlx::query_as!(
+            TrashedCategory,
+            r#"
+				WITH RECURSIVE target AS (
+					SELECT id, parent_id, deleted_at FROM category
+					WHERE id = $1 AND deleted_at IS NOT NULL
+				),
+				subtree AS (
+					SELECT id FROM target
+
+					UNION
+
+					SELECT c.id FROM category c
+					JOIN subtree s ON c.parent_id = s.id
+					WHERE c.deleted_at = (SELECT deleted_at FROM target)
+				),
+				ancestors AS (
+					SELECT parent_id as id FROM target
+
+					UNION
+
+					SELECT c.parent_id FROM category c
+					JOIN ancestors a ON c.id = a.id
+				)
+				UPDATE category c SET deleted_at = NULL
+				FROM category before
+				WHERE before.id = c.id
+					AND c.deleted_at IS NOT NULL
+					AND (c.id IN (SELECT id FROM subtree) OR c.id IN (SELECT id FROM ancestors))
+				RETURNING c.id, to_jsonb(before.*) as "before!";
 			"#,
             id
         )
-        .execute(pool)
+        .fetch_all(executor)
         .await
-        .map(|result| result.rows_affected())
+        .record_rows()
         .map_err(|e| e.to_string())
     }
 
@@ -132,11 +261,11 @@ impl Category {
     }
 }
 
-fn sort_categories(categories: &mut Vec<CategoryDb>) -> Vec<Category> {
+fn sort_categories(categories: &[CategoryDb]) -> Vec<Category> {
     let mut root_categories = categories
         .iter()
-        .filter(|c| c.parent_id == None)
-        .map(|c| Category::from_db(c))
+        .filter(|c| c.parent_id.is_none())
+        .map(Category::from_db)
         .collect::<Vec<Category>>();
 
     for cat in root_categories.iter_mut() {
@@ -147,7 +276,7 @@ fn sort_categories(categories: &mut Vec<CategoryDb>) -> Vec<Category> {
 }
 
 // TODO: this needs to be optimized
-fn recursive_sort(root_category: &mut Category, all_categories: &mut Vec<CategoryDb>) {
+fn recursive_sort(root_category: &mut Category, all_categories: &[CategoryDb]) {
     for category in all_categories.iter() {
         if let Some(parent_id) = category.parent_id {
             if parent_id == root_category.id {
@@ -156,7 +285,7 @@ fn recursive_sort(root_category: &mut Category, all_categories: &mut Vec<Categor
         }
     }
 
-    let mut new_categories = all_categories.clone();
+    let mut new_categories = all_categories.to_vec();
     for child_category in root_category.children.iter_mut() {
         new_categories.swap_remove(
             new_categories
@@ -171,6 +300,6 @@ fn recursive_sort(root_category: &mut Category, all_categories: &mut Vec<Categor
     }
 
     for child_category in root_category.children.iter_mut() {
-        recursive_sort(child_category, &mut new_categories);
+        recursive_sort(child_category, &new_categories);
     }
 }
@@ -0,0 +1,107 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::types::Decimal;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::document_sequence;
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, ApiSchema)]
+pub struct CreditNote {
+    pub id: i64,
+    pub number: String,
+    pub order_id: i64,
+    pub return_id: Option<i64>,
+    pub payment_id: Option<i64>,
+    pub refund_id: Option<i64>,
+    pub currency: String,
+    pub amount: Decimal,
+    pub created_at: NaiveDateTime,
+}
+
+impl CreditNote {
+    #[tracing::instrument(name = "CreditNote::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Option<CreditNote>, String> {
+        sqlx::query_as!(
+            CreditNote,
+            r#"
+				SELECT id, number, order_id, return_id, payment_id, refund_id, currency, amount, created_at
+				FROM credit_note
+				WHERE id = $1;
+			"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "CreditNote::find_by_order", skip_all)]
+    pub async fn find_by_order(order_id: i64, pool: &PgPool) -> Result<Vec<CreditNote>, String> {
+        sqlx::query_as!(
+            CreditNote,
+            r#"
+				SELECT id, number, order_id, return_id, payment_id, refund_id, currency, amount, created_at
+				FROM credit_note
+				WHERE order_id = $1
+				ORDER BY id ASC;
+			"#,
+            order_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "CreditNote::find_by_refund", skip_all)]
+    pub async fn find_by_refund(
+        refund_id: i64,
+        pool: &PgPool,
+    ) -> Result<Option<CreditNote>, String> {
+        sqlx::query_as!(
+            CreditNote,
+            r#"
+				SELECT id, number, order_id, return_id, payment_id, refund_id, currency, amount, created_at
+				FROM credit_note
+				WHERE refund_id = $1;
+			"#,
+            refund_id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "CreditNote::create_in", skip_all)]
+    pub async fn create_in(
+        order_id: i64,
+        return_id: Option<i64>,
+        payment_id: Option<i64>,
+        refund_id: Option<i64>,
+        amount: Decimal,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<CreditNote, sqlx::Error> {
+        let number = document_sequence::next_number("CN", &mut *tx).await?;
+
+        sqlx::query_as!(
+            CreditNote,
+            r#"
+				INSERT INTO credit_note (number, order_id, return_id, payment_id, refund_id, currency, amount)
+				SELECT $1, $2, $3, $4, $5, currency, $6 FROM customer_order WHERE id = $2
+				RETURNING id, number, order_id, return_id, payment_id, refund_id, currency, amount, created_at;
+			"#,
+            number,
+            order_id,
+            return_id,
+            payment_id,
+            refund_id,
+            amount
+        )
+        .fetch_one(tx)
+        .await
+    }
+}
@@ -0,0 +1,401 @@
+use axum::extract::{FromRequest, RequestParts};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::NaiveDateTime;
+use rust_decimal::RoundingStrategy;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use sqlx::{PgExecutor, PgPool};
+use validator::{Validate, ValidationError};
+
+use super::authentication::JwtToken;
+use crate::errors::ApiError;
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+/// Request header selecting the price list, e.g. `X-Currency: USD`.
+pub const CURRENCY_HEADER: &str = "x-currency";
+
+/// Catalog prices are kept in the base currency, other currencies either
+/// have a per-product price or are converted with `exchange_rate`.
+#[derive(Serialize, Deserialize, Clone, Debug, ApiSchema)]
+pub struct Currency {
+    pub code: String,
+    pub name: String,
+    pub minor_units: i16,
+    pub exchange_rate: Decimal,
+    pub is_base: bool,
+    pub active: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct CurrencyInsert {
+    #[validate(required(message = "this field is required"), custom = "validate_code")]
+    code: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 1,
+            max = 64,
+            message = "field must contain between 1 and 64 characters"
+        )
+    )]
+    name: Option<String>,
+
+    /// Amounts of orders, payments and credit notes are stored with two
+    /// decimal places, so currencies can't have more minor units.
+    #[validate(range(
+        min = 0,
+        max = 2,
+        message = "field contains invalid value - min: 0, max: 2"
+    ))]
+    minor_units: Option<i16>,
+
+    #[validate(required(message = "this field is required"), custom = "validate_rate")]
+    exchange_rate: Option<Decimal>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct CurrencyUpdate {
+    #[validate(length(
+        min = 1,
+        max = 64,
+        message = "field must contain between 1 and 64 characters"
+    ))]
+    name: Option<String>,
+
+    #[validate(range(
+        min = 0,
+        max = 2,
+        message = "field contains invalid value - min: 0, max: 2"
+    ))]
+    minor_units: Option<i16>,
+
+    #[validate(custom = "validate_rate")]
+    exchange_rate: Option<Decimal>,
+
+    active: Option<bool>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct ProductPrice {
+    pub product_id: i64,
+    pub currency: String,
+    pub price: Decimal,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ProductPriceUpdate {
+    #[validate(
+        required(message = "this field is required"),
+        custom = "super::validate_decimal"
+    )]
+    price: Option<Decimal>,
+}
+
+fn validate_code(code: &str) -> Result<(), ValidationError> {
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        let mut error = ValidationError::new("code");
+        error.message = Some("field must be an ISO 4217 currency code".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+fn validate_rate(rate: &Decimal) -> Result<(), ValidationError> {
+    if *rate <= Decimal::ZERO {
+        let mut error = ValidationError::new("range");
+        error.message = Some("field must be greater than 0".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+impl Currency {
+    /// Rounds to the currency's minor units, halves away from zero.
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        amount.round_dp_with_strategy(
+            self.minor_units as u32,
+            RoundingStrategy::MidpointAwayFromZero,
+        )
+    }
+
+    /// Converts an amount in the base currency.
+    pub fn convert(&self, base_amount: Decimal) -> Decimal {
+        self.round(base_amount * self.exchange_rate)
+    }
+
+    #[tracing::instrument(name = "Currency::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<Currency>, String> {
+        sqlx::query_as!(
+            Currency,
+            r#"
+				SELECT * FROM currency
+				ORDER BY is_base DESC, code ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Currency::find_by_code", skip_all)]
+    pub async fn find_by_code<'e, E: PgExecutor<'e>>(
+        code: &str,
+        executor: E,
+    ) -> Result<Option<Currency>, String> {
+        sqlx::query_as!(
+            Currency,
+            r#"
+				SELECT * FROM currency
+				WHERE code = $1;
+			"#,
+            code.to_uppercase()
+        )
+        .fetch_optional(executor)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Currency::find_base", skip_all)]
+    pub async fn find_base<'e, E: PgExecutor<'e>>(executor: E) -> Result<Currency, String> {
+        sqlx::query_as!(
+            Currency,
+            r#"
+				SELECT * FROM currency
+				WHERE is_base;
+			"#
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Currency::create", skip_all)]
+    pub async fn create(input: CurrencyInsert, pool: &PgPool) -> Result<Currency, String> {
+        sqlx::query_as!(
+            Currency,
+            r#"
+				INSERT INTO currency (code, name, minor_units, exchange_rate)
+				VALUES ($1, $2, COALESCE($3, 2::smallint), $4)
+				RETURNING *;
+			"#,
+            input.code.map(|c| c.to_uppercase()),
+            input.name,
+            input.minor_units,
+            input.exchange_rate
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// The base currency's rate is fixed at 1 and it can't be deactivated.
+    #[tracing::instrument(name = "Currency::update", skip_all)]
+    pub async fn update(
+        code: &str,
+        input: CurrencyUpdate,
+        pool: &PgPool,
+    ) -> Result<Option<Currency>, String> {
+        sqlx::query_as!(
+            Currency,
+            r#"
+				UPDATE currency SET
+					name = COALESCE($1, name),
+					minor_units = COALESCE($2, minor_units),
+					exchange_rate = CASE WHEN is_base THEN 1 ELSE COALESCE($3, exchange_rate) END,
+					active = is_base OR COALESCE($4, active),
+					updated_at = NOW()
+				WHERE code = $5
+				RETURNING *;
+			"#,
+            input.name,
+            input.minor_units,
+            input.exchange_rate,
+            input.active,
+            code.to_uppercase()
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Imports exchange rates from a local file with one `CODE,rate` pair per
+    /// line, `#` starts a comment. Unknown currencies are added with two
+    /// minor units, the base currency is skipped. Returns the number of
+    /// rates imported.
+    #[tracing::instrument(name = "Currency::import_rates", skip_all)]
+    pub async fn import_rates(path: &str, pool: &PgPool) -> Result<u64, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let mut imported = 0;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parsed = line.split_once(',').and_then(|(code, rate)| {
+                let code = code.trim().to_uppercase();
+                let rate = rate.trim().parse::<Decimal>().ok()?;
+                (validate_code(&code).is_ok() && rate > Decimal::ZERO).then_some((code, rate))
+            });
+            let (code, rate) = match parsed {
+                Some(p) => p,
+                None => return Err(format!("{}:{}: invalid exchange rate", path, number + 1)),
+            };
+
+            imported += sqlx::query!(
+                r#"
+					INSERT INTO currency (code, name, exchange_rate)
+					VALUES ($1, $2, $3)
+					ON CONFLICT (code) DO UPDATE SET
+						exchange_rate = EXCLUDED.exchange_rate,
+						updated_at = NOW()
+					WHERE NOT currency.is_base;
+				"#,
+                code,
+                code,
+                rate
+            )
+            .execute(&mut tx)
+            .await
+            .record_rows()
+            .map_err(|e| e.to_string())?
+            .rows_affected();
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(imported)
+    }
+}
+
+impl ProductPrice {
+    #[tracing::instrument(name = "ProductPrice::find_by_product", skip_all)]
+    pub async fn find_by_product(
+        product_id: i64,
+        pool: &PgPool,
+    ) -> Result<Vec<ProductPrice>, String> {
+        sqlx::query_as!(
+            ProductPrice,
+            r#"
+				SELECT product_id, currency, price, updated_at FROM product_price
+				WHERE product_id = $1
+				ORDER BY currency ASC;
+			"#,
+            product_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Sets the price of a product in `currency`, replacing the converted
+    /// base price.
+    #[tracing::instrument(name = "ProductPrice::set", skip_all)]
+    pub async fn set<'e, E: PgExecutor<'e>>(
+        product_id: i64,
+        currency: &str,
+        input: ProductPriceUpdate,
+        executor: E,
+    ) -> Result<ProductPrice, String> {
+        sqlx::query_as!(
+            ProductPrice,
+            r#"
+				INSERT INTO product_price (product_id, currency, price)
+				VALUES ($1, $2, $3)
+				ON CONFLICT (product_id, currency) DO UPDATE SET
+					price = EXCLUDED.price,
+					updated_at = NOW()
+				RETURNING product_id, currency, price, updated_at;
+			"#,
+            product_id,
+            currency.to_uppercase(),
+            input.price
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ProductPrice::delete", skip_all)]
+    pub async fn delete<'e, E: PgExecutor<'e>>(
+        product_id: i64,
+        currency: &str,
+        executor: E,
+    ) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM product_price WHERE product_id = $1 AND currency = $2;
+			"#,
+            product_id,
+            currency.to_uppercase()
+        )
+        .execute(executor)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// The price list a request is served from: the `X-Currency` header, then
+/// the logged in customer's preferred currency, then the base currency.
+pub struct SelectedCurrency(pub Currency);
+
+#[axum::async_trait]
+impl<B: Send> FromRequest<B> for SelectedCurrency {
+    type Rejection = (StatusCode, Json<ApiError>);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let pool = match request.extensions().get::<PgPool>() {
+            Some(p) => p.clone(),
+            None => return Err(ApiError::internal_server_error("database not configured")),
+        };
+
+        let mut code = request
+            .headers()
+            .get(CURRENCY_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.trim().to_uppercase());
+
+        if code.is_none() {
+            if let Ok(token) = JwtToken::from_request(request).await {
+                code = sqlx::query!(
+                    r#"
+						SELECT currency FROM app_user WHERE id = $1;
+					"#,
+                    token.sub
+                )
+                .fetch_optional(&pool)
+                .await
+                .map_err(|e| ApiError::internal_server_error(&e.to_string()))?
+                .and_then(|r| r.currency);
+            }
+        }
+
+        let currency = match code {
+            Some(code) => Currency::find_by_code(&code, &pool)
+                .await
+                .map_err(|e| ApiError::internal_server_error(&e))?
+                .filter(|c| c.active)
+                .ok_or_else(|| ApiError::bad_request(&format!("unsupported currency: {}", code)))?,
+            None => Currency::find_base(&pool)
+                .await
+                .map_err(|e| ApiError::internal_server_error(&e))?,
+        };
+
+        Ok(SelectedCurrency(currency))
+    }
+}
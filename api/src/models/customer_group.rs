@@ -0,0 +1,197 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use validator::Validate;
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+/// Customers sharing negotiated prices, e.g. resellers. A customer belongs
+/// to at most one group.
+#[derive(Serialize, ApiSchema)]
+pub struct CustomerGroup {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct CustomerGroupInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 1,
+            max = 128,
+            message = "field must contain between 1 and 128 characters"
+        )
+    )]
+    name: Option<String>,
+
+    #[validate(length(max = 500, message = "field contains too many characters - max: 500"))]
+    description: Option<String>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct CustomerGroupUpdate {
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "field must contain between 1 and 128 characters"
+    ))]
+    name: Option<String>,
+
+    #[validate(length(max = 500, message = "field contains too many characters - max: 500"))]
+    description: Option<String>,
+}
+
+impl CustomerGroup {
+    #[tracing::instrument(name = "CustomerGroup::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<CustomerGroup>, String> {
+        sqlx::query_as!(
+            CustomerGroup,
+            r#"
+				SELECT id, name, description, created_at, updated_at
+				FROM customer_group
+				ORDER BY name ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "CustomerGroup::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Option<CustomerGroup>, String> {
+        sqlx::query_as!(
+            CustomerGroup,
+            r#"
+				SELECT id, name, description, created_at, updated_at
+				FROM customer_group
+				WHERE id = $1;
+			"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// The group a customer buys as, if any.
+    #[tracing::instrument(name = "CustomerGroup::id_of_user", skip_all)]
+    pub async fn id_of_user<'e, E: PgExecutor<'e>>(
+        user_id: i64,
+        executor: E,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query!(
+            r#"
+				SELECT customer_group_id FROM app_user WHERE id = $1;
+			"#,
+            user_id
+        )
+        .fetch_optional(executor)
+        .await
+        .record_rows()
+        .map(|r| r.and_then(|r| r.customer_group_id))
+    }
+
+    #[tracing::instrument(name = "CustomerGroup::create", skip_all)]
+    pub async fn create(
+        input: CustomerGroupInsert,
+        pool: &PgPool,
+    ) -> Result<CustomerGroup, String> {
+        sqlx::query_as!(
+            CustomerGroup,
+            r#"
+				INSERT INTO customer_group (name, description)
+				VALUES ($1, $2)
+				RETURNING id, name, description, created_at, updated_at;
+			"#,
+            input.name,
+            input.description
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "CustomerGroup::update", skip_all)]
+    pub async fn update(
+        id: i64,
+        input: CustomerGroupUpdate,
+        pool: &PgPool,
+    ) -> Result<Option<CustomerGroup>, String> {
+        sqlx::query_as!(
+            CustomerGroup,
+            r#"
+				UPDATE customer_group SET
+					name = COALESCE($1, name),
+					description = COALESCE($2, description),
+					updated_at = NOW()
+				WHERE id = $3
+				RETURNING id, name, description, created_at, updated_at;
+			"#,
+            input.name,
+            input.description,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Members drop back to regular prices, the group's tiers go with it.
+    #[tracing::instrument(name = "CustomerGroup::delete", skip_all)]
+    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM customer_group WHERE id = $1;
+			"#,
+            id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Moves a customer into the group, out of any other.
+    #[tracing::instrument(name = "CustomerGroup::add_user", skip_all)]
+    pub async fn add_user(id: i64, user_id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				UPDATE app_user SET customer_group_id = $1, updated_at = NOW()
+				WHERE id = $2;
+			"#,
+            id,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "CustomerGroup::remove_user", skip_all)]
+    pub async fn remove_user(id: i64, user_id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				UPDATE app_user SET customer_group_id = NULL, updated_at = NOW()
+				WHERE id = $2 AND customer_group_id = $1;
+			"#,
+            id,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Decimal;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use validator::Validate;
 
-#[derive(Serialize, Deserialize)]
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, ApiSchema)]
 pub struct Discount {
     pub id: i64,
     pub name: String,
@@ -15,7 +18,7 @@ pub struct Discount {
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
 pub struct DiscountInsert {
     #[validate(
         required(message = "this field is required"),
@@ -32,7 +35,7 @@ pub struct DiscountInsert {
     active: bool,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
 pub struct DiscountUpdate {
     #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
     name: Option<String>,
@@ -46,23 +49,25 @@ pub struct DiscountUpdate {
 }
 
 impl Discount {
+    #[tracing::instrument(name = "Discount::find_all", skip_all)]
     pub async fn find_all(pool: &PgPool) -> Result<Vec<Discount>, String> {
         sqlx::query_as!(
 			Discount,
 			r#"
-				SELECT id, name, description, discount_percent, active, created_at, updated_at FROM discount ORDER BY id ASC;
+				SELECT id, name, description, discount_percent, active, created_at, updated_at FROM discount WHERE deleted_at IS NULL ORDER BY id ASC;
 			"#
 		)
 		.fetch_all(pool)
-		.await
+		.await.record_rows()
 		.map_err(|e| e.to_string())
     }
 
+    #[tracing::instrument(name = "Discount::find_by_id", skip_all)]
     pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Discount, String> {
         sqlx::query_as!(
 			Discount,
 			r#"
-				SELECT id, name, description, discount_percent, active, created_at, updated_at FROM discount WHERE id = $1;
+				SELECT id, name, description, discount_percent, active, created_at, updated_at FROM discount WHERE id = $1 AND deleted_at IS NULL;
 			"#,
 			id
 		)
@@ -71,7 +76,11 @@ impl Discount {
 		.map_err(|e| e.to_string())
     }
 
-    pub async fn create(input: DiscountInsert, pool: &PgPool) -> Result<Discount, String> {
+    #[tracing::instrument(name = "Discount::create", skip_all)]
+    pub async fn create<'e, E: PgExecutor<'e>>(
+        input: DiscountInsert,
+        executor: E,
+    ) -> Result<Discount, String> {
         sqlx::query_as!(
             Discount,
             r#"
@@ -84,36 +93,43 @@ impl Discount {
             input.discount_percent,
             input.active
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map_err(|e| e.to_string())
     }
 
-    pub async fn set_active(id: i64, pool: &PgPool) -> Result<u64, String> {
+    #[tracing::instrument(name = "Discount::set_active", skip_all)]
+    pub async fn set_active<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
         sqlx::query_as!(
             Discount,
-            r#"UPDATE discount SET active = true, updated_at = NOW() WHERE id = $1;"#,
+            r#"UPDATE discount SET active = true, updated_at = NOW() WHERE id = $1 AND deleted_at IS NULL;"#,
             id
         )
-        .execute(pool)
-        .await
+        .execute(executor)
+        .await.record_rows()
         .map(|r| r.rows_affected())
         .map_err(|e| e.to_string())
     }
 
-    pub async fn set_inactive(id: i64, pool: &PgPool) -> Result<u64, String> {
+    #[tracing::instrument(name = "Discount::set_inactive", skip_all)]
+    pub async fn set_inactive<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
         sqlx::query_as!(
             Discount,
-            r#"UPDATE discount SET active = false, updated_at = NOW() WHERE id = $1;"#,
+            r#"UPDATE discount SET active = false, updated_at = NOW() WHERE id = $1 AND deleted_at IS NULL;"#,
             id
         )
-        .execute(pool)
-        .await
+        .execute(executor)
+        .await.record_rows()
         .map(|r| r.rows_affected())
         .map_err(|e| e.to_string())
     }
 
-    pub async fn update(id: i64, input: DiscountUpdate, pool: &PgPool) -> Result<Discount, String> {
+    #[tracing::instrument(name = "Discount::update", skip_all)]
+    pub async fn update<'e, E: PgExecutor<'e>>(
+        id: i64,
+        input: DiscountUpdate,
+        executor: E,
+    ) -> Result<Discount, String> {
         sqlx::query_as!(
             Discount,
             r#"
@@ -123,7 +139,7 @@ impl Discount {
 					discount_percent = COALESCE($3, discount_percent),
 					active = COALESCE($4, active),
 					updated_at = NOW()
-				WHERE id = $5
+				WHERE id = $5 AND deleted_at IS NULL
 				RETURNING id, name, description, discount_percent, active, created_at, updated_at;
 			"#,
             input.name,
@@ -132,20 +148,39 @@ impl Discount {
             input.active,
             id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Moves the discount to the trash, products keep referring to it but
+    /// are sold at full price until it's restored.
+    #[tracing::instrument(name = "Discount::delete", skip_all)]
+    pub async fn delete<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				UPDATE discount SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL;
+			"#,
+            id
+        )
+        .execute(executor)
         .await
+        .record_rows()
+        .map(|r| r.rows_affected())
         .map_err(|e| e.to_string())
     }
 
-    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+    #[tracing::instrument(name = "Discount::restore", skip_all)]
+    pub async fn restore<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
         sqlx::query!(
             r#"
-				DELETE FROM discount WHERE id = $1;
+				UPDATE discount SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL;
 			"#,
             id
         )
-        .execute(pool)
+        .execute(executor)
         .await
+        .record_rows()
         .map(|r| r.rows_affected())
         .map_err(|e| e.to_string())
     }
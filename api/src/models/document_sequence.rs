@@ -0,0 +1,30 @@
+use chrono::{Datelike, Utc};
+use sqlx::{Postgres, Transaction};
+
+/// Hands out the next number of a gapless per-year sequence, formatted as
+/// `{kind}-{year}-{number}` (e.g. `CN-2026-000042`). The sequence row stays
+/// locked until the caller's transaction ends, so a rolled back document
+/// never leaves a hole.
+pub async fn next_number(
+    kind: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<String, sqlx::Error> {
+    let year = Utc::now().year();
+
+    let number = sqlx::query!(
+        r#"
+			INSERT INTO document_sequence (kind, year, last_number)
+			VALUES ($1, $2, 1)
+			ON CONFLICT (kind, year) DO UPDATE SET
+				last_number = document_sequence.last_number + 1
+			RETURNING last_number;
+		"#,
+        kind,
+        year
+    )
+    .fetch_one(tx)
+    .await?
+    .last_number;
+
+    Ok(format!("{}-{}-{:06}", kind, year, number))
+}
@@ -0,0 +1,166 @@
+use sqlx::PgPool;
+
+use crate::telemetry::RecordRows;
+
+/// A response kept to be replayed to retries of its request.
+pub struct StoredResponse {
+    pub status_code: i16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// What became of claiming an `Idempotency-Key`.
+pub enum Claim {
+    /// The key is new, the request is to be handled.
+    Claimed,
+    /// A request with the key is still being handled.
+    InProgress,
+    /// The key was used for a different request.
+    Mismatch,
+    Completed(StoredResponse),
+}
+
+/// The `Idempotency-Key` headers of a user, or of guests when `user_id` is
+/// `None`, and the responses to their requests. Guests share their keys,
+/// which are UUIDs.
+pub struct IdempotencyKey;
+
+impl IdempotencyKey {
+    /// Claims `key` for the request with `request_hash`, unless a request
+    /// already did and hasn't expired.
+    #[tracing::instrument(name = "IdempotencyKey::claim", skip_all)]
+    pub async fn claim(
+        user_id: Option<i64>,
+        key: &str,
+        request_hash: &str,
+        ttl_hours: i32,
+        pool: &PgPool,
+    ) -> Result<Claim, String> {
+        // Requests that are still unfinished after 5 minutes died with the
+        // server, their key is free again.
+        sqlx::query!(
+            r#"
+				DELETE FROM idempotency_key
+				WHERE COALESCE(user_id, 0) = COALESCE($1::bigint, 0) AND idempotency_key = $2
+					AND (expires_at < NOW()
+						OR (status_code IS NULL AND created_at < NOW() - interval '5 minutes'));
+			"#,
+            user_id,
+            key
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let claimed = sqlx::query_scalar!(
+            r#"
+				INSERT INTO idempotency_key (user_id, idempotency_key, request_hash, expires_at)
+				VALUES ($1, $2, $3, NOW() + make_interval(hours => $4))
+				ON CONFLICT ((COALESCE(user_id, 0)), idempotency_key) DO NOTHING
+				RETURNING id;
+			"#,
+            user_id,
+            key,
+            request_hash,
+            ttl_hours
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())?;
+        if claimed.is_some() {
+            return Ok(Claim::Claimed);
+        }
+
+        let existing = sqlx::query!(
+            r#"
+				SELECT request_hash, status_code, content_type, response_body
+				FROM idempotency_key
+				WHERE COALESCE(user_id, 0) = COALESCE($1::bigint, 0) AND idempotency_key = $2;
+			"#,
+            user_id,
+            key
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(match existing {
+            // Released by a failed request in the meantime.
+            None => Claim::InProgress,
+            Some(row) if row.request_hash != request_hash => Claim::Mismatch,
+            Some(row) => match row.status_code {
+                Some(status_code) => Claim::Completed(StoredResponse {
+                    status_code,
+                    content_type: row.content_type,
+                    body: row.response_body.unwrap_or_default(),
+                }),
+                None => Claim::InProgress,
+            },
+        })
+    }
+
+    /// Stores the response to the request that claimed `key`.
+    #[tracing::instrument(name = "IdempotencyKey::complete", skip_all)]
+    pub async fn complete(
+        user_id: Option<i64>,
+        key: &str,
+        response: &StoredResponse,
+        pool: &PgPool,
+    ) -> Result<(), String> {
+        sqlx::query!(
+            r#"
+				UPDATE idempotency_key
+				SET status_code = $3, content_type = $4, response_body = $5
+				WHERE COALESCE(user_id, 0) = COALESCE($1::bigint, 0) AND idempotency_key = $2;
+			"#,
+            user_id,
+            key,
+            response.status_code,
+            response.content_type,
+            response.body
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Frees `key` after its request failed, so that a retry is handled
+    /// again.
+    #[tracing::instrument(name = "IdempotencyKey::release", skip_all)]
+    pub async fn release(user_id: Option<i64>, key: &str, pool: &PgPool) -> Result<(), String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM idempotency_key
+				WHERE COALESCE(user_id, 0) = COALESCE($1::bigint, 0) AND idempotency_key = $2
+					AND status_code IS NULL;
+			"#,
+            user_id,
+            key
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Deletes the expired keys, returns how many there were.
+    #[tracing::instrument(name = "IdempotencyKey::purge_expired", skip_all)]
+    pub async fn purge_expired(pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM idempotency_key WHERE expires_at < NOW();
+			"#
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "inventory_movement_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum InventoryMovementKind {
+    ReturnRestock,
+    WriteOff,
+}
+
+impl InventoryMovementKind {
+    /// How much of the moved quantity ends up back on the shelf.
+    fn stock_delta(&self, quantity: i32) -> i32 {
+        match self {
+            InventoryMovementKind::ReturnRestock => quantity,
+            InventoryMovementKind::WriteOff => 0,
+        }
+    }
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct InventoryMovement {
+    pub id: i64,
+    pub inventory_id: i64,
+    pub kind: InventoryMovementKind,
+    pub quantity: i32,
+    pub return_line_id: Option<i64>,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl InventoryMovement {
+    #[tracing::instrument(name = "InventoryMovement::find_by_inventory", skip_all)]
+    pub async fn find_by_inventory(
+        inventory_id: i64,
+        pool: &PgPool,
+    ) -> Result<Vec<InventoryMovement>, String> {
+        sqlx::query_as!(
+            InventoryMovement,
+            r#"
+				SELECT id, inventory_id, kind as "kind: InventoryMovementKind", quantity,
+					return_line_id, note, created_at
+				FROM inventory_movement
+				WHERE inventory_id = $1
+				ORDER BY id ASC;
+			"#,
+            inventory_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Records a movement and applies it to `product_inventory`.
+    #[tracing::instrument(name = "InventoryMovement::record", skip_all)]
+    pub async fn record(
+        inventory_id: i64,
+        kind: InventoryMovementKind,
+        quantity: i32,
+        return_line_id: Option<i64>,
+        note: Option<String>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+				INSERT INTO inventory_movement (inventory_id, kind, quantity, return_line_id, note)
+				VALUES ($1, $2, $3, $4, $5);
+			"#,
+            inventory_id,
+            kind as _,
+            quantity,
+            return_line_id,
+            note
+        )
+        .execute(&mut *tx)
+        .await
+        .record_rows()?;
+
+        let delta = kind.stock_delta(quantity);
+        if delta != 0 {
+            sqlx::query!(
+                r#"
+					UPDATE product_inventory SET
+						quantity = quantity + $1,
+						updated_at = NOW()
+					WHERE id = $2;
+				"#,
+                delta,
+                inventory_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
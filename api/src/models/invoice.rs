@@ -0,0 +1,230 @@
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::{Decimal, Json};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::address::Address;
+use super::document_sequence;
+use super::shop_profile::ShopProfile;
+use super::tax::{TaxAmount, TaxClass};
+use crate::invoicing::{pdf, ubl};
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, ApiSchema)]
+pub struct Invoice {
+    pub id: i64,
+    pub number: String,
+    pub order_id: i64,
+    pub issued_at: NaiveDateTime,
+}
+
+/// Everything printed on an invoice, copied from the order and the shop
+/// profile when it's issued.
+#[derive(Serialize, Deserialize)]
+pub struct InvoiceData {
+    pub number: String,
+    pub order_id: i64,
+    pub issue_date: NaiveDate,
+    pub currency: String,
+    pub minor_units: i16,
+    pub seller: ShopProfile,
+    pub buyer: Option<Address>,
+    pub buyer_email: Option<String>,
+    pub vat_number: Option<String>,
+    pub reverse_charge: bool,
+    pub lines: Vec<InvoiceLine>,
+    pub tax_breakdown: Vec<TaxAmount>,
+    pub net_total: Decimal,
+    pub tax_total: Decimal,
+    pub total: Decimal,
+}
+
+/// A product or the shipping charge, amounts are net of tax.
+#[derive(Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub name: String,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+    pub tax_class: TaxClass,
+    pub tax_rate: Decimal,
+    pub net_amount: Decimal,
+    pub tax_amount: Decimal,
+}
+
+/// The documents rendered when the invoice was issued.
+pub struct InvoiceDocument {
+    pub number: String,
+    pub pdf: Vec<u8>,
+    pub xml: String,
+}
+
+impl Invoice {
+    #[tracing::instrument(name = "Invoice::find_by_order", skip_all)]
+    pub async fn find_by_order(order_id: i64, pool: &PgPool) -> Result<Option<Invoice>, String> {
+        sqlx::query_as!(
+            Invoice,
+            r#"
+				SELECT id, number, order_id, issued_at FROM invoice
+				WHERE order_id = $1;
+			"#,
+            order_id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Invoice::find_document", skip_all)]
+    pub async fn find_document(
+        order_id: i64,
+        pool: &PgPool,
+    ) -> Result<Option<InvoiceDocument>, String> {
+        sqlx::query_as!(
+            InvoiceDocument,
+            r#"
+				SELECT number, pdf, xml FROM invoice
+				WHERE order_id = $1;
+			"#,
+            order_id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Numbers the invoice of a paid order and renders its PDF and e-invoice.
+    /// Runs in the transaction that marks the order as paid, so every paid
+    /// order has exactly one invoice and the numbering has no gaps.
+    #[tracing::instrument(name = "Invoice::issue_in", skip_all)]
+    pub async fn issue_in(
+        order_id: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Invoice, String> {
+        let data = invoice_data(order_id, tx).await?;
+        let pdf = pdf::render(&data);
+        let xml = ubl::render(&data);
+
+        sqlx::query_as!(
+            Invoice,
+            r#"
+				INSERT INTO invoice (number, order_id, data, pdf, xml)
+				VALUES ($1, $2, $3, $4, $5)
+				RETURNING id, number, order_id, issued_at;
+			"#,
+            data.number,
+            order_id,
+            Json(&data) as _,
+            pdf,
+            xml
+        )
+        .fetch_one(tx)
+        .await
+        .map_err(|e| e.to_string())
+    }
+}
+
+async fn invoice_data(
+    order_id: i64,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<InvoiceData, String> {
+    let order = sqlx::query!(
+        r#"
+			SELECT o.currency, c.minor_units, o.vat_number, o.reverse_charge, o.shipping_cost, o.net_total, o.tax_total, o.total,
+				o.tax_breakdown as "tax_breakdown: Json<Vec<TaxAmount>>",
+				COALESCE(o.billing_address, o.shipping_address) as "buyer: Json<Address>",
+				u.email as "buyer_email?", m.name as "shipping_method?"
+			FROM customer_order o
+			JOIN currency c ON c.code = o.currency
+			LEFT JOIN app_user u ON u.id = o.user_id
+			LEFT JOIN shipping_method m ON m.id = o.shipping_method_id
+			WHERE o.id = $1;
+		"#,
+        order_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let items = sqlx::query!(
+        r#"
+			SELECT name, sku, quantity, tax_class, tax_rate, net_amount, tax_amount
+			FROM order_item
+			WHERE order_id = $1
+			ORDER BY id ASC;
+		"#,
+        order_id
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut lines: Vec<InvoiceLine> = items
+        .into_iter()
+        .map(|i| InvoiceLine {
+            unit_price: (i.net_amount / Decimal::from(i.quantity)).round_dp(4),
+            name: i.name,
+            sku: i.sku,
+            quantity: i.quantity,
+            tax_class: TaxClass::parse(&i.tax_class),
+            tax_rate: i.tax_rate,
+            net_amount: i.net_amount,
+            tax_amount: i.tax_amount,
+        })
+        .collect();
+
+    // Shipping isn't stored as an order item, whatever the items don't
+    // account for is the shipping charge, taxed at the standard rate.
+    if order.shipping_cost > Decimal::ZERO {
+        let net_amount = order.net_total - lines.iter().map(|l| l.net_amount).sum::<Decimal>();
+        let tax_amount = order.tax_total - lines.iter().map(|l| l.tax_amount).sum::<Decimal>();
+        let tax_rate = order
+            .tax_breakdown
+            .iter()
+            .find(|b| b.tax_class == TaxClass::Standard)
+            .map(|b| b.rate)
+            .unwrap_or(Decimal::ZERO);
+
+        lines.push(InvoiceLine {
+            name: format!(
+                "Shipping: {}",
+                order
+                    .shipping_method
+                    .unwrap_or_else(|| "delivery".to_string())
+            ),
+            sku: None,
+            quantity: 1,
+            unit_price: net_amount,
+            tax_class: TaxClass::Standard,
+            tax_rate,
+            net_amount,
+            tax_amount,
+        });
+    }
+
+    let seller = ShopProfile::find(&mut *tx).await?;
+    let number = document_sequence::next_number("INV", tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(InvoiceData {
+        number,
+        order_id,
+        issue_date: Utc::now().naive_utc().date(),
+        currency: order.currency,
+        minor_units: order.minor_units,
+        seller,
+        buyer: order.buyer.map(|b| b.0),
+        buyer_email: order.buyer_email,
+        vat_number: order.vat_number,
+        reverse_charge: order.reverse_charge,
+        lines,
+        tax_breakdown: order.tax_breakdown.0,
+        net_total: order.net_total,
+        tax_total: order.tax_total,
+        total: order.total,
+    })
+}
@@ -1,23 +1,61 @@
+use serde::{Deserialize, Deserializer};
+use sqlx::types::Decimal;
+use validator::ValidationError;
+
+pub mod address;
+pub mod audit;
+pub mod authentication;
 pub mod category;
+pub mod credit_note;
+pub mod currency;
+pub mod customer_group;
 pub mod discount;
+pub mod document_sequence;
+pub mod idempotency;
+pub mod inventory_movement;
+pub mod invoice;
+pub mod money;
+pub mod order;
+pub mod order_return;
+pub mod payment;
+pub mod price_history;
+pub mod price_tier;
 pub mod product;
 pub mod product_inventory;
-// pub mod authentication;
+pub mod shipping;
+pub mod shop_profile;
+pub mod storefront;
+pub mod tax;
+pub mod trash;
+pub mod user;
 
 // const MIN_I64_CONST: i64 = 0;
 // const MAX_I64_CONST: i64 = i64::MAX;
 const MIN_I32_CONST: i32 = 0;
 const MAX_I32_CONST: i32 = i32::MAX;
+const MIN_ORDER_QUANTITY: i32 = 1;
 // const TOO_MANY_CHARACTERS_128: &str = "field contains too many characters - max: 128";
 // const TOO_MANY_CHARACTERS_500: &str = "field contains too many characters - max: 500";
 // const I64_ERROR: &str = "field contains invalid value - min: 0, max: i64";
 // const I32_ERROR: &str = "field contains invalid value - min: 0, max: 2147483647";
 // const REQUIRED: &str = "this field is required";
 
-// fn validate_decimal(value: &Decimal) -> Result<(), ValidationError> {
-//     if *value < Decimal::ZERO {
-//         return Err(ValidationError::new("range"));
-//     }
+fn validate_decimal(value: &Decimal) -> Result<(), ValidationError> {
+    if *value < Decimal::ZERO {
+        let mut error = ValidationError::new("range");
+        error.message = Some("field contains invalid value - min: 0".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
 
-//     Ok(())
-// }
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field
+/// (`None`), for fields that are also `#[serde(default)]`.
+fn deserialize_nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,115 @@
+use rust_decimal::RoundingStrategy;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::types::Decimal;
+
+use super::currency::Currency;
+use crate::openapi::ApiSchema;
+
+/// An amount in a currency. Serialized as `{"amount": "12.50", "currency":
+/// "EUR"}`, the amount always as a string with the currency's minor units so
+/// clients never parse it into a float.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ApiSchema)]
+pub struct Money {
+    #[serde(
+        serialize_with = "serialize_amount",
+        deserialize_with = "deserialize_amount"
+    )]
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl Money {
+    /// Rounds `amount` to `minor_units` (halves away from zero) and pads it
+    /// to exactly that many decimal places.
+    pub fn new(amount: Decimal, currency: &str, minor_units: i16) -> Money {
+        let mut amount = amount
+            .round_dp_with_strategy(minor_units as u32, RoundingStrategy::MidpointAwayFromZero);
+        amount.rescale(minor_units as u32);
+
+        Money {
+            amount,
+            currency: currency.to_string(),
+        }
+    }
+}
+
+impl Currency {
+    pub fn money(&self, amount: Decimal) -> Money {
+        Money::new(amount, &self.code, self.minor_units)
+    }
+}
+
+fn serialize_amount<S: Serializer>(amount: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&amount.to_string())
+}
+
+fn deserialize_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    let amount = String::deserialize(deserializer)?;
+
+    amount.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Money;
+    use serde_json::json;
+    use sqlx::types::Decimal;
+
+    #[test]
+    fn pads_to_the_minor_units() {
+        assert_eq!(
+            Money::new(Decimal::from(12), "EUR", 2).amount.to_string(),
+            "12.00"
+        );
+        assert_eq!(
+            Money::new(Decimal::new(125, 1), "EUR", 2)
+                .amount
+                .to_string(),
+            "12.50"
+        );
+    }
+
+    #[test]
+    fn rounds_halves_away_from_zero() {
+        assert_eq!(
+            Money::new(Decimal::new(12345, 3), "EUR", 2)
+                .amount
+                .to_string(),
+            "12.35"
+        );
+        assert_eq!(
+            Money::new(Decimal::new(-12345, 3), "EUR", 2)
+                .amount
+                .to_string(),
+            "-12.35"
+        );
+        assert_eq!(
+            Money::new(Decimal::new(12344, 3), "EUR", 2)
+                .amount
+                .to_string(),
+            "12.34"
+        );
+        assert_eq!(
+            Money::new(Decimal::new(15, 1), "JPY", 0).amount.to_string(),
+            "2"
+        );
+    }
+
+    #[test]
+    fn serializes_amounts_as_strings() {
+        let money = Money::new(Decimal::new(1999, 2), "USD", 2);
+
+        assert_eq!(
+            serde_json::to_value(&money).unwrap(),
+            json!({ "amount": "19.99", "currency": "USD" })
+        );
+        assert_eq!(
+            serde_json::from_value::<Money>(json!({ "amount": "19.99", "currency": "USD" }))
+                .unwrap(),
+            money
+        );
+        assert!(
+            serde_json::from_value::<Money>(json!({ "amount": 19.99, "currency": "USD" })).is_err()
+        );
+    }
+}
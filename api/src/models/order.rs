@@ -0,0 +1,1015 @@
+use axum::extract::{FromRequest, Path, RequestParts};
+use axum::http::StatusCode;
+use chrono::NaiveDateTime;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::{Decimal, Json};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::metrics;
+use crate::models::address::{Address, AddressKind};
+use crate::models::authentication::OptionalJwtToken;
+use crate::models::currency::Currency;
+use crate::models::customer_group::CustomerGroup;
+use crate::models::invoice::Invoice;
+use crate::models::money::Money;
+use crate::models::price_tier::PriceTier;
+use crate::models::shipping::{normalize_postcode, ShippingMethod};
+use crate::models::tax::{
+    summarize, validate_vat_number, TaxAmount, TaxClass, TaxContext, TaxSettings,
+};
+use crate::models::{MAX_I32_CONST, MIN_ORDER_QUANTITY};
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "order_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Picking,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Refunded,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Picking => "picking",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+        }
+    }
+
+    /// Returns true if the order lifecycle allows moving from `self` to `next`.
+    ///
+    /// pending -> paid -> picking -> shipped -> delivered, an order can be
+    /// cancelled until it leaves the warehouse and refunded once it was paid.
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+
+        matches!(
+            (self, next),
+            (Pending, Paid)
+                | (Paid, Picking)
+                | (Picking, Shipped)
+                | (Shipped, Delivered)
+                | (Pending | Paid | Picking, Cancelled)
+                | (Paid | Picking | Shipped | Delivered, Refunded)
+        )
+    }
+
+    /// Returns true if the reserved stock goes back into `product_inventory`
+    /// when moving from `self` to `next`. Goods that already left the
+    /// warehouse are only restocked through a return.
+    pub fn releases_stock(&self, next: OrderStatus) -> bool {
+        matches!(
+            (self, next),
+            (_, OrderStatus::Cancelled)
+                | (
+                    OrderStatus::Paid | OrderStatus::Picking,
+                    OrderStatus::Refunded
+                )
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum OrderError {
+    NotFound(i64),
+    ProductNotFound(i64),
+    ProductNotPriced(i64),
+    OutOfStock(i64),
+    ShippingUnavailable(i64),
+    NoTaxRate {
+        country: String,
+        tax_class: TaxClass,
+    },
+    AddressNotFound(i64),
+    IllegalTransition {
+        from: OrderStatus,
+        to: OrderStatus,
+    },
+    Database(String),
+}
+
+impl From<sqlx::Error> for OrderError {
+    fn from(e: sqlx::Error) -> Self {
+        OrderError::Database(e.to_string())
+    }
+}
+
+impl From<String> for OrderError {
+    fn from(e: String) -> Self {
+        OrderError::Database(e)
+    }
+}
+
+impl std::fmt::Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::NotFound(id) => write!(f, "order {} not found", id),
+            OrderError::ProductNotFound(id) => write!(f, "product {} not found", id),
+            OrderError::ProductNotPriced(id) => write!(f, "product {} has no price", id),
+            OrderError::OutOfStock(id) => write!(f, "product {} is out of stock", id),
+            OrderError::ShippingUnavailable(id) => {
+                write!(f, "shipping method {} is not available for this order", id)
+            }
+            OrderError::NoTaxRate { country, tax_class } => write!(
+                f,
+                "no {} tax rate is configured for {}",
+                tax_class.as_str(),
+                country
+            ),
+            OrderError::AddressNotFound(id) => write!(f, "address {} not found", id),
+            OrderError::IllegalTransition { from, to } => write!(
+                f,
+                "illegal order transition: {} -> {}",
+                from.as_str(),
+                to.as_str()
+            ),
+            OrderError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ApiSchema)]
+pub struct OrderItem {
+    pub id: i64,
+    pub order_id: i64,
+    pub product_id: Option<i64>,
+    pub inventory_id: Option<i64>,
+    pub name: String,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub tax_class: String,
+    pub tax_rate: Decimal,
+    pub net_amount: Money,
+    pub tax_amount: Money,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct Order {
+    pub id: i64,
+    pub status: OrderStatus,
+    /// All amounts of the order and its items are in this currency.
+    pub currency: String,
+    pub total: Json<Money>,
+    pub shipping_method_id: Option<i64>,
+    pub shipping_cost: Json<Money>,
+    pub shipping_country: Option<String>,
+    pub shipping_postcode: Option<String>,
+    pub tax_country: Option<String>,
+    pub tax_region: Option<String>,
+    pub vat_number: Option<String>,
+    pub reverse_charge: bool,
+    pub prices_include_tax: bool,
+    pub net_total: Json<Money>,
+    pub tax_total: Json<Money>,
+    pub tax_breakdown: Json<Vec<TaxAmount>>,
+    pub user_id: Option<i64>,
+    pub shipping_address: Option<Json<Address>>,
+    pub billing_address: Option<Json<Address>>,
+    pub items: Json<Vec<OrderItem>>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Header guests send the access token of their order in.
+pub const ORDER_TOKEN_HEADER: &str = "x-order-token";
+
+/// An order as returned from checkout.
+#[derive(Serialize, ApiSchema)]
+pub struct PlacedOrder {
+    #[serde(flatten)]
+    pub order: Order,
+    /// Only for guest orders, pays for or returns the order when sent in the
+    /// `X-Order-Token` header. It isn't shown again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct OrderStatusChange {
+    pub id: i64,
+    pub order_id: i64,
+    pub from_status: Option<OrderStatus>,
+    pub to_status: OrderStatus,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct OrderInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(min = 1, message = "order must contain at least one item")
+    )]
+    #[validate]
+    items: Option<Vec<OrderItemInsert>>,
+
+    #[validate]
+    shipping: Option<OrderShipping>,
+
+    #[validate]
+    tax: Option<OrderTaxInfo>,
+
+    /// Address book entries of the logged in customer, their defaults are
+    /// used when left out.
+    shipping_address_id: Option<i64>,
+    billing_address_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Validate, ApiSchema)]
+pub struct OrderItemInsert {
+    pub product_id: i64,
+
+    #[validate(range(
+        min = "MIN_ORDER_QUANTITY",
+        max = "MAX_I32_CONST",
+        message = "field contains invalid value - min: 1, max: i32"
+    ))]
+    pub quantity: i32,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct OrderShipping {
+    method_id: i64,
+
+    #[validate(length(
+        equal = 2,
+        message = "field must be an ISO 3166-1 alpha-2 country code"
+    ))]
+    country: String,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    postcode: Option<String>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct OrderTaxInfo {
+    /// Defaults to the shipping country.
+    #[validate(length(
+        equal = 2,
+        message = "field must be an ISO 3166-1 alpha-2 country code"
+    ))]
+    country: Option<String>,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    region: Option<String>,
+
+    /// Set for B2B customers, intra-EU sales to them are reverse charged.
+    #[validate(custom = "validate_vat_number")]
+    vat_number: Option<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct OrderQuoteItem {
+    pub product_id: i64,
+    #[serde(skip_serializing)]
+    pub inventory_id: Option<i64>,
+    pub name: String,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub tax: TaxAmount,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct OrderQuote {
+    pub currency: String,
+    pub items: Vec<OrderQuoteItem>,
+    pub shipping_method_id: Option<i64>,
+    pub shipping_cost: Money,
+    pub shipping_tax: Option<TaxAmount>,
+    pub tax_country: String,
+    pub tax_region: Option<String>,
+    pub prices_include_tax: bool,
+    pub reverse_charge: bool,
+    pub tax_breakdown: Vec<TaxAmount>,
+    pub net_total: Money,
+    pub tax_total: Money,
+    pub total: Money,
+}
+
+#[derive(Deserialize, Validate, Default, ApiSchema)]
+pub struct OrderTransition {
+    #[validate(length(max = 500, message = "field contains too many characters - max: 500"))]
+    pub note: Option<String>,
+}
+
+impl Order {
+    #[tracing::instrument(name = "Order::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<Order>, String> {
+        sqlx::query_as!(
+            Order,
+            r#"
+				SELECT o.id, o.status as "status: OrderStatus", o.currency,
+					money_json(o.total, o.currency) as "total!: Json<Money>",
+					o.shipping_method_id, money_json(o.shipping_cost, o.currency) as "shipping_cost!: Json<Money>",
+					o.shipping_country, o.shipping_postcode,
+					o.tax_country, o.tax_region, o.vat_number, o.reverse_charge, o.prices_include_tax,
+					money_json(o.net_total, o.currency) as "net_total!: Json<Money>",
+					money_json(o.tax_total, o.currency) as "tax_total!: Json<Money>",
+					o.tax_breakdown as "tax_breakdown: Json<Vec<TaxAmount>>",
+					o.user_id, o.shipping_address as "shipping_address: Json<Address>",
+					o.billing_address as "billing_address: Json<Address>",
+					o.created_at, o.updated_at,
+					COALESCE(
+						jsonb_agg(
+							to_jsonb(oi.*) || jsonb_build_object(
+								'unit_price', money_json(oi.unit_price, o.currency),
+								'net_amount', money_json(oi.net_amount, o.currency),
+								'tax_amount', money_json(oi.tax_amount, o.currency)
+							)
+							ORDER BY oi.id
+						) FILTER (WHERE oi.id IS NOT NULL),
+						'[]'
+					) as "items!: Json<Vec<OrderItem>>"
+				FROM customer_order o
+				LEFT JOIN order_item oi ON oi.order_id = o.id
+				GROUP BY o.id
+				ORDER BY o.id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Order::find_by_user", skip_all)]
+    pub async fn find_by_user(user_id: i64, pool: &PgPool) -> Result<Vec<Order>, String> {
+        sqlx::query_as!(
+            Order,
+            r#"
+				SELECT o.id, o.status as "status: OrderStatus", o.currency,
+					money_json(o.total, o.currency) as "total!: Json<Money>",
+					o.shipping_method_id, money_json(o.shipping_cost, o.currency) as "shipping_cost!: Json<Money>",
+					o.shipping_country, o.shipping_postcode,
+					o.tax_country, o.tax_region, o.vat_number, o.reverse_charge, o.prices_include_tax,
+					money_json(o.net_total, o.currency) as "net_total!: Json<Money>",
+					money_json(o.tax_total, o.currency) as "tax_total!: Json<Money>",
+					o.tax_breakdown as "tax_breakdown: Json<Vec<TaxAmount>>",
+					o.user_id, o.shipping_address as "shipping_address: Json<Address>",
+					o.billing_address as "billing_address: Json<Address>",
+					o.created_at, o.updated_at,
+					COALESCE(
+						jsonb_agg(
+							to_jsonb(oi.*) || jsonb_build_object(
+								'unit_price', money_json(oi.unit_price, o.currency),
+								'net_amount', money_json(oi.net_amount, o.currency),
+								'tax_amount', money_json(oi.tax_amount, o.currency)
+							)
+							ORDER BY oi.id
+						) FILTER (WHERE oi.id IS NOT NULL),
+						'[]'
+					) as "items!: Json<Vec<OrderItem>>"
+				FROM customer_order o
+				LEFT JOIN order_item oi ON oi.order_id = o.id
+				WHERE o.user_id = $1
+				GROUP BY o.id
+				ORDER BY o.id DESC;
+			"#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Order::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Option<Order>, String> {
+        sqlx::query_as!(
+            Order,
+            r#"
+				SELECT o.id, o.status as "status: OrderStatus", o.currency,
+					money_json(o.total, o.currency) as "total!: Json<Money>",
+					o.shipping_method_id, money_json(o.shipping_cost, o.currency) as "shipping_cost!: Json<Money>",
+					o.shipping_country, o.shipping_postcode,
+					o.tax_country, o.tax_region, o.vat_number, o.reverse_charge, o.prices_include_tax,
+					money_json(o.net_total, o.currency) as "net_total!: Json<Money>",
+					money_json(o.tax_total, o.currency) as "tax_total!: Json<Money>",
+					o.tax_breakdown as "tax_breakdown: Json<Vec<TaxAmount>>",
+					o.user_id, o.shipping_address as "shipping_address: Json<Address>",
+					o.billing_address as "billing_address: Json<Address>",
+					o.created_at, o.updated_at,
+					COALESCE(
+						jsonb_agg(
+							to_jsonb(oi.*) || jsonb_build_object(
+								'unit_price', money_json(oi.unit_price, o.currency),
+								'net_amount', money_json(oi.net_amount, o.currency),
+								'tax_amount', money_json(oi.tax_amount, o.currency)
+							)
+							ORDER BY oi.id
+						) FILTER (WHERE oi.id IS NOT NULL),
+						'[]'
+					) as "items!: Json<Vec<OrderItem>>"
+				FROM customer_order o
+				LEFT JOIN order_item oi ON oi.order_id = o.id
+				WHERE o.id = $1
+				GROUP BY o.id;
+			"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Order::history", skip_all)]
+    pub async fn history(id: i64, pool: &PgPool) -> Result<Vec<OrderStatusChange>, OrderError> {
+        let exists = sqlx::query_scalar!(
+            r#"
+				SELECT EXISTS(SELECT 1 FROM customer_order WHERE id = $1) as "exists!";
+			"#,
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        if !exists {
+            return Err(OrderError::NotFound(id));
+        }
+
+        let history = sqlx::query_as!(
+            OrderStatusChange,
+            r#"
+				SELECT id, order_id,
+					from_status as "from_status: OrderStatus",
+					to_status as "to_status: OrderStatus",
+					note, created_at
+				FROM order_status_history
+				WHERE order_id = $1
+				ORDER BY id ASC;
+			"#,
+            id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()?;
+
+        Ok(history)
+    }
+
+    /// Prices a cart the same way [`Order::create`] would, without placing
+    /// the order or reserving stock.
+    #[tracing::instrument(name = "Order::quote", skip_all)]
+    pub async fn quote(
+        input: &OrderInsert,
+        user_id: Option<i64>,
+        currency: &Currency,
+        settings: &TaxSettings,
+        pool: &PgPool,
+    ) -> Result<OrderQuote, OrderError> {
+        let mut conn = pool.acquire().await?;
+
+        OrderQuote::build(input, user_id, currency, settings, &mut conn).await
+    }
+
+    /// Creates a pending order and reserves stock for every item. Nothing is
+    /// written if any of the products can't be ordered. Orders placed by a
+    /// logged in customer keep a copy of their addresses.
+    #[tracing::instrument(name = "Order::create", skip_all)]
+    pub async fn create(
+        input: OrderInsert,
+        user_id: Option<i64>,
+        currency: &Currency,
+        settings: &TaxSettings,
+        pool: &PgPool,
+    ) -> Result<PlacedOrder, OrderError> {
+        let mut tx = pool.begin().await?;
+        let quote = OrderQuote::build(&input, user_id, currency, settings, &mut tx).await?;
+        let access_token = match user_id {
+            Some(_) => None,
+            None => Some(access_token()),
+        };
+
+        let shipping_address = order_address(
+            user_id,
+            input.shipping_address_id,
+            AddressKind::Shipping,
+            &mut tx,
+        )
+        .await?;
+        let billing_address = order_address(
+            user_id,
+            input.billing_address_id,
+            AddressKind::Billing,
+            &mut tx,
+        )
+        .await?;
+
+        let order_id = sqlx::query!(
+            r#"
+				INSERT INTO customer_order (
+					shipping_method_id, shipping_cost, shipping_country, shipping_postcode,
+					tax_country, tax_region, vat_number, reverse_charge, prices_include_tax,
+					net_total, tax_total, total, tax_breakdown, user_id, shipping_address, billing_address,
+					currency, exchange_rate, access_token_hash
+				)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+					$19)
+				RETURNING id;
+			"#,
+            quote.shipping_method_id,
+            quote.shipping_cost.amount,
+            input.shipping.as_ref().map(|s| s.country.to_uppercase()),
+            input
+                .shipping
+                .as_ref()
+                .and_then(|s| s.postcode.as_deref())
+                .map(normalize_postcode),
+            quote.tax_country,
+            quote.tax_region,
+            input.tax.as_ref().and_then(|t| t.vat_number.clone()),
+            quote.reverse_charge,
+            quote.prices_include_tax,
+            quote.net_total.amount,
+            quote.tax_total.amount,
+            quote.total.amount,
+            Json(&quote.tax_breakdown) as _,
+            user_id,
+            shipping_address.map(Json) as _,
+            billing_address.map(Json) as _,
+            currency.code,
+            currency.exchange_rate,
+            access_token.as_deref().map(hash_access_token)
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .id;
+
+        record_transition(order_id, None, OrderStatus::Pending, None, &mut tx).await?;
+
+        for item in quote.items.iter() {
+            let reserved = sqlx::query!(
+                r#"
+					UPDATE product_inventory SET
+						quantity = quantity - $1,
+						updated_at = NOW()
+					WHERE id = $2 AND quantity >= $1
+					RETURNING id;
+				"#,
+                item.quantity,
+                item.inventory_id
+            )
+            .fetch_optional(&mut tx)
+            .await?;
+
+            if reserved.is_none() {
+                metrics::STOCK_OUTS.inc();
+                return Err(OrderError::OutOfStock(item.product_id));
+            }
+
+            sqlx::query!(
+                r#"
+					INSERT INTO order_item (
+						order_id, product_id, inventory_id, name, sku, quantity, unit_price,
+						tax_class, tax_rate, net_amount, tax_amount
+					)
+					VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11);
+				"#,
+                order_id,
+                item.product_id,
+                item.inventory_id,
+                item.name,
+                item.sku,
+                item.quantity,
+                item.unit_price.amount,
+                item.tax.tax_class.as_str(),
+                item.tax.rate,
+                item.tax.net,
+                item.tax.tax
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        metrics::ORDERS_CREATED.inc();
+
+        let order = Order::find_by_id(order_id, pool)
+            .await?
+            .ok_or(OrderError::NotFound(order_id))?;
+
+        Ok(PlacedOrder {
+            order,
+            access_token,
+        })
+    }
+
+    /// Whether the order was placed by `user_id` or, for guest orders, is the
+    /// one `access_token` was handed out for.
+    #[tracing::instrument(name = "Order::is_accessible", skip_all)]
+    pub async fn is_accessible(
+        id: i64,
+        user_id: Option<i64>,
+        access_token: Option<&str>,
+        pool: &PgPool,
+    ) -> Result<bool, String> {
+        sqlx::query_scalar!(
+            r#"
+				SELECT EXISTS (
+					SELECT 1 FROM customer_order
+					WHERE id = $1 AND (user_id = $2 OR (user_id IS NULL AND access_token_hash = $3))
+				) as "exists!";
+			"#,
+            id,
+            user_id,
+            access_token.map(hash_access_token)
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Moves an order to `to`, recording the change in the status history.
+    /// Rejects transitions the lifecycle doesn't allow and releases reserved
+    /// stock when the order is cancelled.
+    #[tracing::instrument(name = "Order::transition", skip_all)]
+    pub async fn transition(
+        id: i64,
+        to: OrderStatus,
+        input: OrderTransition,
+        pool: &PgPool,
+    ) -> Result<Order, OrderError> {
+        let mut tx = pool.begin().await?;
+
+        Order::transition_in(id, to, input.note, &mut tx).await?;
+
+        tx.commit().await?;
+
+        Order::find_by_id(id, pool)
+            .await?
+            .ok_or(OrderError::NotFound(id))
+    }
+
+    /// Same as [`Order::transition`] but runs inside the caller's transaction,
+    /// returning the status the order was in before the change.
+    #[tracing::instrument(name = "Order::transition_in", skip_all)]
+    pub async fn transition_in(
+        id: i64,
+        to: OrderStatus,
+        note: Option<String>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<OrderStatus, OrderError> {
+        let from = sqlx::query!(
+            r#"
+				SELECT status as "status: OrderStatus" FROM customer_order
+				WHERE id = $1
+				FOR UPDATE;
+			"#,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(OrderError::NotFound(id))?
+        .status;
+
+        if !from.can_transition_to(to) {
+            return Err(OrderError::IllegalTransition { from, to });
+        }
+
+        sqlx::query!(
+            r#"
+				UPDATE customer_order SET
+					status = $1,
+					updated_at = NOW()
+				WHERE id = $2;
+			"#,
+            to as _,
+            id
+        )
+        .execute(&mut *tx)
+        .await
+        .record_rows()?;
+
+        record_transition(id, Some(from), to, note, tx).await?;
+
+        if to == OrderStatus::Paid {
+            Invoice::issue_in(id, tx).await?;
+        }
+
+        if from.releases_stock(to) {
+            sqlx::query!(
+                r#"
+					UPDATE product_inventory i SET
+						quantity = i.quantity + oi.quantity,
+						updated_at = NOW()
+					FROM order_item oi
+					WHERE oi.order_id = $1 AND i.id = oi.inventory_id;
+				"#,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(from)
+    }
+}
+
+impl OrderQuote {
+    async fn build(
+        input: &OrderInsert,
+        user_id: Option<i64>,
+        currency: &Currency,
+        settings: &TaxSettings,
+        conn: &mut PgConnection,
+    ) -> Result<OrderQuote, OrderError> {
+        let shipping = input.shipping.as_ref();
+        let tax_info = input.tax.as_ref();
+
+        // Tax follows the customer's country when given, otherwise the
+        // destination of the goods.
+        let tax_country = tax_info
+            .and_then(|t| t.country.as_deref())
+            .or_else(|| shipping.map(|s| s.country.as_str()))
+            .unwrap_or(&settings.shop_country);
+        let tax = TaxContext::resolve(
+            tax_country,
+            tax_info.and_then(|t| t.region.as_deref()),
+            tax_info.and_then(|t| t.vat_number.as_deref()),
+            settings,
+            currency,
+            &mut *conn,
+        )
+        .await?;
+
+        let customer_group_id = match user_id {
+            Some(user_id) => CustomerGroup::id_of_user(user_id, &mut *conn).await?,
+            None => None,
+        };
+
+        let mut items = Vec::new();
+        // Shipping is configured in the base currency, free shipping
+        // thresholds are checked against the base price of the cart.
+        let mut base_subtotal = Decimal::ZERO;
+        let mut weight = Decimal::ZERO;
+
+        for item in input.items.iter().flatten() {
+            let product = sqlx::query!(
+                r#"
+					SELECT p.id, p.name, p.sku, p.inventory_id, p.weight, p.tax_class,
+						ROUND(
+							p.price * (1 - COALESCE(CASE WHEN d.active THEN d.discount_percent END, 0) / 100),
+							2
+						) as base_price,
+						ROUND(
+							COALESCE(pp.price, p.price * $2)
+								* (1 - COALESCE(CASE WHEN d.active THEN d.discount_percent END, 0) / 100),
+							$3
+						) as unit_price
+					FROM product p
+					LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+					LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = $4
+					WHERE p.id = $1 AND p.deleted_at IS NULL AND p.status = 'published';
+				"#,
+                item.product_id,
+                currency.exchange_rate,
+                currency.minor_units as i32,
+                currency.code
+            )
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or(OrderError::ProductNotFound(item.product_id))?;
+
+            // Customers pay the lower of the (discounted) list price and the
+            // best quantity or group tier they qualify for.
+            let tier_price = PriceTier::best_price(
+                product.id,
+                item.quantity,
+                customer_group_id,
+                currency,
+                &mut *conn,
+            )
+            .await?;
+            let unit_price = match (product.unit_price, tier_price) {
+                (Some(list), Some(tier)) => Some(list.min(tier)),
+                (list, tier) => list.or(tier),
+            }
+            .ok_or(OrderError::ProductNotPriced(product.id))?;
+            let amount = unit_price * Decimal::from(item.quantity);
+
+            base_subtotal +=
+                product.base_price.unwrap_or(Decimal::ZERO) * Decimal::from(item.quantity);
+            weight += product.weight.unwrap_or(Decimal::ZERO) * Decimal::from(item.quantity);
+
+            items.push(OrderQuoteItem {
+                product_id: product.id,
+                inventory_id: product.inventory_id,
+                name: product.name,
+                sku: product.sku,
+                quantity: item.quantity,
+                unit_price: currency.money(unit_price),
+                tax: tax.split(TaxClass::parse(&product.tax_class), amount)?,
+            });
+        }
+
+        let (shipping_method_id, shipping_cost) = match shipping {
+            Some(shipping) => {
+                let postcode = shipping.postcode.as_deref().map(normalize_postcode);
+                let cost = ShippingMethod::find_available(
+                    &shipping.country,
+                    postcode.as_deref(),
+                    &mut *conn,
+                )
+                .await?
+                .into_iter()
+                .find(|m| m.id == shipping.method_id)
+                .and_then(|m| m.cost(base_subtotal, weight))
+                .ok_or(OrderError::ShippingUnavailable(shipping.method_id))?;
+
+                (Some(shipping.method_id), currency.convert(cost))
+            }
+            None => (None, Decimal::ZERO),
+        };
+
+        // Shipping is taxed at the standard rate.
+        let shipping_tax = shipping_method_id
+            .map(|_| tax.split(TaxClass::Standard, shipping_cost))
+            .transpose()?;
+
+        let tax_breakdown = summarize(items.iter().map(|i| &i.tax).chain(shipping_tax.iter()));
+        let net_total: Decimal = tax_breakdown.iter().map(|b| b.net).sum();
+        let tax_total: Decimal = tax_breakdown.iter().map(|b| b.tax).sum();
+
+        Ok(OrderQuote {
+            currency: currency.code.clone(),
+            items,
+            shipping_method_id,
+            shipping_cost: currency.money(shipping_cost),
+            shipping_tax,
+            tax_country: tax.country.clone(),
+            tax_region: tax.region.clone(),
+            prices_include_tax: tax.prices_include_tax,
+            reverse_charge: tax.reverse_charge,
+            tax_breakdown,
+            net_total: currency.money(net_total),
+            tax_total: currency.money(tax_total),
+            total: currency.money(net_total + tax_total),
+        })
+    }
+}
+
+/// Picks the address an order is shipped or billed to: the one asked for, or
+/// the customer's default. Guests can't refer to address book entries.
+async fn order_address(
+    user_id: Option<i64>,
+    address_id: Option<i64>,
+    kind: AddressKind,
+    conn: &mut PgConnection,
+) -> Result<Option<Address>, OrderError> {
+    match (user_id, address_id) {
+        (Some(user_id), Some(id)) => Address::find_by_id(id, user_id, conn)
+            .await?
+            .map(Some)
+            .ok_or(OrderError::AddressNotFound(id)),
+        (Some(user_id), None) => Ok(Address::find_default(user_id, kind, conn).await?),
+        (None, Some(id)) => Err(OrderError::AddressNotFound(id)),
+        (None, None) => Ok(None),
+    }
+}
+
+async fn record_transition(
+    order_id: i64,
+    from: Option<OrderStatus>,
+    to: OrderStatus,
+    note: Option<String>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+			INSERT INTO order_status_history (order_id, from_status, to_status, note)
+			VALUES ($1, $2, $3, $4);
+		"#,
+        order_id,
+        from as _,
+        to as _,
+        note
+    )
+    .execute(tx)
+    .await
+    .map(|_| ())
+}
+
+/// The order in the path, if the request may act on it: customers on their
+/// own orders, guests on the one whose access token they send. Other orders
+/// look the same as ones that don't exist.
+pub struct OrderAccess(pub i64);
+
+#[axum::async_trait]
+impl<B: Send> FromRequest<B> for OrderAccess {
+    type Rejection = (StatusCode, axum::Json<ApiError>);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let pool = match request.extensions().get::<PgPool>() {
+            Some(p) => p.clone(),
+            None => return Err(ApiError::internal_server_error("database not configured")),
+        };
+
+        let Path(id) = Path::<i64>::from_request(request)
+            .await
+            .map_err(|e| ApiError::bad_request(&e.to_string()))?;
+        let OptionalJwtToken(token) = OptionalJwtToken::from_request(request).await?;
+        let access_token = request
+            .headers()
+            .get(ORDER_TOKEN_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
+
+        match Order::is_accessible(id, token.map(|t| t.sub), access_token.as_deref(), &pool).await {
+            Ok(true) => Ok(OrderAccess(id)),
+            Ok(false) => Err(ApiError::not_found(&OrderError::NotFound(id).to_string())),
+            Err(e) => Err(ApiError::internal_server_error(&e)),
+        }
+    }
+}
+
+fn access_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+fn hash_access_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderStatus::{self, *};
+
+    const ALL: [OrderStatus; 7] = [
+        Pending, Paid, Picking, Shipped, Delivered, Cancelled, Refunded,
+    ];
+
+    #[test]
+    fn follows_the_order_lifecycle() {
+        assert!(Pending.can_transition_to(Paid));
+        assert!(Paid.can_transition_to(Picking));
+        assert!(Picking.can_transition_to(Shipped));
+        assert!(Shipped.can_transition_to(Delivered));
+
+        assert!(!Pending.can_transition_to(Shipped));
+        assert!(!Delivered.can_transition_to(Shipped));
+        assert!(!Paid.can_transition_to(Pending));
+    }
+
+    #[test]
+    fn cancels_only_before_shipping() {
+        for from in ALL {
+            assert_eq!(
+                from.can_transition_to(Cancelled),
+                matches!(from, Pending | Paid | Picking),
+                "{} -> cancelled",
+                from.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn refunds_only_paid_orders() {
+        for from in ALL {
+            assert_eq!(
+                from.can_transition_to(Refunded),
+                matches!(from, Paid | Picking | Shipped | Delivered),
+                "{} -> refunded",
+                from.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn final_states_stay_final() {
+        for to in ALL {
+            assert!(!Cancelled.can_transition_to(to));
+            assert!(!Refunded.can_transition_to(to));
+        }
+    }
+
+    #[test]
+    fn releases_stock_that_never_left_the_warehouse() {
+        assert!(Pending.releases_stock(Cancelled));
+        assert!(Picking.releases_stock(Cancelled));
+        assert!(Paid.releases_stock(Refunded));
+        assert!(!Shipped.releases_stock(Refunded));
+        assert!(!Delivered.releases_stock(Refunded));
+        assert!(!Pending.releases_stock(Paid));
+    }
+}
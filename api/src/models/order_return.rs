@@ -0,0 +1,692 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::{Decimal, Json};
+use sqlx::{PgPool, Postgres, Transaction};
+use validator::{Validate, ValidationError};
+
+use crate::models::credit_note::CreditNote;
+use crate::models::inventory_movement::{InventoryMovement, InventoryMovementKind};
+use crate::models::order::OrderStatus;
+use crate::models::payment::{Payment, PaymentError, PaymentRefundInsert, RefundStart};
+use crate::models::{validate_decimal, MAX_I32_CONST, MIN_ORDER_QUANTITY};
+use crate::openapi::ApiSchema;
+use crate::payments::PaymentProvider;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "return_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnStatus {
+    Requested,
+    Approved,
+    Rejected,
+    Received,
+    Refunded,
+}
+
+impl ReturnStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReturnStatus::Requested => "requested",
+            ReturnStatus::Approved => "approved",
+            ReturnStatus::Rejected => "rejected",
+            ReturnStatus::Received => "received",
+            ReturnStatus::Refunded => "refunded",
+        }
+    }
+
+    /// requested -> approved -> received -> refunded, or requested -> rejected.
+    pub fn can_transition_to(&self, next: ReturnStatus) -> bool {
+        use ReturnStatus::*;
+
+        matches!(
+            (self, next),
+            (Requested, Approved | Rejected) | (Approved, Received) | (Received, Refunded)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "return_line_condition", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnLineCondition {
+    Restocked,
+    Damaged,
+}
+
+#[derive(Debug)]
+pub enum ReturnError {
+    NotFound(i64),
+    OrderNotFound(i64),
+    OrderNotReturnable(OrderStatus),
+    IllegalTransition {
+        from: ReturnStatus,
+        to: ReturnStatus,
+    },
+    InvalidLine(String),
+    InvalidAmount(String),
+    NoRefundablePayment(i64),
+    Payment(PaymentError),
+    Database(String),
+}
+
+impl From<sqlx::Error> for ReturnError {
+    fn from(e: sqlx::Error) -> Self {
+        ReturnError::Database(e.to_string())
+    }
+}
+
+impl From<PaymentError> for ReturnError {
+    fn from(e: PaymentError) -> Self {
+        ReturnError::Payment(e)
+    }
+}
+
+impl std::fmt::Display for ReturnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReturnError::NotFound(id) => write!(f, "return {} not found", id),
+            ReturnError::OrderNotFound(id) => write!(f, "order {} not found", id),
+            ReturnError::OrderNotReturnable(status) => write!(
+                f,
+                "only shipped or delivered orders can be returned, order is {}",
+                status.as_str()
+            ),
+            ReturnError::IllegalTransition { from, to } => write!(
+                f,
+                "illegal return transition: {} -> {}",
+                from.as_str(),
+                to.as_str()
+            ),
+            ReturnError::InvalidLine(e) => write!(f, "{}", e),
+            ReturnError::InvalidAmount(e) => write!(f, "{}", e),
+            ReturnError::NoRefundablePayment(id) => {
+                write!(f, "order {} has no captured payment to refund", id)
+            }
+            ReturnError::Payment(e) => write!(f, "{}", e),
+            ReturnError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ApiSchema)]
+pub struct ReturnLine {
+    pub id: i64,
+    pub return_id: i64,
+    pub order_item_id: i64,
+    pub quantity: i32,
+    pub reason: String,
+    pub condition: Option<ReturnLineCondition>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct OrderReturn {
+    pub id: i64,
+    pub order_id: i64,
+    pub status: ReturnStatus,
+    pub note: Option<String>,
+    pub lines: Json<Vec<ReturnLine>>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ReturnInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(min = 1, message = "return must contain at least one line")
+    )]
+    #[validate]
+    lines: Option<Vec<ReturnLineInsert>>,
+
+    #[validate(length(max = 500, message = "field contains too many characters - max: 500"))]
+    note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Validate, ApiSchema)]
+pub struct ReturnLineInsert {
+    order_item_id: i64,
+
+    #[validate(range(
+        min = "MIN_ORDER_QUANTITY",
+        max = "MAX_I32_CONST",
+        message = "field contains invalid value - min: 1, max: i32"
+    ))]
+    quantity: i32,
+
+    #[validate(length(
+        min = 1,
+        max = 500,
+        message = "field must contain between 1 and 500 characters"
+    ))]
+    reason: String,
+}
+
+#[derive(Deserialize, Validate, Default, ApiSchema)]
+pub struct ReturnDecision {
+    #[validate(length(max = 500, message = "field contains too many characters - max: 500"))]
+    pub note: Option<String>,
+}
+
+/// Condition of the received goods, lines that aren't listed are restocked.
+#[derive(Deserialize, Validate, Default, ApiSchema)]
+pub struct ReturnReceive {
+    #[serde(default)]
+    #[validate(custom = "validate_unique_lines")]
+    lines: Vec<ReturnLineReceive>,
+}
+
+#[derive(Serialize, Deserialize, ApiSchema)]
+pub struct ReturnLineReceive {
+    line_id: i64,
+    condition: ReturnLineCondition,
+}
+
+#[derive(Deserialize, Validate, Default, ApiSchema)]
+pub struct ReturnRefund {
+    /// Defaults to the value of the returned lines that isn't refunded yet.
+    #[validate(custom = "validate_decimal")]
+    amount: Option<Decimal>,
+}
+
+fn validate_unique_lines(lines: &[ReturnLineReceive]) -> Result<(), ValidationError> {
+    let mut ids: Vec<i64> = lines.iter().map(|l| l.line_id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    if ids.len() != lines.len() {
+        let mut error = ValidationError::new("unique");
+        error.message = Some("every line can only be listed once".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+impl OrderReturn {
+    #[tracing::instrument(name = "OrderReturn::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<OrderReturn>, String> {
+        sqlx::query_as!(
+            OrderReturn,
+            r#"
+				SELECT r.id, r.order_id, r.status as "status: ReturnStatus", r.note,
+					r.created_at, r.updated_at,
+					COALESCE(
+						json_agg(rl.* ORDER BY rl.id) FILTER (WHERE rl.id IS NOT NULL),
+						'[]'
+					) as "lines!: Json<Vec<ReturnLine>>"
+				FROM order_return r
+				LEFT JOIN return_line rl ON rl.return_id = r.id
+				GROUP BY r.id
+				ORDER BY r.id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "OrderReturn::find_by_order", skip_all)]
+    pub async fn find_by_order(order_id: i64, pool: &PgPool) -> Result<Vec<OrderReturn>, String> {
+        sqlx::query_as!(
+            OrderReturn,
+            r#"
+				SELECT r.id, r.order_id, r.status as "status: ReturnStatus", r.note,
+					r.created_at, r.updated_at,
+					COALESCE(
+						json_agg(rl.* ORDER BY rl.id) FILTER (WHERE rl.id IS NOT NULL),
+						'[]'
+					) as "lines!: Json<Vec<ReturnLine>>"
+				FROM order_return r
+				LEFT JOIN return_line rl ON rl.return_id = r.id
+				WHERE r.order_id = $1
+				GROUP BY r.id
+				ORDER BY r.id ASC;
+			"#,
+            order_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "OrderReturn::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Option<OrderReturn>, String> {
+        sqlx::query_as!(
+            OrderReturn,
+            r#"
+				SELECT r.id, r.order_id, r.status as "status: ReturnStatus", r.note,
+					r.created_at, r.updated_at,
+					COALESCE(
+						json_agg(rl.* ORDER BY rl.id) FILTER (WHERE rl.id IS NOT NULL),
+						'[]'
+					) as "lines!: Json<Vec<ReturnLine>>"
+				FROM order_return r
+				LEFT JOIN return_line rl ON rl.return_id = r.id
+				WHERE r.id = $1
+				GROUP BY r.id;
+			"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Opens a return request for items of a shipped or delivered order. A
+    /// line can't return more than was ordered minus what is already being
+    /// returned by requests that weren't rejected.
+    #[tracing::instrument(name = "OrderReturn::create", skip_all)]
+    pub async fn create(
+        order_id: i64,
+        input: ReturnInsert,
+        pool: &PgPool,
+    ) -> Result<OrderReturn, ReturnError> {
+        let mut tx = pool.begin().await?;
+
+        let order_status = sqlx::query!(
+            r#"
+				SELECT status as "status: OrderStatus" FROM customer_order
+				WHERE id = $1
+				FOR UPDATE;
+			"#,
+            order_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(ReturnError::OrderNotFound(order_id))?
+        .status;
+
+        if !matches!(order_status, OrderStatus::Shipped | OrderStatus::Delivered) {
+            return Err(ReturnError::OrderNotReturnable(order_status));
+        }
+
+        let return_id = sqlx::query!(
+            r#"
+				INSERT INTO order_return (order_id, note)
+				VALUES ($1, $2)
+				RETURNING id;
+			"#,
+            order_id,
+            input.note
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .id;
+
+        for line in input.lines.unwrap_or_default() {
+            let returnable = sqlx::query!(
+                r#"
+					SELECT oi.quantity - COALESCE(
+						(
+							SELECT SUM(rl.quantity) FROM return_line rl
+							JOIN order_return r ON r.id = rl.return_id
+							WHERE rl.order_item_id = oi.id AND r.status <> 'rejected'
+						),
+						0
+					) as "returnable!"
+					FROM order_item oi
+					WHERE oi.id = $1 AND oi.order_id = $2;
+				"#,
+                line.order_item_id,
+                order_id
+            )
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| {
+                ReturnError::InvalidLine(format!(
+                    "order item {} doesn't belong to order {}",
+                    line.order_item_id, order_id
+                ))
+            })?
+            .returnable;
+
+            if i64::from(line.quantity) > returnable {
+                return Err(ReturnError::InvalidLine(format!(
+                    "only {} of order item {} can be returned",
+                    returnable, line.order_item_id
+                )));
+            }
+
+            sqlx::query!(
+                r#"
+					INSERT INTO return_line (return_id, order_item_id, quantity, reason)
+					VALUES ($1, $2, $3, $4);
+				"#,
+                return_id,
+                line.order_item_id,
+                line.quantity,
+                line.reason
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        OrderReturn::find_by_id(return_id, pool)
+            .await
+            .map_err(ReturnError::Database)?
+            .ok_or(ReturnError::NotFound(return_id))
+    }
+
+    #[tracing::instrument(name = "OrderReturn::approve", skip_all)]
+    pub async fn approve(
+        id: i64,
+        input: ReturnDecision,
+        pool: &PgPool,
+    ) -> Result<OrderReturn, ReturnError> {
+        let mut tx = pool.begin().await?;
+        transition(id, ReturnStatus::Approved, input.note, &mut tx).await?;
+        tx.commit().await?;
+
+        OrderReturn::find_by_id(id, pool)
+            .await
+            .map_err(ReturnError::Database)?
+            .ok_or(ReturnError::NotFound(id))
+    }
+
+    #[tracing::instrument(name = "OrderReturn::reject", skip_all)]
+    pub async fn reject(
+        id: i64,
+        input: ReturnDecision,
+        pool: &PgPool,
+    ) -> Result<OrderReturn, ReturnError> {
+        let mut tx = pool.begin().await?;
+        transition(id, ReturnStatus::Rejected, input.note, &mut tx).await?;
+        tx.commit().await?;
+
+        OrderReturn::find_by_id(id, pool)
+            .await
+            .map_err(ReturnError::Database)?
+            .ok_or(ReturnError::NotFound(id))
+    }
+
+    /// Books the returned goods back into `product_inventory`, or writes them
+    /// off when they came back damaged.
+    #[tracing::instrument(name = "OrderReturn::receive", skip_all)]
+    pub async fn receive(
+        id: i64,
+        input: ReturnReceive,
+        pool: &PgPool,
+    ) -> Result<OrderReturn, ReturnError> {
+        let mut tx = pool.begin().await?;
+        transition(id, ReturnStatus::Received, None, &mut tx).await?;
+
+        let lines = sqlx::query!(
+            r#"
+				SELECT rl.id, rl.quantity, oi.inventory_id
+				FROM return_line rl
+				JOIN order_item oi ON oi.id = rl.order_item_id
+				WHERE rl.return_id = $1;
+			"#,
+            id
+        )
+        .fetch_all(&mut tx)
+        .await
+        .record_rows()?;
+
+        for received in input.lines.iter() {
+            if !lines.iter().any(|l| l.id == received.line_id) {
+                return Err(ReturnError::InvalidLine(format!(
+                    "line {} doesn't belong to return {}",
+                    received.line_id, id
+                )));
+            }
+        }
+
+        for line in lines {
+            let condition = input
+                .lines
+                .iter()
+                .find(|l| l.line_id == line.id)
+                .map(|l| l.condition)
+                .unwrap_or(ReturnLineCondition::Restocked);
+
+            sqlx::query!(
+                r#"UPDATE return_line SET condition = $1 WHERE id = $2;"#,
+                condition as _,
+                line.id
+            )
+            .execute(&mut tx)
+            .await?;
+
+            let kind = match condition {
+                ReturnLineCondition::Restocked => InventoryMovementKind::ReturnRestock,
+                ReturnLineCondition::Damaged => InventoryMovementKind::WriteOff,
+            };
+
+            if let Some(inventory_id) = line.inventory_id {
+                InventoryMovement::record(
+                    inventory_id,
+                    kind,
+                    line.quantity,
+                    Some(line.id),
+                    Some(format!("return {}", id)),
+                    &mut tx,
+                )
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        OrderReturn::find_by_id(id, pool)
+            .await
+            .map_err(ReturnError::Database)?
+            .ok_or(ReturnError::NotFound(id))
+    }
+
+    /// Refunds a received return through the payment provider, in one go or
+    /// in parts up to the value of the returned lines. Every refund issues a
+    /// credit note, the return is refunded once its full value is.
+    #[tracing::instrument(name = "OrderReturn::refund", skip_all)]
+    pub async fn refund(
+        id: i64,
+        input: ReturnRefund,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<CreditNote, ReturnError> {
+        let mut tx = pool.begin().await?;
+        let current = sqlx::query!(
+            r#"
+				SELECT order_id, status as "status: ReturnStatus" FROM order_return
+				WHERE id = $1
+				FOR UPDATE;
+			"#,
+            id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(ReturnError::NotFound(id))?;
+
+        if !current.status.can_transition_to(ReturnStatus::Refunded) {
+            return Err(ReturnError::IllegalTransition {
+                from: current.status,
+                to: ReturnStatus::Refunded,
+            });
+        }
+        let order_id = current.order_id;
+
+        let value = value(id, &mut tx).await?;
+        let refunded = sqlx::query!(
+            r#"
+				SELECT COALESCE(SUM(amount), 0) as "amount!" FROM payment_refund
+				WHERE return_id = $1;
+			"#,
+            id
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .amount;
+
+        let remaining = value - refunded;
+        let amount = input.amount.unwrap_or(remaining);
+        if amount <= Decimal::ZERO || amount > remaining {
+            return Err(ReturnError::InvalidAmount(format!(
+                "refund amount must be greater than 0 and at most {}",
+                remaining
+            )));
+        }
+
+        let payment_id = sqlx::query!(
+            r#"
+				SELECT id FROM payment
+				WHERE order_id = $1 AND status IN ('captured', 'partially_refunded')
+				ORDER BY id DESC
+				LIMIT 1;
+			"#,
+            order_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(ReturnError::NoRefundablePayment(order_id))?
+        .id;
+
+        let refund = PaymentRefundInsert {
+            amount: Some(amount),
+            idempotency_key: None,
+            reason: Some(format!("return {}", id)),
+        };
+        let RefundStart::Pending(refund) =
+            Payment::start_refund_in(payment_id, refund, Some(id), &mut tx).await?
+        else {
+            return Err(ReturnError::Database(
+                "refund without an idempotency key was repeated".to_string(),
+            ));
+        };
+        tx.commit().await?;
+
+        let refund_id = refund.id();
+        Payment::finish_refund(refund, provider, pool).await?;
+
+        CreditNote::find_by_refund(refund_id, pool)
+            .await
+            .map_err(ReturnError::Database)?
+            .ok_or_else(|| {
+                ReturnError::Database(format!("refund {} has no credit note", refund_id))
+            })
+    }
+
+    /// Moves a received return to refunded once refunds of its full value
+    /// succeeded. Called when a refund for the return completes.
+    pub(crate) async fn settle_in(
+        id: i64,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        let status = sqlx::query!(
+            r#"
+				SELECT status as "status: ReturnStatus" FROM order_return
+				WHERE id = $1
+				FOR UPDATE;
+			"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .status;
+
+        if !status.can_transition_to(ReturnStatus::Refunded) {
+            return Ok(());
+        }
+
+        let refunded = sqlx::query!(
+            r#"
+				SELECT COALESCE(SUM(amount), 0) as "amount!" FROM payment_refund
+				WHERE return_id = $1 AND status = 'succeeded';
+			"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .amount;
+
+        if refunded >= value(id, tx).await? {
+            sqlx::query!(
+                r#"
+					UPDATE order_return SET
+						status = 'refunded',
+						updated_at = NOW()
+					WHERE id = $1;
+				"#,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What the returned lines were paid for, tax included, rounded to the minor
+/// units of the order's currency.
+async fn value(id: i64, tx: &mut Transaction<'_, Postgres>) -> Result<Decimal, sqlx::Error> {
+    sqlx::query!(
+        r#"
+			SELECT COALESCE(
+				SUM(ROUND(
+					rl.quantity * (oi.net_amount + oi.tax_amount) / oi.quantity,
+					c.minor_units
+				)),
+				0
+			) as "value!"
+			FROM return_line rl
+			JOIN order_item oi ON oi.id = rl.order_item_id
+			JOIN customer_order o ON o.id = oi.order_id
+			JOIN currency c ON c.code = o.currency
+			WHERE rl.return_id = $1;
+		"#,
+        id
+    )
+    .fetch_one(tx)
+    .await
+    .map(|r| r.value)
+}
+
+/// Moves a return to `to` and returns the id of its order.
+async fn transition(
+    id: i64,
+    to: ReturnStatus,
+    note: Option<String>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<i64, ReturnError> {
+    let current = sqlx::query!(
+        r#"
+			SELECT order_id, status as "status: ReturnStatus" FROM order_return
+			WHERE id = $1
+			FOR UPDATE;
+		"#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ReturnError::NotFound(id))?;
+
+    if !current.status.can_transition_to(to) {
+        return Err(ReturnError::IllegalTransition {
+            from: current.status,
+            to,
+        });
+    }
+
+    sqlx::query!(
+        r#"
+			UPDATE order_return SET
+				status = $1,
+				note = COALESCE($2, note),
+				updated_at = NOW()
+			WHERE id = $3;
+		"#,
+        to as _,
+        note,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(current.order_id)
+}
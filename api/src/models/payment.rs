@@ -0,0 +1,953 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use sqlx::{PgPool, Postgres, Transaction};
+use validator::Validate;
+
+use crate::models::credit_note::CreditNote;
+use crate::models::order::{Order, OrderError, OrderStatus, OrderTransition};
+use crate::models::order_return::OrderReturn;
+use crate::openapi::ApiSchema;
+use crate::payments::{PaymentProvider, ProviderError, WebhookEvent, WebhookEventKind};
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "payment_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Authorized,
+    Captured,
+    PartiallyRefunded,
+    Refunded,
+    Voided,
+    Failed,
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Authorized => "authorized",
+            PaymentStatus::Captured => "captured",
+            PaymentStatus::PartiallyRefunded => "partially_refunded",
+            PaymentStatus::Refunded => "refunded",
+            PaymentStatus::Voided => "voided",
+            PaymentStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PaymentError {
+    NotFound(i64),
+    UnknownReference(String),
+    /// The order already has a payment waiting to be captured.
+    AlreadyAuthorized(i64),
+    InvalidState {
+        status: PaymentStatus,
+        action: &'static str,
+    },
+    InvalidAmount(String),
+    Provider(ProviderError),
+    Order(OrderError),
+    Database(String),
+}
+
+impl From<sqlx::Error> for PaymentError {
+    fn from(e: sqlx::Error) -> Self {
+        PaymentError::Database(e.to_string())
+    }
+}
+
+impl From<ProviderError> for PaymentError {
+    fn from(e: ProviderError) -> Self {
+        PaymentError::Provider(e)
+    }
+}
+
+impl From<OrderError> for PaymentError {
+    fn from(e: OrderError) -> Self {
+        PaymentError::Order(e)
+    }
+}
+
+impl std::fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaymentError::NotFound(id) => write!(f, "payment {} not found", id),
+            PaymentError::UnknownReference(r) => write!(f, "no payment with reference {}", r),
+            PaymentError::AlreadyAuthorized(id) => {
+                write!(f, "order {} already has an authorized payment", id)
+            }
+            PaymentError::InvalidState { status, action } => {
+                write!(f, "can't {} a payment that is {}", action, status.as_str())
+            }
+            PaymentError::InvalidAmount(e) => write!(f, "{}", e),
+            PaymentError::Provider(e) => write!(f, "{}", e),
+            PaymentError::Order(e) => write!(f, "{}", e),
+            PaymentError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct Payment {
+    pub id: i64,
+    pub order_id: i64,
+    pub provider: String,
+    pub reference: Option<String>,
+    pub status: PaymentStatus,
+    pub currency: String,
+    pub amount: Decimal,
+    pub captured_amount: Decimal,
+    pub refunded_amount: Decimal,
+    pub failure_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct PaymentInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(max = 128, message = "field contains too many characters - max: 128")
+    )]
+    source: Option<String>,
+}
+
+#[derive(Deserialize, Validate, Default, ApiSchema)]
+pub struct PaymentRefundInsert {
+    pub amount: Option<Decimal>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    pub idempotency_key: Option<String>,
+
+    #[validate(length(max = 500, message = "field contains too many characters - max: 500"))]
+    pub reason: Option<String>,
+}
+
+/// A refund recorded before the provider was asked to make it.
+pub struct PendingRefund {
+    id: i64,
+    payment_id: i64,
+    payment_reference: String,
+    amount: Decimal,
+}
+
+impl PendingRefund {
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+}
+
+pub enum RefundStart {
+    Pending(PendingRefund),
+    /// A refund with the same idempotency key was already made.
+    Repeated(Payment),
+}
+
+impl Payment {
+    #[tracing::instrument(name = "Payment::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Option<Payment>, String> {
+        sqlx::query_as!(
+            Payment,
+            r#"
+				SELECT id, order_id, provider, reference, status as "status: PaymentStatus",
+					currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at
+				FROM payment
+				WHERE id = $1;
+			"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "Payment::find_by_order", skip_all)]
+    pub async fn find_by_order(order_id: i64, pool: &PgPool) -> Result<Vec<Payment>, String> {
+        sqlx::query_as!(
+            Payment,
+            r#"
+				SELECT id, order_id, provider, reference, status as "status: PaymentStatus",
+					currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at
+				FROM payment
+				WHERE order_id = $1
+				ORDER BY id ASC;
+			"#,
+            order_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Authorizes the order total with the provider, unless an earlier
+    /// authorization is still live. Declined attempts are kept as failed
+    /// payments so they show up on the order.
+    #[tracing::instrument(name = "Payment::authorize", skip_all)]
+    pub async fn authorize(
+        order_id: i64,
+        input: PaymentInsert,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Payment, PaymentError> {
+        let order = Order::find_by_id(order_id, pool)
+            .await
+            .map_err(PaymentError::Database)?
+            .ok_or(OrderError::NotFound(order_id))?;
+
+        if order.status != OrderStatus::Pending {
+            return Err(OrderError::IllegalTransition {
+                from: order.status,
+                to: OrderStatus::Paid,
+            }
+            .into());
+        }
+
+        let authorized = sqlx::query!(
+            r#"
+				SELECT EXISTS (
+					SELECT 1 FROM payment WHERE order_id = $1 AND status = 'authorized'
+				) as "exists!";
+			"#,
+            order_id
+        )
+        .fetch_one(pool)
+        .await?
+        .exists;
+        if authorized {
+            return Err(PaymentError::AlreadyAuthorized(order_id));
+        }
+
+        let (reference, status, failure_reason) = match provider
+            .authorize(
+                order.total.amount,
+                &order.currency,
+                &input.source.unwrap_or_default(),
+            )
+            .await
+        {
+            Ok(reference) => (Some(reference), PaymentStatus::Authorized, None),
+            Err(ProviderError::Declined(reason)) => (None, PaymentStatus::Failed, Some(reason)),
+            Err(e) => return Err(e.into()),
+        };
+
+        let payment = sqlx::query_as!(
+            Payment,
+            r#"
+				INSERT INTO payment (order_id, provider, reference, status, currency, amount, failure_reason)
+				VALUES ($1, $2, $3, $4, $5, $6, $7)
+				RETURNING id, order_id, provider, reference, status as "status: PaymentStatus",
+					currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at;
+			"#,
+            order_id,
+            provider.name(),
+            reference,
+            status as _,
+            order.currency,
+            order.total.amount,
+            failure_reason
+        )
+        .fetch_one(pool)
+        .await?;
+
+        match payment.failure_reason {
+            Some(reason) => Err(ProviderError::Declined(reason).into()),
+            None => Ok(payment),
+        }
+    }
+
+    /// Captures an authorized payment and marks the order as paid. Capturing
+    /// an already captured payment returns it unchanged.
+    ///
+    /// The provider is asked before anything is written, so that no
+    /// transaction is held open during the call. If recording the capture
+    /// fails afterwards, the provider's `payment.captured` webhook records it.
+    #[tracing::instrument(name = "Payment::capture", skip_all)]
+    pub async fn capture(
+        id: i64,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Payment, PaymentError> {
+        let payment = Payment::find_by_id(id, pool)
+            .await
+            .map_err(PaymentError::Database)?
+            .ok_or(PaymentError::NotFound(id))?;
+
+        match payment.status {
+            PaymentStatus::Captured
+            | PaymentStatus::PartiallyRefunded
+            | PaymentStatus::Refunded => return Ok(payment),
+            PaymentStatus::Authorized => (),
+            status => {
+                return Err(PaymentError::InvalidState {
+                    status,
+                    action: "capture",
+                })
+            }
+        }
+
+        let order_status = order_status(payment.order_id, pool).await?;
+        if !order_status.can_transition_to(OrderStatus::Paid) {
+            return Err(OrderError::IllegalTransition {
+                from: order_status,
+                to: OrderStatus::Paid,
+            }
+            .into());
+        }
+
+        provider
+            .capture(
+                payment.reference.as_deref().unwrap_or_default(),
+                payment.amount,
+            )
+            .await?;
+
+        let mut tx = pool.begin().await?;
+        let payment = lock(id, &mut tx).await?;
+
+        // Recorded by the webhook in the meantime.
+        if payment.status != PaymentStatus::Authorized {
+            return Ok(payment);
+        }
+
+        Order::transition_in(
+            payment.order_id,
+            OrderStatus::Paid,
+            Some(format!("payment {} captured", payment.id)),
+            &mut tx,
+        )
+        .await?;
+
+        let payment = set_captured(payment.id, payment.amount, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(payment)
+    }
+
+    /// Releases an authorization that won't be captured. Voiding twice is a
+    /// no-op. Like [`Payment::capture`], the provider is asked first.
+    #[tracing::instrument(name = "Payment::void", skip_all)]
+    pub async fn void(
+        id: i64,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Payment, PaymentError> {
+        let payment = Payment::find_by_id(id, pool)
+            .await
+            .map_err(PaymentError::Database)?
+            .ok_or(PaymentError::NotFound(id))?;
+
+        match payment.status {
+            PaymentStatus::Voided => return Ok(payment),
+            PaymentStatus::Authorized => (),
+            status => {
+                return Err(PaymentError::InvalidState {
+                    status,
+                    action: "void",
+                })
+            }
+        }
+
+        provider
+            .void(payment.reference.as_deref().unwrap_or_default())
+            .await?;
+
+        let mut tx = pool.begin().await?;
+        let payment = lock(id, &mut tx).await?;
+
+        if payment.status != PaymentStatus::Authorized {
+            return Ok(payment);
+        }
+
+        let payment = set_status(payment.id, PaymentStatus::Voided, None, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(payment)
+    }
+
+    /// Refunds `amount` (the whole remaining captured amount by default).
+    /// Repeating a refund with the same `idempotency_key` returns the payment
+    /// without refunding again. Every refund issues a credit note, a full
+    /// refund also moves the order to refunded.
+    #[tracing::instrument(name = "Payment::refund", skip_all)]
+    pub async fn refund(
+        id: i64,
+        input: PaymentRefundInsert,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Payment, PaymentError> {
+        let mut tx = pool.begin().await?;
+        let started = Payment::start_refund_in(id, input, None, &mut tx).await?;
+        tx.commit().await?;
+
+        match started {
+            RefundStart::Pending(refund) => Payment::finish_refund(refund, provider, pool).await,
+            RefundStart::Repeated(payment) => Ok(payment),
+        }
+    }
+
+    /// Records a refund as pending inside the caller's transaction, to be
+    /// made with [`Payment::finish_refund`] once it's committed. Pending
+    /// refunds count against the refundable amount, so that concurrent
+    /// refunds can't add up to more than was captured.
+    #[tracing::instrument(name = "Payment::start_refund_in", skip_all)]
+    pub async fn start_refund_in(
+        id: i64,
+        input: PaymentRefundInsert,
+        return_id: Option<i64>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<RefundStart, PaymentError> {
+        let payment = lock(id, tx).await?;
+
+        if let Some(key) = &input.idempotency_key {
+            let existing = sqlx::query!(
+                r#"
+					SELECT id FROM payment_refund
+					WHERE payment_id = $1 AND idempotency_key = $2;
+				"#,
+                payment.id,
+                key
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if existing.is_some() {
+                return Ok(RefundStart::Repeated(payment));
+            }
+        }
+
+        if !matches!(
+            payment.status,
+            PaymentStatus::Captured | PaymentStatus::PartiallyRefunded
+        ) {
+            return Err(PaymentError::InvalidState {
+                status: payment.status,
+                action: "refund",
+            });
+        }
+
+        let pending = sqlx::query!(
+            r#"
+				SELECT COALESCE(SUM(amount), 0) as "amount!" FROM payment_refund
+				WHERE payment_id = $1 AND status = 'pending';
+			"#,
+            payment.id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .amount;
+
+        let remaining = payment.captured_amount - payment.refunded_amount - pending;
+        let amount = input.amount.unwrap_or(remaining);
+
+        if amount <= Decimal::ZERO || amount > remaining {
+            return Err(PaymentError::InvalidAmount(format!(
+                "refund amount must be greater than 0 and at most {}",
+                remaining
+            )));
+        }
+
+        let refund_id = sqlx::query!(
+            r#"
+				INSERT INTO payment_refund
+					(payment_id, return_id, idempotency_key, amount, reason, status)
+				VALUES ($1, $2, $3, $4, $5, 'pending')
+				RETURNING id;
+			"#,
+            payment.id,
+            return_id,
+            input.idempotency_key,
+            amount,
+            input.reason
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .id;
+
+        Ok(RefundStart::Pending(PendingRefund {
+            id: refund_id,
+            payment_id: payment.id,
+            payment_reference: payment.reference.unwrap_or_default(),
+            amount,
+        }))
+    }
+
+    /// Asks the provider for a refund recorded by
+    /// [`Payment::start_refund_in`] and completes it. A refund the provider
+    /// declined is dropped. If completing it fails after the provider made
+    /// it, the provider's `refund.succeeded` webhook completes it instead.
+    #[tracing::instrument(name = "Payment::finish_refund", skip_all)]
+    pub async fn finish_refund(
+        refund: PendingRefund,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Payment, PaymentError> {
+        let reference = match provider
+            .refund(&refund.payment_reference, refund.amount)
+            .await
+        {
+            Ok(reference) => reference,
+            Err(e) => {
+                sqlx::query!(
+                    r#"DELETE FROM payment_refund WHERE id = $1 AND status = 'pending';"#,
+                    refund.id
+                )
+                .execute(pool)
+                .await
+                .record_rows()?;
+
+                return Err(e.into());
+            }
+        };
+
+        let mut tx = pool.begin().await?;
+        let payment = lock(refund.payment_id, &mut tx).await?;
+        let payment = complete_refund(payment, refund.id, &reference, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(payment)
+    }
+
+    /// Cancels an order, voiding its authorized payments first. An order
+    /// with captured money has to be refunded instead.
+    #[tracing::instrument(name = "Payment::cancel_order", skip_all)]
+    pub async fn cancel_order(
+        order_id: i64,
+        input: OrderTransition,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Order, PaymentError> {
+        let from = order_status(order_id, pool).await?;
+        if !from.can_transition_to(OrderStatus::Cancelled) {
+            return Err(OrderError::IllegalTransition {
+                from,
+                to: OrderStatus::Cancelled,
+            }
+            .into());
+        }
+
+        let payments = Payment::find_by_order(order_id, pool)
+            .await
+            .map_err(PaymentError::Database)?;
+        if let Some(payment) = payments.iter().find(|p| {
+            matches!(
+                p.status,
+                PaymentStatus::Captured | PaymentStatus::PartiallyRefunded
+            )
+        }) {
+            return Err(PaymentError::InvalidState {
+                status: payment.status,
+                action: "cancel the order of",
+            });
+        }
+
+        for payment in payments {
+            if payment.status == PaymentStatus::Authorized {
+                Payment::void(payment.id, provider, pool).await?;
+            }
+        }
+
+        Ok(Order::transition(order_id, OrderStatus::Cancelled, input, pool).await?)
+    }
+
+    /// Refunds an order: authorized payments are voided and the remaining
+    /// captured amount of the others is refunded, which moves the order to
+    /// refunded. Orders paid without a payment are moved there directly.
+    #[tracing::instrument(name = "Payment::refund_order", skip_all)]
+    pub async fn refund_order(
+        order_id: i64,
+        input: OrderTransition,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<Order, PaymentError> {
+        let from = order_status(order_id, pool).await?;
+        if !from.can_transition_to(OrderStatus::Refunded) {
+            return Err(OrderError::IllegalTransition {
+                from,
+                to: OrderStatus::Refunded,
+            }
+            .into());
+        }
+
+        let payments = Payment::find_by_order(order_id, pool)
+            .await
+            .map_err(PaymentError::Database)?;
+        for payment in payments {
+            match payment.status {
+                PaymentStatus::Authorized => {
+                    Payment::void(payment.id, provider, pool).await?;
+                }
+                PaymentStatus::Captured | PaymentStatus::PartiallyRefunded => {
+                    let refund = PaymentRefundInsert {
+                        reason: input.note.clone(),
+                        ..Default::default()
+                    };
+                    Payment::refund(payment.id, refund, provider, pool).await?;
+                }
+                _ => (),
+            }
+        }
+
+        if order_status(order_id, pool).await? != OrderStatus::Refunded {
+            return Ok(Order::transition(order_id, OrderStatus::Refunded, input, pool).await?);
+        }
+
+        Order::find_by_id(order_id, pool)
+            .await
+            .map_err(PaymentError::Database)?
+            .ok_or_else(|| OrderError::NotFound(order_id).into())
+    }
+
+    /// Applies a verified webhook. Events that were already processed are
+    /// ignored, returns whether the event was new. A capture or refund that
+    /// its order or payment can no longer take is stored and logged, so that
+    /// the provider stops redelivering it.
+    #[tracing::instrument(name = "Payment::handle_webhook", skip_all)]
+    pub async fn handle_webhook(
+        event: WebhookEvent,
+        provider: &dyn PaymentProvider,
+        pool: &PgPool,
+    ) -> Result<bool, PaymentError> {
+        let mut tx = pool.begin().await?;
+
+        let payload =
+            serde_json::to_value(&event).map_err(|e| PaymentError::Database(e.to_string()))?;
+        let inserted = sqlx::query!(
+            r#"
+				INSERT INTO payment_webhook_event (provider, event_id, payload)
+				VALUES ($1, $2, $3)
+				ON CONFLICT (provider, event_id) DO NOTHING
+				RETURNING id;
+			"#,
+            provider.name(),
+            event.id,
+            payload
+        )
+        .fetch_optional(&mut tx)
+        .await
+        .record_rows()?;
+
+        if inserted.is_none() {
+            return Ok(false);
+        }
+
+        let id = sqlx::query!(
+            r#"
+				SELECT id FROM payment
+				WHERE provider = $1 AND reference = $2;
+			"#,
+            provider.name(),
+            event.reference
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| PaymentError::UnknownReference(event.reference.clone()))?
+        .id;
+
+        let payment = lock(id, &mut tx).await?;
+
+        match (event.kind, payment.status) {
+            (WebhookEventKind::Captured, PaymentStatus::Authorized) => {
+                match Order::transition_in(
+                    payment.order_id,
+                    OrderStatus::Paid,
+                    Some(format!("payment {} captured", payment.id)),
+                    &mut tx,
+                )
+                .await
+                {
+                    Ok(_) => (),
+                    Err(OrderError::IllegalTransition { from, .. }) => tracing::warn!(
+                        "Payment {} was captured for order {} that is {}",
+                        payment.id,
+                        payment.order_id,
+                        from.as_str()
+                    ),
+                    Err(e) => return Err(e.into()),
+                }
+                set_captured(payment.id, event.amount.unwrap_or(payment.amount), &mut tx).await?;
+            }
+            (WebhookEventKind::Voided, PaymentStatus::Authorized) => {
+                set_status(payment.id, PaymentStatus::Voided, None, &mut tx).await?;
+            }
+            (WebhookEventKind::Failed, PaymentStatus::Authorized) => {
+                set_status(payment.id, PaymentStatus::Failed, event.reason, &mut tx).await?;
+            }
+            (
+                WebhookEventKind::Refunded,
+                PaymentStatus::Captured
+                | PaymentStatus::PartiallyRefunded
+                | PaymentStatus::Refunded,
+            ) => {
+                let reference = event.refund_reference.ok_or_else(|| {
+                    ProviderError::InvalidPayload("missing refund_reference".to_string())
+                })?;
+                let amount = event
+                    .amount
+                    .ok_or_else(|| ProviderError::InvalidPayload("missing amount".to_string()))?;
+
+                record_refund(payment, &reference, amount, event.reason, &mut tx).await?;
+            }
+            // The capture may still be on its way, the provider retries.
+            (WebhookEventKind::Refunded, PaymentStatus::Authorized) => {
+                return Err(PaymentError::InvalidState {
+                    status: PaymentStatus::Authorized,
+                    action: "refund",
+                });
+            }
+            (WebhookEventKind::Refunded, status) => tracing::warn!(
+                "Refund {} reported for payment {} that is {}",
+                event.refund_reference.as_deref().unwrap_or_default(),
+                payment.id,
+                status.as_str()
+            ),
+            _ => (),
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+}
+
+async fn lock(id: i64, tx: &mut Transaction<'_, Postgres>) -> Result<Payment, PaymentError> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+			SELECT id, order_id, provider, reference, status as "status: PaymentStatus",
+				currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at
+			FROM payment
+			WHERE id = $1
+			FOR UPDATE;
+		"#,
+        id
+    )
+    .fetch_optional(tx)
+    .await?
+    .ok_or(PaymentError::NotFound(id))
+}
+
+async fn set_captured(
+    id: i64,
+    amount: Decimal,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Payment, PaymentError> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+			UPDATE payment SET
+				status = 'captured',
+				captured_amount = $1,
+				updated_at = NOW()
+			WHERE id = $2
+			RETURNING id, order_id, provider, reference, status as "status: PaymentStatus",
+				currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at;
+		"#,
+        amount,
+        id
+    )
+    .fetch_one(tx)
+    .await
+    .map_err(|e| e.into())
+}
+
+async fn set_status(
+    id: i64,
+    status: PaymentStatus,
+    failure_reason: Option<String>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Payment, PaymentError> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+			UPDATE payment SET
+				status = $1,
+				failure_reason = COALESCE($2, failure_reason),
+				updated_at = NOW()
+			WHERE id = $3
+			RETURNING id, order_id, provider, reference, status as "status: PaymentStatus",
+				currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at;
+		"#,
+        status as _,
+        failure_reason,
+        id
+    )
+    .fetch_one(tx)
+    .await
+    .map_err(|e| e.into())
+}
+
+async fn order_status(order_id: i64, pool: &PgPool) -> Result<OrderStatus, PaymentError> {
+    sqlx::query!(
+        r#"SELECT status as "status: OrderStatus" FROM customer_order WHERE id = $1;"#,
+        order_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|o| o.status)
+    .ok_or(PaymentError::Order(OrderError::NotFound(order_id)))
+}
+
+/// Completes a pending refund the provider made under `reference`. Returns
+/// the payment unchanged if the refund was already completed.
+async fn complete_refund(
+    payment: Payment,
+    refund_id: i64,
+    reference: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Payment, PaymentError> {
+    let completed = sqlx::query!(
+        r#"
+			UPDATE payment_refund SET
+				status = 'succeeded',
+				reference = $1
+			WHERE id = $2 AND status = 'pending'
+			RETURNING id, return_id, amount;
+		"#,
+        reference,
+        refund_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    match completed {
+        Some(refund) => apply_refund(payment, refund.id, refund.return_id, refund.amount, tx).await,
+        None => Ok(payment),
+    }
+}
+
+/// Records a refund the provider reported by webhook: completes the pending
+/// refund it belongs to, or records one made at the provider directly.
+/// Refunds that were already recorded are ignored.
+async fn record_refund(
+    payment: Payment,
+    reference: &str,
+    amount: Decimal,
+    reason: Option<String>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Payment, PaymentError> {
+    let recorded = sqlx::query!(
+        r#"SELECT id FROM payment_refund WHERE reference = $1;"#,
+        reference
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if recorded.is_some() {
+        return Ok(payment);
+    }
+
+    let pending = sqlx::query!(
+        r#"
+			SELECT id FROM payment_refund
+			WHERE payment_id = $1 AND status = 'pending' AND amount = $2
+			ORDER BY id ASC
+			LIMIT 1
+			FOR UPDATE;
+		"#,
+        payment.id,
+        amount
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some(pending) = pending {
+        return complete_refund(payment, pending.id, reference, tx).await;
+    }
+
+    let remaining = payment.captured_amount - payment.refunded_amount;
+    if amount <= Decimal::ZERO || amount > remaining {
+        return Err(PaymentError::InvalidAmount(format!(
+            "refund amount must be greater than 0 and at most {}",
+            remaining
+        )));
+    }
+
+    let refund_id = sqlx::query!(
+        r#"
+			INSERT INTO payment_refund (payment_id, reference, amount, reason)
+			VALUES ($1, $2, $3, $4)
+			RETURNING id;
+		"#,
+        payment.id,
+        reference,
+        amount,
+        reason
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    apply_refund(payment, refund_id, None, amount, tx).await
+}
+
+/// Adds a refund to the refunded amount of its payment and issues a credit
+/// note for it. A full refund also moves the order to refunded.
+async fn apply_refund(
+    payment: Payment,
+    refund_id: i64,
+    return_id: Option<i64>,
+    amount: Decimal,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Payment, PaymentError> {
+    let refunded_amount = payment.refunded_amount + amount;
+    let status = if refunded_amount >= payment.captured_amount {
+        PaymentStatus::Refunded
+    } else {
+        PaymentStatus::PartiallyRefunded
+    };
+
+    let payment = sqlx::query_as!(
+        Payment,
+        r#"
+			UPDATE payment SET
+				status = $1,
+				refunded_amount = $2,
+				updated_at = NOW()
+			WHERE id = $3
+			RETURNING id, order_id, provider, reference, status as "status: PaymentStatus",
+				currency, amount, captured_amount, refunded_amount, failure_reason, created_at, updated_at;
+		"#,
+        status as _,
+        refunded_amount,
+        payment.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    CreditNote::create_in(
+        payment.order_id,
+        return_id,
+        Some(payment.id),
+        Some(refund_id),
+        amount,
+        &mut *tx,
+    )
+    .await?;
+    if let Some(return_id) = return_id {
+        OrderReturn::settle_in(return_id, tx).await?;
+    }
+
+    if status == PaymentStatus::Refunded {
+        let order_status = sqlx::query!(
+            r#"SELECT status as "status: OrderStatus" FROM customer_order WHERE id = $1;"#,
+            payment.order_id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .status;
+
+        if order_status.can_transition_to(OrderStatus::Refunded) {
+            Order::transition_in(
+                payment.order_id,
+                OrderStatus::Refunded,
+                Some(format!("payment {} refunded", payment.id)),
+                tx,
+            )
+            .await?;
+        }
+    }
+
+    Ok(payment)
+}
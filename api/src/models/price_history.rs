@@ -0,0 +1,46 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::types::Decimal;
+use sqlx::PgPool;
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+/// A change of a product's price, recorded by the database whenever the
+/// catalog price, a per-currency price or the product's discount changes.
+#[derive(Serialize, ApiSchema)]
+pub struct PriceChange {
+    pub id: i64,
+    pub product_id: i64,
+    /// `None` for the catalog price in the base currency.
+    pub currency: Option<String>,
+    /// The price with the active discount applied, `None` once the price
+    /// was removed.
+    pub price: Option<Decimal>,
+    /// The discount active at the time, if any.
+    pub discount_percent: Option<Decimal>,
+    pub changed_at: NaiveDateTime,
+}
+
+impl PriceChange {
+    #[tracing::instrument(name = "PriceChange::find_by_product", skip_all)]
+    pub async fn find_by_product(
+        product_id: i64,
+        pool: &PgPool,
+    ) -> Result<Vec<PriceChange>, String> {
+        sqlx::query_as!(
+            PriceChange,
+            r#"
+				SELECT id, product_id, currency, price, discount_percent, changed_at
+				FROM product_price_history
+				WHERE product_id = $1
+				ORDER BY changed_at ASC, id ASC;
+			"#,
+            product_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use sqlx::{PgExecutor, PgPool};
+use validator::Validate;
+
+use super::currency::Currency;
+use super::{validate_decimal, MAX_I32_CONST, MIN_ORDER_QUANTITY};
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+/// Price of a product when buying at least `min_quantity` units, for the
+/// members of a customer group or, without one, for everyone.
+#[derive(Serialize, ApiSchema)]
+pub struct PriceTier {
+    pub id: i64,
+    pub product_id: i64,
+    pub customer_group_id: Option<i64>,
+    pub currency: String,
+    pub min_quantity: i32,
+    pub price: Decimal,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct PriceTierInsert {
+    customer_group_id: Option<i64>,
+
+    /// Defaults to the base currency.
+    #[validate(length(equal = 3, message = "field must be an ISO 4217 currency code"))]
+    currency: Option<String>,
+
+    #[validate(range(
+        min = "MIN_ORDER_QUANTITY",
+        max = "MAX_I32_CONST",
+        message = "field contains invalid value - min: 1, max: i32"
+    ))]
+    min_quantity: Option<i32>,
+
+    #[validate(
+        required(message = "this field is required"),
+        custom = "validate_decimal"
+    )]
+    price: Option<Decimal>,
+}
+
+impl PriceTier {
+    #[tracing::instrument(name = "PriceTier::find_by_product", skip_all)]
+    pub async fn find_by_product(product_id: i64, pool: &PgPool) -> Result<Vec<PriceTier>, String> {
+        sqlx::query_as!(
+            PriceTier,
+            r#"
+				SELECT id, product_id, customer_group_id, currency, min_quantity, price,
+					created_at, updated_at
+				FROM product_price_tier
+				WHERE product_id = $1
+				ORDER BY customer_group_id NULLS FIRST, currency, min_quantity;
+			"#,
+            product_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// The tier [`PriceTier::set`] would replace, if there is one.
+    #[tracing::instrument(name = "PriceTier::find_matching", skip_all)]
+    pub async fn find_matching<'e, E: PgExecutor<'e>>(
+        product_id: i64,
+        input: &PriceTierInsert,
+        executor: E,
+    ) -> Result<Option<i64>, String> {
+        sqlx::query_scalar!(
+            r#"
+				SELECT id FROM product_price_tier
+				WHERE product_id = $1
+					AND customer_group_id IS NOT DISTINCT FROM $2
+					AND currency = COALESCE($3, (SELECT code FROM currency WHERE is_base))
+					AND min_quantity = COALESCE($4, 1);
+			"#,
+            product_id,
+            input.customer_group_id,
+            input.currency.as_ref().map(|c| c.to_uppercase()),
+            input.min_quantity
+        )
+        .fetch_optional(executor)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    /// Adds a tier, or replaces the price of the same group, currency and
+    /// quantity.
+    #[tracing::instrument(name = "PriceTier::set", skip_all)]
+    pub async fn set<'e, E: PgExecutor<'e>>(
+        product_id: i64,
+        input: PriceTierInsert,
+        executor: E,
+    ) -> Result<PriceTier, String> {
+        sqlx::query_as!(
+            PriceTier,
+            r#"
+				INSERT INTO product_price_tier (product_id, customer_group_id, currency, min_quantity, price)
+				VALUES ($1, $2, COALESCE($3, (SELECT code FROM currency WHERE is_base)), COALESCE($4, 1), $5)
+				ON CONFLICT (product_id, customer_group_id, currency, min_quantity) DO UPDATE SET
+					price = EXCLUDED.price,
+					updated_at = NOW()
+				RETURNING id, product_id, customer_group_id, currency, min_quantity, price,
+					created_at, updated_at;
+			"#,
+            product_id,
+            input.customer_group_id,
+            input.currency.map(|c| c.to_uppercase()),
+            input.min_quantity,
+            input.price
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "PriceTier::delete", skip_all)]
+    pub async fn delete<'e, E: PgExecutor<'e>>(
+        product_id: i64,
+        id: i64,
+        executor: E,
+    ) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM product_price_tier WHERE id = $1 AND product_id = $2;
+			"#,
+            id,
+            product_id
+        )
+        .execute(executor)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+
+    /// The lowest tier price `quantity` units qualify for, among the tiers
+    /// open to everyone and those of the customer's group. Tiers in the base
+    /// currency count too, converted to `currency`.
+    #[tracing::instrument(name = "PriceTier::best_price", skip_all)]
+    pub async fn best_price<'e, E: PgExecutor<'e>>(
+        product_id: i64,
+        quantity: i32,
+        customer_group_id: Option<i64>,
+        currency: &Currency,
+        executor: E,
+    ) -> Result<Option<Decimal>, sqlx::Error> {
+        sqlx::query!(
+            r#"
+				SELECT MIN(
+					ROUND(CASE WHEN t.currency = $4 THEN t.price ELSE t.price * $5 END, $6)
+				) as price
+				FROM product_price_tier t
+				JOIN currency c ON c.code = t.currency
+				WHERE t.product_id = $1
+					AND t.min_quantity <= $2
+					AND (t.customer_group_id IS NULL OR t.customer_group_id = $3)
+					AND (t.currency = $4 OR c.is_base);
+			"#,
+            product_id,
+            quantity,
+            customer_group_id,
+            currency.code,
+            currency.exchange_rate,
+            currency.minor_units as i32
+        )
+        .fetch_one(executor)
+        .await
+        .map(|r| r.price)
+    }
+}
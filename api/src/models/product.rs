@@ -1,12 +1,27 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::types::{Decimal, Json};
-use sqlx::PgPool;
-use validator::Validate;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use validator::{Validate, ValidationError};
 
-use super::{category::CategoryDb, discount::Discount, product_inventory::ProductInventory};
+use super::{
+    category::CategoryDb, currency::Currency, deserialize_nullable, discount::Discount,
+    money::Money, product_inventory::ProductInventory, tax::TaxClass, validate_decimal,
+};
+use crate::openapi::ApiSchema;
+use crate::telemetry::{self, RecordRows, ROWS_AFFECTED};
 
-#[derive(Serialize)]
+/// Only published products are shown in the shop and can be ordered.
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "product_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ProductStatus {
+    Draft,
+    Published,
+    Archived,
+}
+
+#[derive(Serialize, ApiSchema)]
 pub struct Product {
     pub id: i64,
     name: String,
@@ -16,14 +31,31 @@ pub struct Product {
     category: Option<Json<CategoryDb>>,
     inventory_id: Option<i64>,
     inventory: Option<Json<ProductInventory>>,
-    price: Option<Decimal>,
+    /// In the price list's currency, converted from the base price unless
+    /// the product has its own price there.
+    price: Option<Json<Money>>,
+    /// What shoppers pay while the discount is active.
+    discounted_price: Option<Json<Money>>,
+    /// Lowest price paid in the 30 days before the active discount started.
+    lowest_price_30d: Option<Json<Money>>,
     discount_id: Option<i64>,
     discount: Option<Json<Discount>>,
+    weight: Option<Decimal>,
+    length: Option<Decimal>,
+    width: Option<Decimal>,
+    height: Option<Decimal>,
+    tax_class: String,
+    status: ProductStatus,
+    /// When the scheduler publishes the product.
+    publish_at: Option<NaiveDateTime>,
+    /// When the scheduler archives the product.
+    unpublish_at: Option<NaiveDateTime>,
     created_at: NaiveDateTime,
     updated_at: NaiveDateTime,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
+#[validate(schema(function = "validate_insert_schedule"))]
 pub struct ProductInsert {
     #[validate(
         required(message = "this field is required"),
@@ -43,10 +75,32 @@ pub struct ProductInsert {
     pub inventory_id: Option<i64>,
 
     discount_id: Option<i64>,
+
+    /// In the base currency.
+    #[validate(custom = "validate_decimal")]
     price: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    weight: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    length: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    width: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    height: Option<Decimal>,
+
+    tax_class: Option<TaxClass>,
+
+    status: Option<ProductStatus>,
+    publish_at: Option<NaiveDateTime>,
+    unpublish_at: Option<NaiveDateTime>,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
+#[validate(schema(function = "validate_update_schedule"))]
 pub struct ProductUpdate {
     #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
     name: Option<String>,
@@ -60,80 +114,180 @@ pub struct ProductUpdate {
     category_id: Option<i64>,
     inventory_id: Option<i64>,
     discount_id: Option<i64>,
+
+    /// In the base currency.
+    #[validate(custom = "validate_decimal")]
     price: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    weight: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    length: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    width: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    height: Option<Decimal>,
+
+    tax_class: Option<TaxClass>,
+
+    status: Option<ProductStatus>,
+
+    /// `null` clears the schedule.
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    publish_at: Option<Option<NaiveDateTime>>,
+
+    /// `null` clears the schedule.
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    unpublish_at: Option<Option<NaiveDateTime>>,
 }
 
 impl Product {
-    pub async fn find_all(pool: &PgPool) -> Result<Vec<Product>, String> {
+    /// Every status, shoppers get [`StorefrontProduct`](super::storefront::StorefrontProduct) instead.
+    #[tracing::instrument(name = "Product::find_all", skip_all)]
+    pub async fn find_all(currency: &Currency, pool: &PgPool) -> Result<Vec<Product>, String> {
         sqlx::query_as!(
             Product,
             r#"
-				SELECT p.*, 
+				SELECT p.id, p.name, p.description, p.sku, p.category_id, p.inventory_id, p.discount_id,
+					p.weight, p.length, p.width, p.height, p.tax_class,
+					p.status as "status: ProductStatus", p.publish_at, p.unpublish_at,
+					p.created_at, p.updated_at,
+					money_json(COALESCE(pp.price, p.price * cur.exchange_rate), cur.code) as "price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate) * (1 - d.discount_percent / 100),
+						cur.code
+					) END as "discounted_price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						CASE WHEN pp.price IS NOT NULL
+							THEN product_lowest_price(p.id, cur.code, 30)
+							ELSE product_lowest_price(p.id, NULL, 30) * cur.exchange_rate
+						END,
+						cur.code
+					) END as "lowest_price_30d: Json<Money>",
 					row_to_json(i.*) as "inventory: Json<ProductInventory>",
 					row_to_json(d.*) as "discount: Json<Discount>",
 					row_to_json(c.*) as "category: Json<CategoryDb>"
 				FROM product p
+				JOIN currency cur ON cur.code = $1
+				LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = cur.code
 				LEFT JOIN product_inventory i ON i.id = p.inventory_id
-				LEFT JOIN discount d ON d.id = p.discount_id
-				LEFT JOIN category c on c.id = p.category_id
+				LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+				LEFT JOIN category c on c.id = p.category_id AND c.deleted_at IS NULL
+				WHERE p.deleted_at IS NULL
 				ORDER BY id ASC;
-			"#
+			"#,
+            currency.code
         )
         .fetch_all(pool)
         .await
+        .record_rows()
         .map_err(|e| e.to_string())
     }
 
-    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<Product, String> {
+    #[tracing::instrument(name = "Product::find_by_id", skip_all)]
+    pub async fn find_by_id<'e, E: PgExecutor<'e>>(
+        id: i64,
+        currency: &Currency,
+        executor: E,
+    ) -> Result<Product, String> {
         sqlx::query_as!(
             Product,
             r#"
-				SELECT p.*, 
+				SELECT p.id, p.name, p.description, p.sku, p.category_id, p.inventory_id, p.discount_id,
+					p.weight, p.length, p.width, p.height, p.tax_class,
+					p.status as "status: ProductStatus", p.publish_at, p.unpublish_at,
+					p.created_at, p.updated_at,
+					money_json(COALESCE(pp.price, p.price * cur.exchange_rate), cur.code) as "price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate) * (1 - d.discount_percent / 100),
+						cur.code
+					) END as "discounted_price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						CASE WHEN pp.price IS NOT NULL
+							THEN product_lowest_price(p.id, cur.code, 30)
+							ELSE product_lowest_price(p.id, NULL, 30) * cur.exchange_rate
+						END,
+						cur.code
+					) END as "lowest_price_30d: Json<Money>",
 					row_to_json(i.*) as "inventory: Json<ProductInventory>",
 					row_to_json(d.*) as "discount: Json<Discount>",
 					row_to_json(c.*) as "category: Json<CategoryDb>"
 				FROM product p
+				JOIN currency cur ON cur.code = $1
+				LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = cur.code
 				LEFT JOIN product_inventory i ON i.id = p.inventory_id
-				LEFT JOIN discount d ON d.id = p.discount_id
-				LEFT JOIN category c on c.id = p.category_id
-				WHERE p.id = $1;
+				LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+				LEFT JOIN category c on c.id = p.category_id AND c.deleted_at IS NULL
+				WHERE p.id = $2 AND p.deleted_at IS NULL;
 			"#,
+            currency.code,
             id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map_err(|e| e.to_string())
     }
 
+    #[tracing::instrument(name = "Product::find_by_category", skip_all)]
     pub async fn find_by_category(
         category_id: Option<i64>,
+        currency: &Currency,
         pool: &PgPool,
     ) -> Result<Vec<Product>, String> {
         sqlx::query_as!(
             Product,
             r#"
-				SELECT p.*, 
+				SELECT p.id, p.name, p.description, p.sku, p.category_id, p.inventory_id, p.discount_id,
+					p.weight, p.length, p.width, p.height, p.tax_class,
+					p.status as "status: ProductStatus", p.publish_at, p.unpublish_at,
+					p.created_at, p.updated_at,
+					money_json(COALESCE(pp.price, p.price * cur.exchange_rate), cur.code) as "price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate) * (1 - d.discount_percent / 100),
+						cur.code
+					) END as "discounted_price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						CASE WHEN pp.price IS NOT NULL
+							THEN product_lowest_price(p.id, cur.code, 30)
+							ELSE product_lowest_price(p.id, NULL, 30) * cur.exchange_rate
+						END,
+						cur.code
+					) END as "lowest_price_30d: Json<Money>",
 					row_to_json(i.*) as "inventory: Json<ProductInventory>",
 					row_to_json(d.*) as "discount: Json<Discount>",
 					row_to_json(c.*) as "category: Json<CategoryDb>"
 				FROM product p
+				JOIN currency cur ON cur.code = $1
+				LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = cur.code
 				LEFT JOIN product_inventory i ON i.id = p.inventory_id
-				LEFT JOIN discount d ON d.id = p.discount_id
-				LEFT JOIN category c on c.id = p.category_id
-				WHERE p.category_id = $1;;
+				LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+				LEFT JOIN category c on c.id = p.category_id AND c.deleted_at IS NULL
+				WHERE p.category_id = $2 AND p.deleted_at IS NULL;
 			"#,
+            currency.code,
             category_id
         )
         .fetch_all(pool)
         .await
+        .record_rows()
         .map_err(|e| e.to_string())
     }
 
-    pub async fn create(input: ProductInsert, pool: &PgPool) -> Result<Product, String> {
+    #[tracing::instrument(name = "Product::create", skip_all)]
+    pub async fn create(
+        input: ProductInsert,
+        currency: &Currency,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Product, String> {
         let id = match sqlx::query!(
             r#"
-				INSERT INTO product (name, description, sku, category_id, price, discount_id, inventory_id)
-				VALUES ($1, $2, $3, $4, $5, $6, $7)
+				INSERT INTO product (name, description, sku, category_id, price, discount_id, inventory_id,
+					weight, length, width, height, tax_class, status, publish_at, unpublish_at)
+				VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, COALESCE($12, 'standard'),
+					COALESCE($13, 'draft'::product_status), $14, $15)
 				RETURNING id;
 			"#,
             input.name,
@@ -142,9 +296,17 @@ impl Product {
             input.category_id,
             input.price,
             input.discount_id,
-            input.inventory_id
+            input.inventory_id,
+            input.weight,
+            input.length,
+            input.width,
+            input.height,
+            input.tax_class.map(|c| c.as_str()),
+            input.status as _,
+            input.publish_at,
+            input.unpublish_at
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await
         .map(|r| r.id)
         .map_err(|e| e.to_string())
@@ -153,10 +315,17 @@ impl Product {
             Err(e) => return Err(e),
         };
 
-        Product::find_by_id(id, pool).await
+        Product::find_by_id(id, currency, &mut *tx).await
     }
 
-    pub async fn update(id: i64, input: ProductUpdate, pool: &PgPool) -> Result<Product, String> {
+    /// Archiving a product drops its pending publication.
+    #[tracing::instrument(name = "Product::update", skip_all)]
+    pub async fn update(
+        id: i64,
+        input: ProductUpdate,
+        currency: &Currency,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Product, String> {
         let id = match sqlx::query!(
             r#"
 				UPDATE product SET 
@@ -167,8 +336,20 @@ impl Product {
 					price = COALESCE($5, price),
 					discount_id = COALESCE($6, discount_id),
 					inventory_id = COALESCE($7, inventory_id),
+					weight = COALESCE($8, weight),
+					length = COALESCE($9, length),
+					width = COALESCE($10, width),
+					height = COALESCE($11, height),
+					tax_class = COALESCE($12, tax_class),
+					status = COALESCE($13, status),
+					publish_at = CASE
+						WHEN $14 THEN $15
+						WHEN $13 = 'archived' THEN NULL
+						ELSE publish_at
+					END,
+					unpublish_at = CASE WHEN $16 THEN $17 ELSE unpublish_at END,
 					updated_at = NOW()
-				WHERE id = $8
+				WHERE id = $18 AND deleted_at IS NULL
 				RETURNING id;
 			"#,
             input.name,
@@ -178,9 +359,19 @@ impl Product {
             input.price,
             input.discount_id,
             input.inventory_id,
+            input.weight,
+            input.length,
+            input.width,
+            input.height,
+            input.tax_class.map(|c| c.as_str()),
+            input.status as _,
+            input.publish_at.is_some(),
+            input.publish_at.flatten(),
+            input.unpublish_at.is_some(),
+            input.unpublish_at.flatten(),
             id
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await
         .map(|r| r.id)
         .map_err(|e| e.to_string())
@@ -189,19 +380,130 @@ impl Product {
             Err(e) => return Err(e),
         };
 
-        Product::find_by_id(id, pool).await
+        Product::find_by_id(id, currency, &mut *tx).await
+    }
+
+    /// Applies due publication schedules: publishes products whose
+    /// `publish_at` has passed, unless they were archived in the meantime,
+    /// then archives those past `unpublish_at`.
+    /// Returns how many products were published and archived.
+    #[tracing::instrument(name = "Product::apply_schedules", skip_all)]
+    pub async fn apply_schedules(pool: &PgPool) -> Result<(u64, u64), String> {
+        let published = sqlx::query!(
+            r#"
+				UPDATE product SET status = 'published', publish_at = NULL, updated_at = NOW()
+				WHERE publish_at <= NOW() AND status <> 'archived' AND deleted_at IS NULL;
+			"#
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        let archived = sqlx::query!(
+            r#"
+				UPDATE product SET status = 'archived', unpublish_at = NULL, updated_at = NOW()
+				WHERE unpublish_at <= NOW() AND deleted_at IS NULL;
+			"#
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        telemetry::record_rows(ROWS_AFFECTED, published + archived);
+
+        Ok((published, archived))
     }
 
-    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+    /// Moves the product to the trash, it's purged after the retention
+    /// period.
+    #[tracing::instrument(name = "Product::delete", skip_all)]
+    pub async fn delete<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
         sqlx::query!(
             r#"
-				DELETE FROM product WHERE id = $1;
+				UPDATE product SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL;
 			"#,
             id
         )
-        .execute(pool)
+        .execute(executor)
         .await
+        .record_rows()
         .map(|r| r.rows_affected())
         .map_err(|e| e.to_string())
     }
+
+    #[tracing::instrument(name = "Product::restore", skip_all)]
+    pub async fn restore<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				UPDATE product SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL;
+			"#,
+            id
+        )
+        .execute(executor)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
+
+fn validate_insert_schedule(input: &ProductInsert) -> Result<(), ValidationError> {
+    validate_schedule(input.publish_at, input.unpublish_at)
+}
+
+/// Only compares the dates given, the database rejects an update that
+/// schedules the archiving before an unchanged publication date.
+fn validate_update_schedule(input: &ProductUpdate) -> Result<(), ValidationError> {
+    validate_schedule(input.publish_at.flatten(), input.unpublish_at.flatten())
+}
+
+fn validate_schedule(
+    publish_at: Option<NaiveDateTime>,
+    unpublish_at: Option<NaiveDateTime>,
+) -> Result<(), ValidationError> {
+    match (publish_at, unpublish_at) {
+        (Some(publish_at), Some(unpublish_at)) if publish_at >= unpublish_at => {
+            let mut error = ValidationError::new("unpublish_at");
+            error.message = Some("unpublish_at must be later than publish_at".into());
+            Err(error)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(body: &str) -> ProductUpdate {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn tells_a_cleared_schedule_from_an_unchanged_one() {
+        let cleared = update(r#"{ "publish_at": null }"#);
+        assert_eq!(cleared.publish_at, Some(None));
+        assert_eq!(cleared.unpublish_at, None);
+    }
+
+    #[test]
+    fn rejects_archiving_before_publishing() {
+        let insert: ProductInsert = serde_json::from_str(
+            r#"{ "name": "Scheduled", "publish_at": "2026-11-02T00:00:00", "unpublish_at": "2026-11-01T00:00:00" }"#,
+        )
+        .unwrap();
+        assert!(insert.validate().is_err());
+
+        let same_time = update(
+            r#"{ "publish_at": "2026-11-01T00:00:00", "unpublish_at": "2026-11-01T00:00:00" }"#,
+        );
+        assert!(same_time.validate().is_err());
+
+        let valid = update(
+            r#"{ "publish_at": "2026-11-01T00:00:00", "unpublish_at": "2026-11-02T00:00:00" }"#,
+        );
+        assert!(valid.validate().is_ok());
+    }
 }
@@ -1,11 +1,13 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use validator::Validate;
 
 use crate::models::{MAX_I32_CONST, MIN_I32_CONST};
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ApiSchema)]
 pub struct ProductInventory {
     pub id: i64,
     pub quantity: i32,
@@ -13,7 +15,7 @@ pub struct ProductInventory {
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
 pub struct ProductInventoryInsert {
     #[validate(
         required(message = "this field is required"),
@@ -34,7 +36,7 @@ impl ProductInventoryInsert {
     }
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ApiSchema)]
 pub struct ProductInventoryUpdate {
     #[validate(range(
         min = "MIN_I32_CONST",
@@ -45,6 +47,7 @@ pub struct ProductInventoryUpdate {
 }
 
 impl ProductInventory {
+    #[tracing::instrument(name = "ProductInventory::find_all", skip_all)]
     pub async fn find_all(pool: &PgPool) -> Result<Vec<ProductInventory>, String> {
         sqlx::query_as!(
             ProductInventory,
@@ -54,9 +57,11 @@ impl ProductInventory {
         )
         .fetch_all(pool)
         .await
+        .record_rows()
         .map_err(|e| e.to_string())
     }
 
+    #[tracing::instrument(name = "ProductInventory::find_by_id", skip_all)]
     pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<ProductInventory, String> {
         sqlx::query_as!(
             ProductInventory,
@@ -70,9 +75,10 @@ impl ProductInventory {
         .map_err(|e| e.to_string())
     }
 
-    pub async fn create(
+    #[tracing::instrument(name = "ProductInventory::create", skip_all)]
+    pub async fn create<'e, E: PgExecutor<'e>>(
         input: ProductInventoryInsert,
-        pool: &PgPool,
+        executor: E,
     ) -> Result<ProductInventory, String> {
         sqlx::query_as!(
             ProductInventory,
@@ -83,15 +89,16 @@ impl ProductInventory {
 			"#,
             input.quantity
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map_err(|e| e.to_string())
     }
 
-    pub async fn update(
+    #[tracing::instrument(name = "ProductInventory::update", skip_all)]
+    pub async fn update<'e, E: PgExecutor<'e>>(
         id: i64,
         input: ProductInventoryUpdate,
-        pool: &PgPool,
+        executor: E,
     ) -> Result<ProductInventory, String> {
         sqlx::query_as!(
             ProductInventory,
@@ -105,20 +112,22 @@ impl ProductInventory {
             input.quantity,
             id
         )
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await
         .map_err(|e| e.to_string())
     }
 
-    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+    #[tracing::instrument(name = "ProductInventory::delete", skip_all)]
+    pub async fn delete<'e, E: PgExecutor<'e>>(id: i64, executor: E) -> Result<u64, String> {
         sqlx::query!(
             r#"
 				DELETE FROM product_inventory WHERE id = $1;
 			"#,
             id
         )
-        .execute(pool)
+        .execute(executor)
         .await
+        .record_rows()
         .map(|r| r.rows_affected())
         .map_err(|e| e.to_string())
     }
@@ -0,0 +1,682 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::{Decimal, Json};
+use sqlx::{PgExecutor, PgPool};
+use validator::{Validate, ValidationError};
+
+use super::currency::Currency;
+use super::order::{OrderError, OrderItemInsert};
+use super::validate_decimal;
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[sqlx(type_name = "shipping_method_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ShippingMethodKind {
+    FlatRate,
+    WeightTable,
+    FreeOverThreshold,
+    LocalPickup,
+}
+
+/// One bracket of a weight table: carts up to `max_weight` kg cost `price`.
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
+pub struct WeightRate {
+    pub max_weight: Decimal,
+    pub price: Decimal,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct ShippingMethod {
+    pub id: i64,
+    pub name: String,
+    pub kind: ShippingMethodKind,
+    pub zone_id: Option<i64>,
+    pub price: Decimal,
+    pub threshold: Option<Decimal>,
+    pub weight_rates: Json<Vec<WeightRate>>,
+    /// Prices are set in the base currency.
+    pub currency: String,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, ApiSchema)]
+pub struct ShippingZoneRule {
+    pub id: i64,
+    pub zone_id: i64,
+    pub country: String,
+    pub postcode_prefix: Option<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct ShippingZone {
+    pub id: i64,
+    pub name: String,
+    pub rules: Json<Vec<ShippingZoneRule>>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct ShippingQuote {
+    pub method_id: i64,
+    pub name: String,
+    pub kind: ShippingMethodKind,
+    pub cost: Decimal,
+    pub currency: String,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ShippingZoneInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(max = 128, message = "field contains too many characters - max: 128")
+    )]
+    name: Option<String>,
+
+    #[validate]
+    #[serde(default)]
+    rules: Vec<ShippingZoneRuleInsert>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ShippingZoneUpdate {
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    name: Option<String>,
+
+    /// Replaces all rules of the zone when present.
+    #[validate]
+    rules: Option<Vec<ShippingZoneRuleInsert>>,
+}
+
+#[derive(Serialize, Deserialize, Validate, ApiSchema)]
+pub struct ShippingZoneRuleInsert {
+    #[validate(length(
+        equal = 2,
+        message = "field must be an ISO 3166-1 alpha-2 country code"
+    ))]
+    country: String,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    postcode_prefix: Option<String>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+#[validate(schema(function = "validate_method_kind", skip_on_field_errors = false))]
+pub struct ShippingMethodInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(max = 128, message = "field contains too many characters - max: 128")
+    )]
+    name: Option<String>,
+
+    #[validate(required(message = "this field is required"))]
+    kind: Option<ShippingMethodKind>,
+
+    zone_id: Option<i64>,
+
+    #[validate(custom = "validate_decimal")]
+    price: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    threshold: Option<Decimal>,
+
+    #[validate(custom = "validate_weight_rates")]
+    #[serde(default)]
+    weight_rates: Vec<WeightRate>,
+
+    #[serde(default = "default_active")]
+    active: bool,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ShippingMethodUpdate {
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    name: Option<String>,
+
+    zone_id: Option<i64>,
+
+    #[validate(custom = "validate_decimal")]
+    price: Option<Decimal>,
+
+    #[validate(custom = "validate_decimal")]
+    threshold: Option<Decimal>,
+
+    #[validate(custom = "validate_weight_rates")]
+    weight_rates: Option<Vec<WeightRate>>,
+
+    active: Option<bool>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ShippingQuoteRequest {
+    #[validate(length(
+        equal = 2,
+        message = "field must be an ISO 3166-1 alpha-2 country code"
+    ))]
+    pub country: String,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    pub postcode: Option<String>,
+
+    #[validate(length(min = 1, message = "cart must contain at least one item"))]
+    #[validate]
+    pub items: Vec<OrderItemInsert>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+fn validate_weight_rates(rates: &[WeightRate]) -> Result<(), ValidationError> {
+    for rate in rates {
+        validate_decimal(&rate.max_weight)?;
+        validate_decimal(&rate.price)?;
+    }
+
+    Ok(())
+}
+
+fn validate_method_kind(input: &ShippingMethodInsert) -> Result<(), ValidationError> {
+    let mut error = match input.kind {
+        Some(ShippingMethodKind::WeightTable) if input.weight_rates.is_empty() => {
+            ValidationError::new("weight_rates")
+        }
+        Some(ShippingMethodKind::FreeOverThreshold) if input.threshold.is_none() => {
+            ValidationError::new("threshold")
+        }
+        _ => return Ok(()),
+    };
+
+    error.message = Some(
+        format!(
+            "{} is required for this kind of shipping method",
+            error.code
+        )
+        .into(),
+    );
+    Err(error)
+}
+
+/// Postcodes are compared without spaces and case, so "sw1a 1aa" matches a
+/// rule for "SW1A".
+pub fn normalize_postcode(postcode: &str) -> String {
+    postcode
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_uppercase())
+        .collect()
+}
+
+impl ShippingMethod {
+    /// Cost of shipping a cart with the given subtotal and weight (kg), or
+    /// `None` if the method can't ship it.
+    pub fn cost(&self, subtotal: Decimal, weight: Decimal) -> Option<Decimal> {
+        match self.kind {
+            ShippingMethodKind::FlatRate => Some(self.price),
+            ShippingMethodKind::WeightTable => {
+                let mut rates = self.weight_rates.0.clone();
+                rates.sort_by_key(|r| r.max_weight);
+
+                rates
+                    .iter()
+                    .find(|r| weight <= r.max_weight)
+                    .map(|r| r.price)
+            }
+            ShippingMethodKind::FreeOverThreshold => match self.threshold {
+                Some(threshold) if subtotal >= threshold => Some(Decimal::ZERO),
+                _ => Some(self.price),
+            },
+            ShippingMethodKind::LocalPickup => Some(Decimal::ZERO),
+        }
+    }
+
+    #[tracing::instrument(name = "ShippingMethod::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<ShippingMethod>, String> {
+        sqlx::query_as!(
+            ShippingMethod,
+            r#"
+				SELECT id, name, kind as "kind: ShippingMethodKind", zone_id, price, threshold,
+					weight_rates as "weight_rates: Json<Vec<WeightRate>>", active, created_at, updated_at,
+					(SELECT code FROM currency WHERE is_base) as "currency!"
+				FROM shipping_method
+				ORDER BY id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ShippingMethod::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<ShippingMethod, String> {
+        sqlx::query_as!(
+            ShippingMethod,
+            r#"
+				SELECT id, name, kind as "kind: ShippingMethodKind", zone_id, price, threshold,
+					weight_rates as "weight_rates: Json<Vec<WeightRate>>", active, created_at, updated_at,
+					(SELECT code FROM currency WHERE is_base) as "currency!"
+				FROM shipping_method
+				WHERE id = $1;
+			"#,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Active methods whose zone covers the destination. Methods without a
+    /// zone ship everywhere.
+    #[tracing::instrument(name = "ShippingMethod::find_available", skip_all)]
+    pub async fn find_available<'e, E: PgExecutor<'e>>(
+        country: &str,
+        postcode: Option<&str>,
+        executor: E,
+    ) -> Result<Vec<ShippingMethod>, sqlx::Error> {
+        sqlx::query_as!(
+            ShippingMethod,
+            r#"
+				SELECT m.id, m.name, m.kind as "kind: ShippingMethodKind", m.zone_id, m.price, m.threshold,
+					m.weight_rates as "weight_rates: Json<Vec<WeightRate>>", m.active, m.created_at, m.updated_at,
+					(SELECT code FROM currency WHERE is_base) as "currency!"
+				FROM shipping_method m
+				WHERE m.active AND (
+					m.zone_id IS NULL OR EXISTS (
+						SELECT 1 FROM shipping_zone_rule r
+						WHERE r.zone_id = m.zone_id
+							AND r.country = $1
+							AND (r.postcode_prefix IS NULL OR starts_with($2, r.postcode_prefix))
+					)
+				)
+				ORDER BY m.id ASC;
+			"#,
+            country.to_uppercase(),
+            postcode.map(normalize_postcode)
+        )
+        .fetch_all(executor)
+        .await
+        .record_rows()
+    }
+
+    #[tracing::instrument(name = "ShippingMethod::create", skip_all)]
+    pub async fn create(
+        input: ShippingMethodInsert,
+        pool: &PgPool,
+    ) -> Result<ShippingMethod, String> {
+        sqlx::query_as!(
+            ShippingMethod,
+            r#"
+				INSERT INTO shipping_method (name, kind, zone_id, price, threshold, weight_rates, active)
+				VALUES ($1, $2, $3, $4, $5, $6, $7)
+				RETURNING id, name, kind as "kind: ShippingMethodKind", zone_id, price, threshold,
+					weight_rates as "weight_rates: Json<Vec<WeightRate>>", active, created_at, updated_at,
+					(SELECT code FROM currency WHERE is_base) as "currency!";
+			"#,
+            input.name,
+            input.kind as _,
+            input.zone_id,
+            input.price.unwrap_or(Decimal::ZERO),
+            input.threshold,
+            Json(input.weight_rates) as _,
+            input.active
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ShippingMethod::update", skip_all)]
+    pub async fn update(
+        id: i64,
+        input: ShippingMethodUpdate,
+        pool: &PgPool,
+    ) -> Result<ShippingMethod, String> {
+        sqlx::query_as!(
+            ShippingMethod,
+            r#"
+				UPDATE shipping_method SET
+					name = COALESCE($1, name),
+					zone_id = COALESCE($2, zone_id),
+					price = COALESCE($3, price),
+					threshold = COALESCE($4, threshold),
+					weight_rates = COALESCE($5, weight_rates),
+					active = COALESCE($6, active),
+					updated_at = NOW()
+				WHERE id = $7
+				RETURNING id, name, kind as "kind: ShippingMethodKind", zone_id, price, threshold,
+					weight_rates as "weight_rates: Json<Vec<WeightRate>>", active, created_at, updated_at,
+					(SELECT code FROM currency WHERE is_base) as "currency!";
+			"#,
+            input.name,
+            input.zone_id,
+            input.price,
+            input.threshold,
+            input.weight_rates.map(Json) as _,
+            input.active,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ShippingMethod::delete", skip_all)]
+    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM shipping_method WHERE id = $1;
+			"#,
+            id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Prices every method that can ship the cart to the destination.
+    #[tracing::instrument(name = "ShippingMethod::quote", skip_all)]
+    pub async fn quote(
+        input: ShippingQuoteRequest,
+        currency: &Currency,
+        pool: &PgPool,
+    ) -> Result<Vec<ShippingQuote>, OrderError> {
+        let (subtotal, weight) = cart_totals(&input.items, pool).await?;
+
+        let methods =
+            ShippingMethod::find_available(&input.country, input.postcode.as_deref(), pool).await?;
+
+        Ok(methods
+            .into_iter()
+            .filter_map(|m| {
+                m.cost(subtotal, weight).map(|cost| ShippingQuote {
+                    method_id: m.id,
+                    name: m.name,
+                    kind: m.kind,
+                    cost: currency.convert(cost),
+                    currency: currency.code.clone(),
+                })
+            })
+            .collect())
+    }
+}
+
+/// Subtotal (after active discounts) and weight (kg) of a cart. Products
+/// without a weight count as weightless, products that can't be ordered
+/// fail the quote.
+pub async fn cart_totals<'e, E: PgExecutor<'e>>(
+    items: &[OrderItemInsert],
+    executor: E,
+) -> Result<(Decimal, Decimal), OrderError> {
+    let product_ids: Vec<i64> = items.iter().map(|i| i.product_id).collect();
+    let quantities: Vec<i32> = items.iter().map(|i| i.quantity).collect();
+
+    let totals = sqlx::query!(
+        r#"
+			SELECT
+				COALESCE(SUM(
+					c.quantity * ROUND(
+						p.price * (1 - COALESCE(CASE WHEN d.active THEN d.discount_percent END, 0) / 100),
+						2
+					)
+				), 0) as "subtotal!",
+				COALESCE(SUM(c.quantity * COALESCE(p.weight, 0)), 0) as "weight!",
+				MIN(c.product_id) FILTER (WHERE p.id IS NULL) as unknown
+			FROM UNNEST($1::bigint[], $2::integer[]) AS c(product_id, quantity)
+			LEFT JOIN product p ON p.id = c.product_id
+				AND p.deleted_at IS NULL
+				AND p.status = 'published'
+			LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL;
+		"#,
+        &product_ids,
+        &quantities
+    )
+    .fetch_one(executor)
+    .await?;
+
+    match totals.unknown {
+        Some(id) => Err(OrderError::ProductNotFound(id)),
+        None => Ok((totals.subtotal, totals.weight)),
+    }
+}
+
+impl ShippingZone {
+    #[tracing::instrument(name = "ShippingZone::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<ShippingZone>, String> {
+        sqlx::query_as!(
+            ShippingZone,
+            r#"
+				SELECT z.id, z.name,
+					COALESCE(
+						json_agg(r.* ORDER BY r.id) FILTER (WHERE r.id IS NOT NULL),
+						'[]'
+					) as "rules!: Json<Vec<ShippingZoneRule>>"
+				FROM shipping_zone z
+				LEFT JOIN shipping_zone_rule r ON r.zone_id = z.id
+				GROUP BY z.id
+				ORDER BY z.id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ShippingZone::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<ShippingZone, String> {
+        sqlx::query_as!(
+            ShippingZone,
+            r#"
+				SELECT z.id, z.name,
+					COALESCE(
+						json_agg(r.* ORDER BY r.id) FILTER (WHERE r.id IS NOT NULL),
+						'[]'
+					) as "rules!: Json<Vec<ShippingZoneRule>>"
+				FROM shipping_zone z
+				LEFT JOIN shipping_zone_rule r ON r.zone_id = z.id
+				WHERE z.id = $1
+				GROUP BY z.id;
+			"#,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ShippingZone::create", skip_all)]
+    pub async fn create(input: ShippingZoneInsert, pool: &PgPool) -> Result<ShippingZone, String> {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+        let id = sqlx::query!(
+            r#"
+				INSERT INTO shipping_zone (name) VALUES ($1)
+				RETURNING id;
+			"#,
+            input.name
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .id;
+
+        insert_rules(id, input.rules, &mut tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        ShippingZone::find_by_id(id, pool).await
+    }
+
+    #[tracing::instrument(name = "ShippingZone::update", skip_all)]
+    pub async fn update(
+        id: i64,
+        input: ShippingZoneUpdate,
+        pool: &PgPool,
+    ) -> Result<ShippingZone, String> {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+        let id = sqlx::query!(
+            r#"
+				UPDATE shipping_zone SET
+					name = COALESCE($1, name)
+				WHERE id = $2
+				RETURNING id;
+			"#,
+            input.name,
+            id
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .id;
+
+        if let Some(rules) = input.rules {
+            sqlx::query!(r#"DELETE FROM shipping_zone_rule WHERE zone_id = $1;"#, id)
+                .execute(&mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            insert_rules(id, rules, &mut tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        ShippingZone::find_by_id(id, pool).await
+    }
+
+    #[tracing::instrument(name = "ShippingZone::delete", skip_all)]
+    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM shipping_zone WHERE id = $1;
+			"#,
+            id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
+
+async fn insert_rules(
+    zone_id: i64,
+    rules: Vec<ShippingZoneRuleInsert>,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), sqlx::Error> {
+    for rule in rules {
+        sqlx::query!(
+            r#"
+				INSERT INTO shipping_zone_rule (zone_id, country, postcode_prefix)
+				VALUES ($1, $2, $3);
+			"#,
+            zone_id,
+            rule.country.to_uppercase(),
+            rule.postcode_prefix.as_deref().map(normalize_postcode)
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShippingMethod, ShippingMethodKind, WeightRate};
+    use chrono::NaiveDateTime;
+    use sqlx::types::{Decimal, Json};
+
+    fn method(kind: ShippingMethodKind) -> ShippingMethod {
+        ShippingMethod {
+            id: 1,
+            name: "Post".to_string(),
+            kind,
+            zone_id: None,
+            price: Decimal::new(490, 2),
+            threshold: Some(Decimal::from(50)),
+            weight_rates: Json(vec![
+                WeightRate {
+                    max_weight: Decimal::from(10),
+                    price: Decimal::new(990, 2),
+                },
+                WeightRate {
+                    max_weight: Decimal::from(2),
+                    price: Decimal::new(390, 2),
+                },
+            ]),
+            currency: "EUR".to_string(),
+            active: true,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+        }
+    }
+
+    #[test]
+    fn charges_a_flat_rate() {
+        let method = method(ShippingMethodKind::FlatRate);
+
+        assert_eq!(
+            method.cost(Decimal::from(500), Decimal::from(100)),
+            Some(Decimal::new(490, 2))
+        );
+    }
+
+    #[test]
+    fn picks_the_smallest_weight_bracket() {
+        let method = method(ShippingMethodKind::WeightTable);
+
+        assert_eq!(
+            method.cost(Decimal::ZERO, Decimal::ZERO),
+            Some(Decimal::new(390, 2))
+        );
+        assert_eq!(
+            method.cost(Decimal::ZERO, Decimal::from(2)),
+            Some(Decimal::new(390, 2))
+        );
+        assert_eq!(
+            method.cost(Decimal::ZERO, Decimal::new(25, 1)),
+            Some(Decimal::new(990, 2))
+        );
+        assert_eq!(method.cost(Decimal::ZERO, Decimal::new(101, 1)), None);
+    }
+
+    #[test]
+    fn ships_for_free_from_the_threshold() {
+        let mut method = method(ShippingMethodKind::FreeOverThreshold);
+
+        assert_eq!(
+            method.cost(Decimal::new(4999, 2), Decimal::ZERO),
+            Some(Decimal::new(490, 2))
+        );
+        assert_eq!(
+            method.cost(Decimal::from(50), Decimal::ZERO),
+            Some(Decimal::ZERO)
+        );
+
+        method.threshold = None;
+        assert_eq!(
+            method.cost(Decimal::from(500), Decimal::ZERO),
+            Some(Decimal::new(490, 2))
+        );
+    }
+
+    #[test]
+    fn picks_up_for_free() {
+        let method = method(ShippingMethodKind::LocalPickup);
+
+        assert_eq!(
+            method.cost(Decimal::ZERO, Decimal::from(100)),
+            Some(Decimal::ZERO)
+        );
+    }
+}
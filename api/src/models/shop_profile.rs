@@ -0,0 +1,114 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use validator::Validate;
+
+use crate::openapi::ApiSchema;
+
+/// The seller printed on invoices. Invoices keep a copy of it from the moment
+/// they were issued.
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
+pub struct ShopProfile {
+    pub name: String,
+    pub line1: Option<String>,
+    pub line2: Option<String>,
+    pub city: Option<String>,
+    pub postcode: Option<String>,
+    pub country: String,
+    pub vat_number: Option<String>,
+    pub email: Option<String>,
+    pub iban: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct ShopProfileUpdate {
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "field must contain between 1 and 128 characters"
+    ))]
+    name: Option<String>,
+
+    #[validate(length(max = 256, message = "field contains too many characters - max: 256"))]
+    line1: Option<String>,
+
+    #[validate(length(max = 256, message = "field contains too many characters - max: 256"))]
+    line2: Option<String>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    city: Option<String>,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    postcode: Option<String>,
+
+    #[validate(length(
+        equal = 2,
+        message = "field must be an ISO 3166-1 alpha-2 country code"
+    ))]
+    country: Option<String>,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    vat_number: Option<String>,
+
+    #[validate(email(message = "field must be a valid email address"))]
+    email: Option<String>,
+
+    #[validate(length(max = 34, message = "field contains too many characters - max: 34"))]
+    iban: Option<String>,
+}
+
+impl ShopProfile {
+    #[tracing::instrument(name = "ShopProfile::find", skip_all)]
+    pub async fn find<'e, E: PgExecutor<'e>>(executor: E) -> Result<ShopProfile, String> {
+        sqlx::query_as!(
+            ShopProfile,
+            r#"
+				SELECT name, line1, line2, city, postcode, country, vat_number, email, iban, updated_at
+				FROM shop_profile
+				WHERE id = 1;
+			"#
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "ShopProfile::update", skip_all)]
+    pub async fn update<'e, E: PgExecutor<'e>>(
+        input: ShopProfileUpdate,
+        executor: E,
+    ) -> Result<ShopProfile, String> {
+        sqlx::query_as!(
+            ShopProfile,
+            r#"
+				UPDATE shop_profile SET
+					name = COALESCE($1, name),
+					line1 = COALESCE($2, line1),
+					line2 = COALESCE($3, line2),
+					city = COALESCE($4, city),
+					postcode = COALESCE($5, postcode),
+					country = COALESCE($6, country),
+					vat_number = COALESCE($7, vat_number),
+					email = COALESCE($8, email),
+					iban = COALESCE($9, iban),
+					updated_at = NOW()
+				WHERE id = 1
+				RETURNING name, line1, line2, city, postcode, country, vat_number, email, iban,
+					updated_at;
+			"#,
+            input.name,
+            input.line1,
+            input.line2,
+            input.city,
+            input.postcode,
+            input.country.map(|c| c.to_uppercase()),
+            input.vat_number,
+            input.email,
+            input.iban
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| e.to_string())
+    }
+}
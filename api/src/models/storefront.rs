@@ -0,0 +1,173 @@
+use serde::Serialize;
+use sqlx::types::{Decimal, Json};
+use sqlx::PgPool;
+
+use super::{category::CategoryDb, currency::Currency, money::Money};
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+/// A published product as shoppers see it, priced in the selected currency
+/// and without stock keeping or discount internals.
+#[derive(Serialize, ApiSchema)]
+pub struct StorefrontProduct {
+    pub id: i64,
+    name: String,
+    description: Option<String>,
+    sku: Option<String>,
+    category_id: Option<i64>,
+    category: Option<Json<CategoryDb>>,
+    /// What a guest pays for one unit, the active discount applied.
+    price: Option<Json<Money>>,
+    /// The price before the discount, only set while one is active.
+    regular_price: Option<Json<Money>>,
+    /// Lowest price paid in the 30 days before the discount started, only set
+    /// while one is active.
+    lowest_price_30d: Option<Json<Money>>,
+    discount_percent: Option<Decimal>,
+    in_stock: bool,
+    weight: Option<Decimal>,
+    length: Option<Decimal>,
+    width: Option<Decimal>,
+    height: Option<Decimal>,
+    tax_class: String,
+}
+
+impl StorefrontProduct {
+    #[tracing::instrument(name = "StorefrontProduct::find_all", skip_all)]
+    pub async fn find_all(
+        currency: &Currency,
+        pool: &PgPool,
+    ) -> Result<Vec<StorefrontProduct>, String> {
+        sqlx::query_as!(
+            StorefrontProduct,
+            r#"
+				SELECT p.id, p.name, p.description, p.sku, p.category_id,
+					row_to_json(c.*) as "category: Json<CategoryDb>",
+					money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate)
+							* (1 - COALESCE(CASE WHEN d.active THEN d.discount_percent END, 0) / 100),
+						cur.code
+					) as "price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate), cur.code
+					) END as "regular_price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						CASE WHEN pp.price IS NOT NULL
+							THEN product_lowest_price(p.id, cur.code, 30)
+							ELSE product_lowest_price(p.id, NULL, 30) * cur.exchange_rate
+						END,
+						cur.code
+					) END as "lowest_price_30d: Json<Money>",
+					CASE WHEN d.active THEN d.discount_percent END as discount_percent,
+					COALESCE(i.quantity, 0) > 0 as "in_stock!",
+					p.weight, p.length, p.width, p.height, p.tax_class
+				FROM product p
+				JOIN currency cur ON cur.code = $1
+				LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = cur.code
+				LEFT JOIN product_inventory i ON i.id = p.inventory_id
+				LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+				LEFT JOIN category c on c.id = p.category_id AND c.deleted_at IS NULL
+				WHERE p.deleted_at IS NULL AND p.status = 'published'
+				ORDER BY id ASC;
+			"#,
+            currency.code
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "StorefrontProduct::find_by_id", skip_all)]
+    pub async fn find_by_id(
+        id: i64,
+        currency: &Currency,
+        pool: &PgPool,
+    ) -> Result<Option<StorefrontProduct>, String> {
+        sqlx::query_as!(
+            StorefrontProduct,
+            r#"
+				SELECT p.id, p.name, p.description, p.sku, p.category_id,
+					row_to_json(c.*) as "category: Json<CategoryDb>",
+					money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate)
+							* (1 - COALESCE(CASE WHEN d.active THEN d.discount_percent END, 0) / 100),
+						cur.code
+					) as "price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate), cur.code
+					) END as "regular_price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						CASE WHEN pp.price IS NOT NULL
+							THEN product_lowest_price(p.id, cur.code, 30)
+							ELSE product_lowest_price(p.id, NULL, 30) * cur.exchange_rate
+						END,
+						cur.code
+					) END as "lowest_price_30d: Json<Money>",
+					CASE WHEN d.active THEN d.discount_percent END as discount_percent,
+					COALESCE(i.quantity, 0) > 0 as "in_stock!",
+					p.weight, p.length, p.width, p.height, p.tax_class
+				FROM product p
+				JOIN currency cur ON cur.code = $1
+				LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = cur.code
+				LEFT JOIN product_inventory i ON i.id = p.inventory_id
+				LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+				LEFT JOIN category c on c.id = p.category_id AND c.deleted_at IS NULL
+				WHERE p.id = $2 AND p.deleted_at IS NULL AND p.status = 'published';
+			"#,
+            currency.code,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "StorefrontProduct::find_by_category", skip_all)]
+    pub async fn find_by_category(
+        category_id: Option<i64>,
+        currency: &Currency,
+        pool: &PgPool,
+    ) -> Result<Vec<StorefrontProduct>, String> {
+        sqlx::query_as!(
+            StorefrontProduct,
+            r#"
+				SELECT p.id, p.name, p.description, p.sku, p.category_id,
+					row_to_json(c.*) as "category: Json<CategoryDb>",
+					money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate)
+							* (1 - COALESCE(CASE WHEN d.active THEN d.discount_percent END, 0) / 100),
+						cur.code
+					) as "price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						COALESCE(pp.price, p.price * cur.exchange_rate), cur.code
+					) END as "regular_price: Json<Money>",
+					CASE WHEN d.active AND d.discount_percent > 0 THEN money_json(
+						CASE WHEN pp.price IS NOT NULL
+							THEN product_lowest_price(p.id, cur.code, 30)
+							ELSE product_lowest_price(p.id, NULL, 30) * cur.exchange_rate
+						END,
+						cur.code
+					) END as "lowest_price_30d: Json<Money>",
+					CASE WHEN d.active THEN d.discount_percent END as discount_percent,
+					COALESCE(i.quantity, 0) > 0 as "in_stock!",
+					p.weight, p.length, p.width, p.height, p.tax_class
+				FROM product p
+				JOIN currency cur ON cur.code = $1
+				LEFT JOIN product_price pp ON pp.product_id = p.id AND pp.currency = cur.code
+				LEFT JOIN product_inventory i ON i.id = p.inventory_id
+				LEFT JOIN discount d ON d.id = p.discount_id AND d.deleted_at IS NULL
+				LEFT JOIN category c on c.id = p.category_id AND c.deleted_at IS NULL
+				WHERE p.category_id = $2 AND p.deleted_at IS NULL AND p.status = 'published'
+				ORDER BY id ASC;
+			"#,
+            currency.code,
+            category_id
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,418 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
+use validator::{Validate, ValidationError};
+
+use super::currency::Currency;
+use super::order::OrderError;
+use super::validate_decimal;
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+const EU_COUNTRIES: [&str; 27] = [
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxClass {
+    Standard,
+    Reduced,
+    Zero,
+}
+
+impl TaxClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxClass::Standard => "standard",
+            TaxClass::Reduced => "reduced",
+            TaxClass::Zero => "zero",
+        }
+    }
+
+    pub fn parse(value: &str) -> TaxClass {
+        match value {
+            "reduced" => TaxClass::Reduced,
+            "zero" => TaxClass::Zero,
+            _ => TaxClass::Standard,
+        }
+    }
+}
+
+/// Shop wide tax configuration.
+#[derive(Clone)]
+pub struct TaxSettings {
+    /// Whether catalog prices and shipping costs already contain VAT.
+    pub prices_include_tax: bool,
+    /// Country the shop is registered in, B2B sales to other EU countries
+    /// are reverse charged.
+    pub shop_country: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct TaxRate {
+    pub id: i64,
+    pub country: String,
+    pub region: Option<String>,
+    pub tax_class: String,
+    pub rate: Decimal,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct TaxRateInsert {
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            equal = 2,
+            message = "field must be an ISO 3166-1 alpha-2 country code"
+        )
+    )]
+    country: Option<String>,
+
+    #[validate(length(max = 16, message = "field contains too many characters - max: 16"))]
+    region: Option<String>,
+
+    #[validate(required(message = "this field is required"))]
+    tax_class: Option<TaxClass>,
+
+    #[validate(
+        required(message = "this field is required"),
+        custom = "validate_decimal"
+    )]
+    rate: Option<Decimal>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct TaxRateUpdate {
+    #[validate(custom = "validate_decimal")]
+    rate: Option<Decimal>,
+}
+
+/// How much of a line amount is tax.
+#[derive(Serialize, Deserialize, Clone, Debug, ApiSchema)]
+pub struct TaxAmount {
+    pub tax_class: TaxClass,
+    pub rate: Decimal,
+    pub net: Decimal,
+    pub tax: Decimal,
+}
+
+/// Rates that apply to one destination and customer.
+pub struct TaxContext {
+    rates: HashMap<TaxClass, Decimal>,
+    pub country: String,
+    pub region: Option<String>,
+    pub prices_include_tax: bool,
+    pub reverse_charge: bool,
+    /// Amounts are rounded to its minor units.
+    pub currency: Currency,
+}
+
+pub fn validate_vat_number(vat_number: &str) -> Result<(), ValidationError> {
+    let valid = vat_number.is_ascii()
+        && vat_number.len() >= 4
+        && vat_number.len() <= 16
+        && vat_number[..2].chars().all(|c| c.is_ascii_uppercase())
+        && vat_number[2..].chars().all(|c| c.is_ascii_alphanumeric());
+
+    if !valid {
+        let mut error = ValidationError::new("vat_number");
+        error.message = Some("field must be a VAT number prefixed with its country code".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// B2B sales to a VAT registered customer in another EU country are reverse
+/// charged: the customer accounts for the VAT, we charge none. Greek VAT
+/// numbers use the `EL` prefix.
+pub fn is_reverse_charge(country: &str, vat_number: Option<&str>, shop_country: &str) -> bool {
+    let prefix = match vat_number.and_then(|v| v.get(..2)) {
+        Some("EL") => "GR",
+        Some(prefix) => prefix,
+        None => return false,
+    };
+
+    prefix == country && country != shop_country && EU_COUNTRIES.contains(&country)
+}
+
+impl TaxContext {
+    #[tracing::instrument(name = "TaxContext::resolve", skip_all)]
+    pub async fn resolve<'e, E: PgExecutor<'e>>(
+        country: &str,
+        region: Option<&str>,
+        vat_number: Option<&str>,
+        settings: &TaxSettings,
+        currency: &Currency,
+        executor: E,
+    ) -> Result<TaxContext, sqlx::Error> {
+        let country = country.to_uppercase();
+
+        // Region specific rates override the country wide ones.
+        let rows = sqlx::query!(
+            r#"
+				SELECT tax_class, rate FROM tax_rate
+				WHERE country = $1 AND (region IS NULL OR region = $2)
+				ORDER BY region NULLS FIRST;
+			"#,
+            country,
+            region
+        )
+        .fetch_all(executor)
+        .await
+        .record_rows()?;
+
+        let rates = rows
+            .into_iter()
+            .map(|r| (TaxClass::parse(&r.tax_class), r.rate))
+            .collect();
+
+        Ok(TaxContext {
+            rates,
+            reverse_charge: is_reverse_charge(&country, vat_number, &settings.shop_country),
+            country,
+            region: region.map(|r| r.to_string()),
+            prices_include_tax: settings.prices_include_tax,
+            currency: currency.clone(),
+        })
+    }
+
+    /// Rate of a tax class at the destination. Only the zero class may go
+    /// without a configured rate.
+    pub fn rate(&self, tax_class: TaxClass) -> Result<Decimal, OrderError> {
+        match self.rates.get(&tax_class) {
+            Some(rate) => Ok(*rate),
+            None if tax_class == TaxClass::Zero => Ok(Decimal::ZERO),
+            None => Err(OrderError::NoTaxRate {
+                country: self.country.clone(),
+                tax_class,
+            }),
+        }
+    }
+
+    /// Splits a line amount priced the shop's way into net and tax. Reverse
+    /// charged lines keep only the net part.
+    pub fn split(&self, tax_class: TaxClass, amount: Decimal) -> Result<TaxAmount, OrderError> {
+        let hundred = Decimal::from(100);
+        let rate = self.rate(tax_class)?;
+
+        let (net, tax) = if self.prices_include_tax {
+            let net = self.currency.round(amount * hundred / (hundred + rate));
+            (net, amount - net)
+        } else {
+            (amount, self.currency.round(amount * rate / hundred))
+        };
+
+        if self.reverse_charge {
+            return Ok(TaxAmount {
+                tax_class,
+                rate: Decimal::ZERO,
+                net,
+                tax: Decimal::ZERO,
+            });
+        }
+
+        Ok(TaxAmount {
+            tax_class,
+            rate,
+            net,
+            tax,
+        })
+    }
+}
+
+/// Sums up net and tax per tax class and rate.
+pub fn summarize<'a>(amounts: impl Iterator<Item = &'a TaxAmount>) -> Vec<TaxAmount> {
+    let mut breakdown: Vec<TaxAmount> = Vec::new();
+
+    for amount in amounts {
+        match breakdown
+            .iter_mut()
+            .find(|b| b.tax_class == amount.tax_class && b.rate == amount.rate)
+        {
+            Some(b) => {
+                b.net += amount.net;
+                b.tax += amount.tax;
+            }
+            None => breakdown.push(amount.clone()),
+        }
+    }
+
+    breakdown
+}
+
+impl TaxRate {
+    #[tracing::instrument(name = "TaxRate::find_all", skip_all)]
+    pub async fn find_all(pool: &PgPool) -> Result<Vec<TaxRate>, String> {
+        sqlx::query_as!(
+            TaxRate,
+            r#"
+				SELECT id, country, region, tax_class, rate, created_at, updated_at
+				FROM tax_rate
+				ORDER BY country, region NULLS FIRST, tax_class;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .record_rows()
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "TaxRate::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<TaxRate, String> {
+        sqlx::query_as!(
+            TaxRate,
+            r#"
+				SELECT id, country, region, tax_class, rate, created_at, updated_at
+				FROM tax_rate
+				WHERE id = $1;
+			"#,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "TaxRate::create", skip_all)]
+    pub async fn create(input: TaxRateInsert, pool: &PgPool) -> Result<TaxRate, String> {
+        sqlx::query_as!(
+            TaxRate,
+            r#"
+				INSERT INTO tax_rate (country, region, tax_class, rate)
+				VALUES ($1, $2, $3, $4)
+				RETURNING id, country, region, tax_class, rate, created_at, updated_at;
+			"#,
+            input.country.map(|c| c.to_uppercase()),
+            input.region,
+            input.tax_class.map(|c| c.as_str()),
+            input.rate
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "TaxRate::update", skip_all)]
+    pub async fn update(id: i64, input: TaxRateUpdate, pool: &PgPool) -> Result<TaxRate, String> {
+        sqlx::query_as!(
+            TaxRate,
+            r#"
+				UPDATE tax_rate SET
+					rate = COALESCE($1, rate),
+					updated_at = NOW()
+				WHERE id = $2
+				RETURNING id, country, region, tax_class, rate, created_at, updated_at;
+			"#,
+            input.rate,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    #[tracing::instrument(name = "TaxRate::delete", skip_all)]
+    pub async fn delete(id: i64, pool: &PgPool) -> Result<u64, String> {
+        sqlx::query!(
+            r#"
+				DELETE FROM tax_rate WHERE id = $1;
+			"#,
+            id
+        )
+        .execute(pool)
+        .await
+        .record_rows()
+        .map(|r| r.rows_affected())
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TaxClass, TaxContext};
+    use crate::models::currency::Currency;
+    use crate::models::order::OrderError;
+    use chrono::NaiveDateTime;
+    use sqlx::types::Decimal;
+
+    fn context(prices_include_tax: bool, reverse_charge: bool) -> TaxContext {
+        TaxContext {
+            rates: [
+                (TaxClass::Standard, Decimal::from(22)),
+                (TaxClass::Reduced, Decimal::new(95, 1)),
+            ]
+            .into_iter()
+            .collect(),
+            country: "SI".to_string(),
+            region: None,
+            prices_include_tax,
+            reverse_charge,
+            currency: Currency {
+                code: "EUR".to_string(),
+                name: "Euro".to_string(),
+                minor_units: 2,
+                exchange_rate: Decimal::ONE,
+                is_base: true,
+                active: true,
+                updated_at: NaiveDateTime::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn adds_tax_to_net_prices() {
+        let split = context(false, false)
+            .split(TaxClass::Standard, Decimal::new(1000, 2))
+            .unwrap();
+
+        assert_eq!(split.rate, Decimal::from(22));
+        assert_eq!(split.net, Decimal::new(1000, 2));
+        assert_eq!(split.tax, Decimal::new(220, 2));
+    }
+
+    #[test]
+    fn takes_tax_out_of_gross_prices() {
+        let split = context(true, false)
+            .split(TaxClass::Reduced, Decimal::new(1000, 2))
+            .unwrap();
+
+        assert_eq!(split.net, Decimal::new(913, 2));
+        assert_eq!(split.tax, Decimal::new(87, 2));
+        assert_eq!(split.net + split.tax, Decimal::new(1000, 2));
+    }
+
+    #[test]
+    fn reverse_charges_without_tax() {
+        let split = context(true, true)
+            .split(TaxClass::Standard, Decimal::new(1220, 2))
+            .unwrap();
+
+        assert_eq!(split.rate, Decimal::ZERO);
+        assert_eq!(split.net, Decimal::new(1000, 2));
+        assert_eq!(split.tax, Decimal::ZERO);
+    }
+
+    #[test]
+    fn fails_without_a_configured_rate() {
+        let mut context = context(false, false);
+        context.rates.clear();
+
+        assert!(matches!(
+            context.split(TaxClass::Standard, Decimal::ONE),
+            Err(OrderError::NoTaxRate {
+                tax_class: TaxClass::Standard,
+                ..
+            })
+        ));
+
+        let split = context.split(TaxClass::Zero, Decimal::ONE).unwrap();
+        assert_eq!(split.tax, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,202 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::{self, ROWS_AFFECTED, ROWS_RETURNED};
+
+#[derive(Serialize, ApiSchema)]
+pub struct TrashedItem {
+    pub id: i64,
+    pub name: String,
+    pub deleted_at: NaiveDateTime,
+}
+
+/// Soft deleted catalog records, most recently deleted first.
+#[derive(Serialize, ApiSchema)]
+pub struct Trash {
+    pub products: Vec<TrashedItem>,
+    pub categories: Vec<TrashedItem>,
+    pub discounts: Vec<TrashedItem>,
+}
+
+impl Trash {
+    #[tracing::instrument(name = "Trash::find", skip_all)]
+    pub async fn find(pool: &PgPool) -> Result<Trash, String> {
+        let products = sqlx::query_as!(
+            TrashedItem,
+            r#"
+				SELECT id, name, deleted_at as "deleted_at!" FROM product
+				WHERE deleted_at IS NOT NULL
+				ORDER BY deleted_at DESC, id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let categories = sqlx::query_as!(
+            TrashedItem,
+            r#"
+				SELECT id, name, deleted_at as "deleted_at!" FROM category
+				WHERE deleted_at IS NOT NULL
+				ORDER BY deleted_at DESC, id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let discounts = sqlx::query_as!(
+            TrashedItem,
+            r#"
+				SELECT id, name, deleted_at as "deleted_at!" FROM discount
+				WHERE deleted_at IS NOT NULL
+				ORDER BY deleted_at DESC, id ASC;
+			"#
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        telemetry::record_rows(
+            ROWS_RETURNED,
+            (products.len() + categories.len() + discounts.len()) as u64,
+        );
+
+        Ok(Trash {
+            products,
+            categories,
+            discounts,
+        })
+    }
+
+    /// Deletes everything that has been in the trash for longer than
+    /// `retention_days` for good, returns how many records were purged.
+    /// Categories stay as long as one of their subcategories does.
+    #[tracing::instrument(name = "Trash::purge", skip_all)]
+    pub async fn purge(retention_days: i32, pool: &PgPool) -> Result<u64, String> {
+        let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+        let mut purged = 0;
+
+        purged += sqlx::query!(
+            r#"
+				DELETE FROM product WHERE deleted_at < NOW() - make_interval(days => $1);
+			"#,
+            retention_days
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        purged += sqlx::query!(
+            r#"
+				DELETE FROM discount WHERE deleted_at < NOW() - make_interval(days => $1);
+			"#,
+            retention_days
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        purged += sqlx::query!(
+            r#"
+				WITH RECURSIVE kept AS (
+					SELECT id, parent_id FROM category
+					WHERE deleted_at IS NULL OR deleted_at >= NOW() - make_interval(days => $1)
+
+					UNION
+
+					SELECT c.id, c.parent_id FROM category c
+					JOIN kept k ON c.id = k.parent_id
+				)
+				DELETE FROM category
+				WHERE deleted_at < NOW() - make_interval(days => $1)
+					AND id NOT IN (SELECT id FROM kept);
+			"#,
+            retention_days
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        telemetry::record_rows(ROWS_AFFECTED, purged);
+
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn category(
+        name: &str,
+        parent_id: Option<i64>,
+        days_ago: Option<i32>,
+        pool: &PgPool,
+    ) -> i64 {
+        sqlx::query_scalar!(
+            r#"
+				INSERT INTO category (name, parent_id, deleted_at)
+				VALUES ($1, $2, NOW() - make_interval(days => $3))
+				RETURNING id;
+			"#,
+            name,
+            parent_id,
+            days_ago
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn exists(id: i64, pool: &PgPool) -> bool {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM category WHERE id = $1) as "exists!";"#,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /// Needs the database in `DATABASE_URL`.
+    #[tokio::test]
+    async fn purges_categories_only_once_their_subcategories_are_gone() {
+        dotenv::dotenv().ok();
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL isn't set");
+        let pool = PgPool::connect(&database_url).await.unwrap();
+
+        let old = category("Purge test", None, Some(40), &pool).await;
+        let old_child = category("Purge test", Some(old), Some(40), &pool).await;
+        let parent_of_recent = category("Purge test", None, Some(40), &pool).await;
+        let recent = category("Purge test", Some(parent_of_recent), Some(10), &pool).await;
+        let parent_of_live = category("Purge test", None, Some(40), &pool).await;
+        let live = category("Purge test", Some(parent_of_live), None, &pool).await;
+
+        assert!(Trash::purge(30, &pool).await.unwrap() >= 2);
+
+        assert!(!exists(old, &pool).await);
+        assert!(!exists(old_child, &pool).await);
+        assert!(exists(parent_of_recent, &pool).await);
+        assert!(exists(recent, &pool).await);
+        assert!(exists(parent_of_live, &pool).await);
+        assert!(exists(live, &pool).await);
+
+        sqlx::query!(
+            "DELETE FROM category WHERE id IN ($1, $2, $3, $4);",
+            recent,
+            live,
+            parent_of_recent,
+            parent_of_live
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+}
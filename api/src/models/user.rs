@@ -0,0 +1,283 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, SaltString};
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::openapi::ApiSchema;
+use crate::telemetry::RecordRows;
+
+#[derive(Debug)]
+pub enum UserError {
+    NotFound(i64),
+    EmailTaken,
+    UnknownCurrency,
+    InvalidCredentials,
+    Database(String),
+}
+
+impl From<sqlx::Error> for UserError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(ref d) if d.constraint() == Some("app_user_email_idx") => {
+                UserError::EmailTaken
+            }
+            sqlx::Error::Database(ref d) if d.constraint() == Some("app_user_currency_fk") => {
+                UserError::UnknownCurrency
+            }
+            e => UserError::Database(e.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserError::NotFound(id) => write!(f, "user {} not found", id),
+            UserError::EmailTaken => write!(f, "an account with this email already exists"),
+            UserError::UnknownCurrency => write!(f, "unsupported currency"),
+            UserError::InvalidCredentials => write!(f, "invalid email or password"),
+            UserError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Anyone who can log in: shoppers have the `customer` role, staff `admin`.
+#[derive(Serialize, ApiSchema)]
+pub struct User {
+    pub id: i64,
+    pub email: String,
+    pub user_role: Vec<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone: Option<String>,
+    /// Preferred price list, see `SelectedCurrency`.
+    pub currency: Option<String>,
+    pub customer_group_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct UserRegister {
+    #[validate(
+        required(message = "this field is required"),
+        email(message = "field must be a valid email address")
+    )]
+    email: Option<String>,
+
+    #[validate(
+        required(message = "this field is required"),
+        length(
+            min = 8,
+            max = 128,
+            message = "field must contain between 8 and 128 characters"
+        )
+    )]
+    password: Option<String>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    first_name: Option<String>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    last_name: Option<String>,
+
+    #[validate(length(max = 32, message = "field contains too many characters - max: 32"))]
+    phone: Option<String>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct UserLogin {
+    #[validate(required(message = "this field is required"))]
+    pub email: Option<String>,
+
+    #[validate(required(message = "this field is required"))]
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Validate, ApiSchema)]
+pub struct UserUpdate {
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    first_name: Option<String>,
+
+    #[validate(length(max = 128, message = "field contains too many characters - max: 128"))]
+    last_name: Option<String>,
+
+    #[validate(length(max = 32, message = "field contains too many characters - max: 32"))]
+    phone: Option<String>,
+
+    #[validate(length(
+        min = 8,
+        max = 128,
+        message = "field must contain between 8 and 128 characters"
+    ))]
+    password: Option<String>,
+
+    #[validate(length(equal = 3, message = "field must be an ISO 4217 currency code"))]
+    currency: Option<String>,
+}
+
+/// Hash of a password nobody knows, made with the default parameters.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=4096,t=3,p=1$91PTh/ivXtdFIvaYHCinww$zZ3pRGK35l9LhsBSt+u6kg9914L2LGx9POivKSo3VIw";
+
+fn hash_password(password: &str) -> Result<String, UserError> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| UserError::Database(e.to_string()))
+}
+
+impl User {
+    #[tracing::instrument(name = "User::find_by_id", skip_all)]
+    pub async fn find_by_id(id: i64, pool: &PgPool) -> Result<User, UserError> {
+        sqlx::query_as!(
+            User,
+            r#"
+				SELECT id, email, user_role as "user_role: Vec<String>", first_name, last_name, phone,
+					currency, customer_group_id, created_at, updated_at
+				FROM app_user
+				WHERE id = $1;
+			"#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()?
+        .ok_or(UserError::NotFound(id))
+    }
+
+    /// Creates a customer account.
+    #[tracing::instrument(name = "User::register", skip_all)]
+    pub async fn register(input: UserRegister, pool: &PgPool) -> Result<User, UserError> {
+        let password_hash = hash_password(&input.password.unwrap_or_default())?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+				INSERT INTO app_user (email, password_hash, first_name, last_name, phone)
+				VALUES ($1, $2, $3, $4, $5)
+				RETURNING id, email, user_role as "user_role: Vec<String>", first_name, last_name,
+					phone, currency, customer_group_id, created_at, updated_at;
+			"#,
+            input.email.map(|e| e.trim().to_string()),
+            password_hash,
+            input.first_name,
+            input.last_name,
+            input.phone
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Creates a staff account, see `crabbyshop create-admin`.
+    #[tracing::instrument(name = "User::create_admin", skip_all)]
+    pub async fn create_admin(
+        email: &str,
+        password: &str,
+        pool: &PgPool,
+    ) -> Result<User, UserError> {
+        let password_hash = hash_password(password)?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+				INSERT INTO app_user (email, password_hash, user_role)
+				VALUES ($1, $2, '{admin}')
+				RETURNING id, email, user_role as "user_role: Vec<String>", first_name, last_name,
+					phone, currency, customer_group_id, created_at, updated_at;
+			"#,
+            email.trim(),
+            password_hash
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Looks up a user by email and checks the password against the stored
+    /// hash. Unknown emails and wrong passwords fail the same way.
+    #[tracing::instrument(name = "User::authenticate", skip_all)]
+    pub async fn authenticate(input: UserLogin, pool: &PgPool) -> Result<User, UserError> {
+        let row = sqlx::query!(
+            r#"
+				SELECT id, password_hash FROM app_user WHERE LOWER(email) = LOWER($1);
+			"#,
+            input.email.map(|e| e.trim().to_string())
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()?;
+
+        // Unknown emails are checked against a dummy hash so that they take
+        // as long as wrong passwords.
+        let password_hash = row
+            .as_ref()
+            .map_or(DUMMY_PASSWORD_HASH, |r| r.password_hash.as_str());
+        let hash = PasswordHash::new(password_hash).map_err(|_| UserError::InvalidCredentials)?;
+        Argon2::default()
+            .verify_password(input.password.unwrap_or_default().as_bytes(), &hash)
+            .map_err(|_| UserError::InvalidCredentials)?;
+
+        match row {
+            Some(row) => User::find_by_id(row.id, pool).await,
+            None => Err(UserError::InvalidCredentials),
+        }
+    }
+
+    #[tracing::instrument(name = "User::update", skip_all)]
+    pub async fn update(id: i64, input: UserUpdate, pool: &PgPool) -> Result<User, UserError> {
+        let password_hash = match input.password {
+            Some(password) => Some(hash_password(&password)?),
+            None => None,
+        };
+
+        sqlx::query_as!(
+            User,
+            r#"
+				UPDATE app_user SET
+					first_name = COALESCE($1, first_name),
+					last_name = COALESCE($2, last_name),
+					phone = COALESCE($3, phone),
+					password_hash = COALESCE($4, password_hash),
+					currency = COALESCE($5, currency),
+					updated_at = NOW()
+				WHERE id = $6
+				RETURNING id, email, user_role as "user_role: Vec<String>", first_name, last_name,
+					phone, currency, customer_group_id, created_at, updated_at;
+			"#,
+            input.first_name,
+            input.last_name,
+            input.phone,
+            password_hash,
+            input.currency.map(|c| c.to_uppercase()),
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .record_rows()?
+        .ok_or(UserError::NotFound(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DUMMY_PASSWORD_HASH;
+    use argon2::password_hash::PasswordHash;
+    use argon2::{Argon2, PasswordVerifier};
+
+    #[test]
+    fn rejects_every_password_with_the_dummy_hash() {
+        let hash = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+
+        assert!(Argon2::default()
+            .verify_password(b"password", &hash)
+            .is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{Html, IntoResponse};
+use axum::{routing, Json, Router};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::{json, Map, Value};
+use sqlx::types::Decimal;
+use std::sync::OnceLock;
+
+pub use crabbyshop_derive::ApiSchema;
+
+use crate::errors::ApiError;
+
+mod operation;
+mod router;
+
+pub use operation::Operation;
+pub use router::{delete, get, patch, post, put, ApiMethodRouter, ApiRouter};
+
+/// Named schemas of the document, by type name.
+pub type Components = Map<String, Value>;
+
+/// A type that can be described in the OpenAPI document, derived for the
+/// request and response types with `#[derive(ApiSchema)]`.
+pub trait ApiSchema {
+    /// Whether a field of this type may be left out of a request.
+    const OPTIONAL: bool = false;
+
+    /// The schema to use where the type appears, named types are added to
+    /// `components` and referenced.
+    fn schema(components: &mut Components) -> Value;
+}
+
+impl ApiSchema for String {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl ApiSchema for &str {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl ApiSchema for bool {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+impl ApiSchema for i16 {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "integer", "format": "int32" })
+    }
+}
+
+impl ApiSchema for i32 {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "integer", "format": "int32" })
+    }
+}
+
+impl ApiSchema for i64 {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "integer", "format": "int64" })
+    }
+}
+
+impl ApiSchema for u64 {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "integer", "format": "int64", "minimum": 0 })
+    }
+}
+
+/// Decimals are exchanged as strings so that no precision is lost, numbers
+/// are accepted too.
+impl ApiSchema for Decimal {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string", "format": "decimal", "example": "19.99" })
+    }
+}
+
+impl ApiSchema for NaiveDateTime {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string", "format": "date-time", "example": "2022-07-21T12:00:00" })
+    }
+}
+
+impl ApiSchema for NaiveDate {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string", "format": "date" })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn schema(components: &mut Components) -> Value {
+        T::schema(components)
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components) })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for sqlx::types::Json<T> {
+    fn schema(components: &mut Components) -> Value {
+        T::schema(components)
+    }
+}
+
+/// Any JSON value, like the snapshots of the audit log.
+impl ApiSchema for Value {
+    fn schema(_: &mut Components) -> Value {
+        json!({})
+    }
+}
+
+pub fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+pub fn enum_schema(description: Option<&str>, values: &[&str]) -> Value {
+    let mut schema = json!({ "type": "string", "enum": values });
+    if let Some(description) = description {
+        schema["description"] = json!(description);
+    }
+
+    schema
+}
+
+/// Builds the schema of a struct, used by the derive.
+pub struct ObjectSchema {
+    schema: Map<String, Value>,
+    properties: Map<String, Value>,
+    required: Vec<String>,
+    flattened: Vec<Value>,
+}
+
+impl ObjectSchema {
+    pub fn new(description: Option<&str>) -> ObjectSchema {
+        let mut schema = Map::new();
+        schema.insert("type".to_string(), json!("object"));
+        if let Some(description) = description {
+            schema.insert("description".to_string(), json!(description));
+        }
+
+        ObjectSchema {
+            schema,
+            properties: Map::new(),
+            required: Vec::new(),
+            flattened: Vec::new(),
+        }
+    }
+
+    /// Adds a field, `constraints` are the bounds of its validators like
+    /// `("length_max", 128)`.
+    pub fn property(
+        &mut self,
+        name: &str,
+        schema: Value,
+        required: bool,
+        description: Option<&str>,
+        constraints: &[(&str, Value)],
+    ) {
+        // Siblings of a reference are ignored, so it's wrapped instead.
+        let mut schema =
+            if schema.get("$ref").is_some() && (description.is_some() || !constraints.is_empty()) {
+                json!({ "allOf": [schema] })
+            } else {
+                schema
+            };
+
+        let is_array = schema["type"] == "array";
+        for (constraint, value) in constraints {
+            let key = match (*constraint, is_array) {
+                ("length_min", false) => "minLength",
+                ("length_max", false) => "maxLength",
+                ("length_min", true) => "minItems",
+                ("length_max", true) => "maxItems",
+                ("range_min", _) => "minimum",
+                ("range_max", _) => "maximum",
+                ("length_equal", false) => {
+                    schema["minLength"] = value.clone();
+                    "maxLength"
+                }
+                ("length_equal", true) => {
+                    schema["minItems"] = value.clone();
+                    "maxItems"
+                }
+                (other, _) => other,
+            };
+            schema[key] = value.clone();
+        }
+
+        if let Some(description) = description {
+            schema["description"] = json!(description);
+        }
+
+        self.properties.insert(name.to_string(), schema);
+        if required {
+            self.required.push(name.to_string());
+        }
+    }
+
+    /// Adds the fields of a `#[serde(flatten)]` field.
+    pub fn flatten(&mut self, schema: Value) {
+        self.flattened.push(schema);
+    }
+
+    pub fn into_value(mut self) -> Value {
+        self.schema
+            .insert("properties".to_string(), Value::Object(self.properties));
+        if !self.required.is_empty() {
+            self.schema
+                .insert("required".to_string(), json!(self.required));
+        }
+
+        if self.flattened.is_empty() {
+            Value::Object(self.schema)
+        } else {
+            self.flattened.push(Value::Object(self.schema));
+            json!({ "allOf": self.flattened })
+        }
+    }
+}
+
+/// The OpenAPI 3 document of the API, relative to `/api/v1`.
+pub fn spec() -> Value {
+    let mut components = Components::new();
+    let mut paths = Map::new();
+
+    let error = ApiError::schema(&mut components);
+
+    for operation in crate::routes::api_routes().into_operations() {
+        let item = paths
+            .entry(operation.openapi_path())
+            .or_insert_with(|| json!({}));
+        let method = operation.method;
+        item[method] = operation.into_value(&error, &mut components);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "crabbyshop",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": paths,
+        "components": {
+            "schemas": components,
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+            },
+        },
+    })
+}
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/openapi.json",
+            get(fetch_spec, "This document").returns::<Value>(),
+        )
+        .route(
+            "/docs",
+            get(fetch_docs, "API reference").produces("text/html"),
+        )
+        .merge_undocumented(
+            Router::new()
+                .route(
+                    "/docs/swagger-ui-bundle.js",
+                    routing::get(fetch_docs_script),
+                )
+                .route("/docs/swagger-ui.css", routing::get(fetch_docs_stylesheet)),
+        )
+}
+
+async fn fetch_spec() -> impl IntoResponse {
+    static SPEC: OnceLock<Value> = OnceLock::new();
+
+    Json(SPEC.get_or_init(spec).clone())
+}
+
+/// Swagger UI, served from the binary so the docs work offline.
+async fn fetch_docs() -> impl IntoResponse {
+    Html(
+        r##"<!DOCTYPE html>
+<html>
+	<head>
+		<title>crabbyshop API</title>
+		<meta charset="utf-8">
+		<meta name="viewport" content="width=device-width, initial-scale=1">
+		<link rel="stylesheet" href="docs/swagger-ui.css">
+	</head>
+	<body>
+		<div id="swagger-ui"></div>
+		<script src="docs/swagger-ui-bundle.js"></script>
+		<script>
+			window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+		</script>
+	</body>
+</html>
+"##,
+    )
+}
+
+async fn fetch_docs_script() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/javascript")],
+        include_str!("../../assets/swagger-ui/swagger-ui-bundle.js"),
+    )
+}
+
+async fn fetch_docs_stylesheet() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/css")],
+        include_str!("../../assets/swagger-ui/swagger-ui.css"),
+    )
+}
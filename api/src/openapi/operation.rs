@@ -0,0 +1,245 @@
+use serde_json::{json, Map, Value};
+
+use super::{ApiSchema, Components};
+
+type SchemaFn = fn(&mut Components) -> Value;
+
+/// Path parameters that aren't numeric ids.
+const STRING_PARAMETERS: &[&str] = &["code", "currency"];
+
+/// The documentation of one route, collected from its registration and the
+/// extractors of its handler.
+pub struct Operation {
+    pub method: &'static str,
+    /// As registered with axum, like `/product/:id`.
+    pub path: String,
+    summary: &'static str,
+    status: u16,
+    content: Vec<(&'static str, Option<SchemaFn>)>,
+    pub(super) body: Option<(SchemaFn, bool)>,
+    pub(super) query: Option<SchemaFn>,
+    pub(super) authenticated: bool,
+    pub(super) order_access: bool,
+    pub(super) priced: bool,
+    pub(super) idempotent: bool,
+}
+
+impl Operation {
+    pub(super) fn new(method: &'static str, summary: &'static str) -> Operation {
+        Operation {
+            method,
+            path: String::new(),
+            summary,
+            status: 200,
+            content: Vec::new(),
+            body: None,
+            query: None,
+            authenticated: false,
+            order_access: false,
+            priced: false,
+            idempotent: false,
+        }
+    }
+
+    pub(super) fn status(&mut self, status: u16) {
+        self.status = status;
+    }
+
+    /// The response is `T` as JSON.
+    pub(super) fn returns<T: ApiSchema>(&mut self) {
+        self.content.push(("application/json", Some(T::schema)));
+    }
+
+    /// The response is a document of `content_type`.
+    pub(super) fn produces(&mut self, content_type: &'static str) {
+        self.content.push((content_type, None));
+    }
+
+    /// The path in the OpenAPI form, like `/product/{id}`.
+    pub fn openapi_path(&self) -> String {
+        self.path
+            .split('/')
+            .map(|s| match s.strip_prefix(':') {
+                Some(param) => format!("{{{}}}", param),
+                None => s.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    pub fn into_value(self, error: &Value, components: &mut Components) -> Value {
+        let path = self.openapi_path();
+        let is_admin = path.starts_with("/admin/");
+        let authenticated = self.authenticated || is_admin;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let tag = match segments.as_slice() {
+            ["admin", tag, ..] | [tag, ..] => *tag,
+            [] => "api",
+        };
+
+        let mut parameters = Vec::new();
+        for segment in &segments {
+            if let Some(name) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                let schema = if STRING_PARAMETERS.contains(&name) {
+                    json!({ "type": "string" })
+                } else {
+                    json!({ "type": "integer", "format": "int64" })
+                };
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": schema,
+                }));
+            }
+        }
+
+        if let Some(query) = self.query {
+            let reference = query(components);
+            let name = reference["$ref"].as_str().unwrap_or_default();
+            let name = name.rsplit('/').next().unwrap_or_default();
+            // Query parameters are listed one by one instead.
+            let params = components.remove(name).unwrap_or_default();
+            let required = params["required"].as_array().cloned().unwrap_or_default();
+
+            if let Some(properties) = params["properties"].as_object() {
+                for (name, schema) in properties {
+                    let mut schema = schema.clone();
+                    let mut parameter = json!({
+                        "name": name,
+                        "in": "query",
+                        "required": required.contains(&json!(name)),
+                    });
+                    if let Some(description) =
+                        schema.as_object_mut().and_then(|s| s.remove("description"))
+                    {
+                        parameter["description"] = description;
+                    }
+                    parameter["schema"] = schema;
+                    parameters.push(parameter);
+                }
+            }
+        }
+
+        if self.priced {
+            parameters.push(json!({
+                "name": "X-Currency",
+                "in": "header",
+                "required": false,
+                "description": "ISO 4217 code of the price list, defaults to the customer's preferred or the base currency.",
+                "schema": { "type": "string" },
+            }));
+        }
+        if self.order_access {
+            parameters.push(json!({
+                "name": "X-Order-Token",
+                "in": "header",
+                "required": false,
+                "description": "Access token of a guest order, as returned when it was placed.",
+                "schema": { "type": "string" },
+            }));
+        }
+        if self.idempotent {
+            parameters.push(json!({
+                "name": "Idempotency-Key",
+                "in": "header",
+                "required": false,
+                "description": "Unique key of the request, retries with it get the first response for 24 hours by default. Guests have to send a UUID.",
+                "schema": { "type": "string", "maxLength": 255 },
+            }));
+        }
+
+        let mut responses = Map::new();
+        let description = match self.status {
+            201 => "Created",
+            202 => "Accepted",
+            204 => "No content",
+            _ => "OK",
+        };
+        let mut success = json!({ "description": description });
+        if !self.content.is_empty() {
+            let mut content = Map::new();
+            for (content_type, schema) in self.content {
+                let schema = match schema {
+                    Some(schema) => schema(components),
+                    None if content_type.starts_with("text/") => json!({ "type": "string" }),
+                    None => json!({ "type": "string", "format": "binary" }),
+                };
+                content.insert(content_type.to_string(), json!({ "schema": schema }));
+            }
+            success["content"] = Value::Object(content);
+        }
+        responses.insert(self.status.to_string(), success);
+        if authenticated {
+            responses.insert(
+                "401".to_string(),
+                json!({ "description": "Missing or invalid token" }),
+            );
+        }
+        if self.order_access {
+            responses.insert("401".to_string(), json!({ "description": "Invalid token" }));
+            responses.insert(
+                "404".to_string(),
+                json!({ "description": "No such order, or not the customer's or the token's" }),
+            );
+        }
+        if is_admin {
+            responses.insert(
+                "403".to_string(),
+                json!({ "description": "Admin role required" }),
+            );
+        }
+        if self.idempotent {
+            responses.insert(
+                "409".to_string(),
+                json!({ "description": "A request with the same Idempotency-Key is still being handled" }),
+            );
+            responses.insert(
+                "422".to_string(),
+                json!({ "description": "The Idempotency-Key was used for a different request" }),
+            );
+        }
+        responses.insert(
+            "429".to_string(),
+            json!({
+                "description": "Rate limited",
+                "headers": {
+                    "Retry-After": {
+                        "description": "Seconds until the request may be retried.",
+                        "schema": { "type": "integer" },
+                    },
+                },
+            }),
+        );
+        responses.insert(
+            "default".to_string(),
+            json!({
+                "description": "Error",
+                "content": { "application/json": { "schema": error } },
+            }),
+        );
+
+        let mut value = json!({
+            "tags": [tag],
+            "summary": self.summary,
+            "responses": responses,
+        });
+        if !parameters.is_empty() {
+            value["parameters"] = json!(parameters);
+        }
+        if let Some((body, required)) = self.body {
+            value["requestBody"] = json!({
+                "required": required,
+                "content": { "application/json": { "schema": body(components) } },
+            });
+        }
+        if authenticated {
+            value["security"] = json!([{ "bearer": [] }]);
+        } else if self.order_access {
+            // Guests send the order's access token instead.
+            value["security"] = json!([{ "bearer": [] }, {}]);
+        }
+
+        value
+    }
+}
@@ -0,0 +1,349 @@
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::{Path, Query};
+use axum::handler::Handler;
+use axum::http::{HeaderMap, Request};
+use axum::response::Response;
+use axum::routing::{self, MethodRouter, Route};
+use axum::{middleware, BoxError, Extension, Json, Router};
+use std::convert::Infallible;
+use tower::{Layer, Service};
+
+use crate::idempotency;
+use crate::models::authentication::{JwtToken, OptionalJwtToken};
+use crate::models::currency::SelectedCurrency;
+use crate::models::order::OrderAccess;
+
+use super::operation::Operation;
+use super::ApiSchema;
+
+/// A router that documents its routes as they're registered, the OpenAPI
+/// document is built from the operations of [`crate::routes::api_routes`].
+#[derive(Default)]
+pub struct ApiRouter {
+    router: Router,
+    operations: Vec<Operation>,
+}
+
+impl ApiRouter {
+    pub fn new() -> ApiRouter {
+        ApiRouter::default()
+    }
+
+    pub fn route(mut self, path: &str, method_router: ApiMethodRouter) -> ApiRouter {
+        self.router = self.router.route(path, method_router.router);
+        self.operations
+            .extend(method_router.operations.into_iter().map(|mut operation| {
+                operation.path = path.to_string();
+                operation
+            }));
+        self
+    }
+
+    pub fn merge(mut self, other: ApiRouter) -> ApiRouter {
+        self.router = self.router.merge(other.router);
+        self.operations.extend(other.operations);
+        self
+    }
+
+    /// Adds routes that aren't part of the API, like the assets of the docs.
+    pub fn merge_undocumented(mut self, other: Router) -> ApiRouter {
+        self.router = self.router.merge(other);
+        self
+    }
+
+    pub fn nest(mut self, path: &str, other: ApiRouter) -> ApiRouter {
+        self.router = self.router.nest(path, other.router);
+        self.operations
+            .extend(other.operations.into_iter().map(|mut operation| {
+                operation.path = format!("{}{}", path, operation.path);
+                operation
+            }));
+        self
+    }
+
+    pub fn layer<L, NewResBody>(mut self, layer: L) -> ApiRouter
+    where
+        L: Layer<Route>,
+        L::Service: Service<Request<Body>, Response = Response<NewResBody>, Error = Infallible>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+        NewResBody: HttpBody<Data = Bytes> + Send + 'static,
+        NewResBody::Error: Into<BoxError>,
+    {
+        self.router = self.router.layer(layer);
+        self
+    }
+
+    pub fn route_layer<L, NewResBody>(mut self, layer: L) -> ApiRouter
+    where
+        L: Layer<Route>,
+        L::Service: Service<Request<Body>, Response = Response<NewResBody>, Error = Infallible>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+        NewResBody: HttpBody<Data = Bytes> + Send + 'static,
+        NewResBody::Error: Into<BoxError>,
+    {
+        self.router = self.router.route_layer(layer);
+        self
+    }
+
+    pub fn into_router(self) -> Router {
+        self.router
+    }
+
+    pub fn into_operations(self) -> Vec<Operation> {
+        self.operations
+    }
+}
+
+/// The handlers of one path and their documentation, `status`, `returns`
+/// and `produces` describe the handler added last.
+pub struct ApiMethodRouter {
+    router: MethodRouter,
+    operations: Vec<Operation>,
+}
+
+fn operation<H: HandlerDocs<T>, T>(method: &'static str, summary: &'static str) -> Operation {
+    let mut operation = Operation::new(method, summary);
+    H::describe(&mut operation);
+    operation
+}
+
+pub fn get<H, T>(handler: H, summary: &'static str) -> ApiMethodRouter
+where
+    H: Handler<T, Body> + HandlerDocs<T>,
+    T: 'static,
+{
+    ApiMethodRouter {
+        router: routing::get(handler),
+        operations: vec![operation::<H, T>("get", summary)],
+    }
+}
+
+pub fn post<H, T>(handler: H, summary: &'static str) -> ApiMethodRouter
+where
+    H: Handler<T, Body> + HandlerDocs<T>,
+    T: 'static,
+{
+    ApiMethodRouter {
+        router: routing::post(handler),
+        operations: vec![operation::<H, T>("post", summary)],
+    }
+}
+
+pub fn put<H, T>(handler: H, summary: &'static str) -> ApiMethodRouter
+where
+    H: Handler<T, Body> + HandlerDocs<T>,
+    T: 'static,
+{
+    ApiMethodRouter {
+        router: routing::put(handler),
+        operations: vec![operation::<H, T>("put", summary)],
+    }
+}
+
+pub fn patch<H, T>(handler: H, summary: &'static str) -> ApiMethodRouter
+where
+    H: Handler<T, Body> + HandlerDocs<T>,
+    T: 'static,
+{
+    ApiMethodRouter {
+        router: routing::patch(handler),
+        operations: vec![operation::<H, T>("patch", summary)],
+    }
+}
+
+pub fn delete<H, T>(handler: H, summary: &'static str) -> ApiMethodRouter
+where
+    H: Handler<T, Body> + HandlerDocs<T>,
+    T: 'static,
+{
+    ApiMethodRouter {
+        router: routing::delete(handler),
+        operations: vec![operation::<H, T>("delete", summary)],
+    }
+}
+
+impl ApiMethodRouter {
+    pub fn get<H, T>(mut self, handler: H, summary: &'static str) -> ApiMethodRouter
+    where
+        H: Handler<T, Body> + HandlerDocs<T>,
+        T: 'static,
+    {
+        self.router = self.router.get(handler);
+        self.operations.push(operation::<H, T>("get", summary));
+        self
+    }
+
+    pub fn post<H, T>(mut self, handler: H, summary: &'static str) -> ApiMethodRouter
+    where
+        H: Handler<T, Body> + HandlerDocs<T>,
+        T: 'static,
+    {
+        self.router = self.router.post(handler);
+        self.operations.push(operation::<H, T>("post", summary));
+        self
+    }
+
+    pub fn put<H, T>(mut self, handler: H, summary: &'static str) -> ApiMethodRouter
+    where
+        H: Handler<T, Body> + HandlerDocs<T>,
+        T: 'static,
+    {
+        self.router = self.router.put(handler);
+        self.operations.push(operation::<H, T>("put", summary));
+        self
+    }
+
+    pub fn patch<H, T>(mut self, handler: H, summary: &'static str) -> ApiMethodRouter
+    where
+        H: Handler<T, Body> + HandlerDocs<T>,
+        T: 'static,
+    {
+        self.router = self.router.patch(handler);
+        self.operations.push(operation::<H, T>("patch", summary));
+        self
+    }
+
+    pub fn delete<H, T>(mut self, handler: H, summary: &'static str) -> ApiMethodRouter
+    where
+        H: Handler<T, Body> + HandlerDocs<T>,
+        T: 'static,
+    {
+        self.router = self.router.delete(handler);
+        self.operations.push(operation::<H, T>("delete", summary));
+        self
+    }
+
+    fn last(&mut self) -> &mut Operation {
+        self.operations
+            .last_mut()
+            .expect("method router without operations")
+    }
+
+    /// The status of a successful response, 200 by default.
+    pub fn status(mut self, status: u16) -> ApiMethodRouter {
+        self.last().status(status);
+        self
+    }
+
+    /// Responds with `T` as JSON.
+    pub fn returns<T: ApiSchema>(mut self) -> ApiMethodRouter {
+        self.last().returns::<T>();
+        self
+    }
+
+    /// Responds with a document of `content_type`.
+    pub fn produces(mut self, content_type: &'static str) -> ApiMethodRouter {
+        self.last().produces(content_type);
+        self
+    }
+
+    /// Retries of the POST handler with the same `Idempotency-Key` get the
+    /// first response.
+    pub fn idempotent(mut self) -> ApiMethodRouter {
+        for operation in &mut self.operations {
+            if operation.method == "post" {
+                operation.idempotent = true;
+            }
+        }
+        self.layer(middleware::from_fn(idempotency::idempotent))
+    }
+
+    pub fn layer<L, NewResBody>(mut self, layer: L) -> ApiMethodRouter
+    where
+        L: Layer<Route>,
+        L::Service: Service<Request<Body>, Response = Response<NewResBody>, Error = Infallible>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+        NewResBody: HttpBody<Data = Bytes> + Send + 'static,
+        NewResBody::Error: Into<BoxError>,
+    {
+        self.router = self.router.layer(layer);
+        self
+    }
+}
+
+/// What an extractor adds to the documentation of the handlers taking it.
+pub trait OperationInput {
+    fn describe(_operation: &mut Operation) {}
+}
+
+impl<T: ApiSchema> OperationInput for Json<T> {
+    fn describe(operation: &mut Operation) {
+        operation.body = Some((T::schema, true));
+    }
+}
+
+impl<T: ApiSchema> OperationInput for Option<Json<T>> {
+    fn describe(operation: &mut Operation) {
+        operation.body = Some((T::schema, false));
+    }
+}
+
+impl<T: ApiSchema> OperationInput for Query<T> {
+    fn describe(operation: &mut Operation) {
+        operation.query = Some(T::schema);
+    }
+}
+
+impl OperationInput for JwtToken {
+    fn describe(operation: &mut Operation) {
+        operation.authenticated = true;
+    }
+}
+
+impl OperationInput for SelectedCurrency {
+    fn describe(operation: &mut Operation) {
+        operation.priced = true;
+    }
+}
+
+impl OperationInput for OrderAccess {
+    fn describe(operation: &mut Operation) {
+        operation.order_access = true;
+    }
+}
+
+// Path parameters are documented from the path, the rest isn't visible to
+// clients or is documented elsewhere.
+impl<T> OperationInput for Path<T> {}
+impl<T> OperationInput for Extension<T> {}
+impl OperationInput for Option<JwtToken> {}
+impl OperationInput for OptionalJwtToken {}
+impl OperationInput for HeaderMap {}
+impl OperationInput for Bytes {}
+
+/// Documents a handler from the extractors it takes.
+pub trait HandlerDocs<T> {
+    fn describe(operation: &mut Operation);
+}
+
+macro_rules! impl_handler_docs {
+    ($($ty:ident),*) => {
+        impl<F, Fut, $($ty,)*> HandlerDocs<($($ty,)*)> for F
+        where
+            F: FnOnce($($ty),*) -> Fut,
+            $($ty: OperationInput,)*
+        {
+            #[allow(unused_variables)]
+            fn describe(operation: &mut Operation) {
+                $($ty::describe(operation);)*
+            }
+        }
+    };
+}
+
+impl_handler_docs!();
+impl_handler_docs!(T1);
+impl_handler_docs!(T1, T2);
+impl_handler_docs!(T1, T2, T3);
+impl_handler_docs!(T1, T2, T3, T4);
+impl_handler_docs!(T1, T2, T3, T4, T5);
+impl_handler_docs!(T1, T2, T3, T4, T5, T6);
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use std::sync::Arc;
+
+pub mod mock;
+
+pub use mock::MockProvider;
+
+pub type DynPaymentProvider = Arc<dyn PaymentProvider>;
+
+/// A payment gateway. Implementations only talk to the gateway, recording
+/// payments and moving orders along is done by `models::payment`.
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    /// Name stored on every payment record made through this provider.
+    fn name(&self) -> &'static str;
+
+    /// Reserves `amount` in `currency` (ISO 4217) on the customer's payment
+    /// `source` (card token, wallet id, ...) and returns the gateway
+    /// reference of the authorization.
+    async fn authorize(
+        &self,
+        amount: Decimal,
+        currency: &str,
+        source: &str,
+    ) -> Result<String, ProviderError>;
+
+    async fn capture(&self, reference: &str, amount: Decimal) -> Result<(), ProviderError>;
+
+    async fn void(&self, reference: &str) -> Result<(), ProviderError>;
+
+    /// Refunds `amount` of a captured payment and returns the gateway
+    /// reference of the refund.
+    async fn refund(&self, reference: &str, amount: Decimal) -> Result<String, ProviderError>;
+
+    /// Verifies the signature of an incoming webhook and parses its body.
+    fn handle_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<WebhookEvent, ProviderError>;
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+    Declined(String),
+    InvalidSignature,
+    InvalidPayload(String),
+    Unavailable(String),
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::Declined(reason) => write!(f, "payment declined: {}", reason),
+            ProviderError::InvalidSignature => write!(f, "invalid webhook signature"),
+            ProviderError::InvalidPayload(e) => write!(f, "invalid webhook payload: {}", e),
+            ProviderError::Unavailable(e) => write!(f, "payment provider unavailable: {}", e),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WebhookEventKind {
+    #[serde(rename = "payment.captured")]
+    Captured,
+    #[serde(rename = "payment.voided")]
+    Voided,
+    #[serde(rename = "payment.failed")]
+    Failed,
+    #[serde(rename = "refund.succeeded")]
+    Refunded,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookEvent {
+    /// Unique id of the event, used to ignore redelivered webhooks.
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: WebhookEventKind,
+    /// Gateway reference of the payment the event belongs to.
+    pub reference: String,
+    /// Gateway reference of the refund for `refund.succeeded` events.
+    pub refund_reference: Option<String>,
+    pub amount: Option<Decimal>,
+    pub reason: Option<String>,
+}
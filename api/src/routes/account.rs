@@ -0,0 +1,243 @@
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{middleware, Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::auth::{AccessToken, JwtKeys};
+use crate::errors::ApiError;
+use crate::limits;
+use crate::models::address::{Address, AddressInsert, AddressUpdate};
+use crate::models::authentication::JwtToken;
+use crate::models::invoice::Invoice;
+use crate::models::order::{Order, OrderError};
+use crate::models::user::{User, UserError, UserLogin, UserRegister, UserUpdate};
+use crate::openapi::{get, post, ApiRouter};
+use crate::routes::order::{invoice_response, order_error, InvoiceParams};
+
+/// Self-service endpoints, everything below `/account` is scoped to the user
+/// the access token was issued for.
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/account/register",
+            post(register, "Register a customer account")
+                .status(201)
+                .returns::<User>()
+                .layer(middleware::from_fn(limits::strict_rate_limit)),
+        )
+        .route(
+            "/account/login",
+            post(login, "Log in")
+                .returns::<AccessToken>()
+                .layer(middleware::from_fn(limits::strict_rate_limit)),
+        )
+        .route(
+            "/account",
+            get(fetch_profile, "Get the profile")
+                .returns::<User>()
+                .patch(update_profile, "Update the profile")
+                .returns::<User>(),
+        )
+        .route(
+            "/account/address",
+            get(fetch_addresses, "List addresses")
+                .returns::<Vec<Address>>()
+                .post(create_address, "Add an address")
+                .status(201)
+                .returns::<Address>(),
+        )
+        .route(
+            "/account/address/:id",
+            get(fetch_address, "Get an address")
+                .returns::<Address>()
+                .patch(update_address, "Update an address")
+                .returns::<Address>()
+                .delete(delete_address, "Delete an address")
+                .status(204),
+        )
+        .route(
+            "/account/order",
+            get(fetch_orders, "List own orders").returns::<Vec<Order>>(),
+        )
+        .route(
+            "/account/order/:id",
+            get(fetch_order, "Get an own order").returns::<Order>(),
+        )
+        .route(
+            "/account/order/:id/invoice",
+            get(fetch_invoice, "Get the invoice of an own order")
+                .produces("application/pdf")
+                .produces("application/xml")
+                .returns::<Invoice>(),
+        )
+}
+
+fn user_error(e: UserError) -> (StatusCode, Json<ApiError>) {
+    match e {
+        UserError::NotFound(_) => ApiError::not_found(&e.to_string()),
+        UserError::EmailTaken => ApiError::conflict(&e.to_string()),
+        UserError::UnknownCurrency => ApiError::bad_request(&e.to_string()),
+        UserError::InvalidCredentials => {
+            ApiError::with_status(StatusCode::UNAUTHORIZED, &e.to_string())
+        }
+        UserError::Database(e) => ApiError::internal_server_error(&e),
+    }
+}
+
+async fn register(
+    Extension(pool): Extension<PgPool>,
+    Json(user): Json<UserRegister>,
+) -> impl IntoResponse {
+    if let Err(e) = user.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    User::register(user, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(user_error)
+}
+
+async fn login(
+    Extension(pool): Extension<PgPool>,
+    Extension(keys): Extension<JwtKeys>,
+    Json(credentials): Json<UserLogin>,
+) -> impl IntoResponse {
+    if let Err(e) = credentials.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    let user = User::authenticate(credentials, &pool)
+        .await
+        .map_err(user_error)?;
+
+    keys.issue(user.id, user.user_role)
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_profile(Extension(pool): Extension<PgPool>, token: JwtToken) -> impl IntoResponse {
+    User::find_by_id(token.sub, &pool)
+        .await
+        .map(Json)
+        .map_err(user_error)
+}
+
+async fn update_profile(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Json(user): Json<UserUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = user.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    User::update(token.sub, user, &pool)
+        .await
+        .map(Json)
+        .map_err(user_error)
+}
+
+fn address_not_found(id: i64) -> (StatusCode, Json<ApiError>) {
+    ApiError::not_found(&format!("address {} not found", id))
+}
+
+async fn fetch_addresses(Extension(pool): Extension<PgPool>, token: JwtToken) -> impl IntoResponse {
+    Address::find_all(token.sub, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_address(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match Address::find_by_id(id, token.sub, &pool).await {
+        Ok(Some(address)) => Ok(Json(address)),
+        Ok(None) => Err(address_not_found(id)),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn create_address(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Json(address): Json<AddressInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = address.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Address::create(token.sub, address, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update_address(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Path(id): Path<i64>,
+    Json(address): Json<AddressUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = address.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    match Address::update(id, token.sub, address, &pool).await {
+        Ok(Some(address)) => Ok(Json(address)),
+        Ok(None) => Err(address_not_found(id)),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn delete_address(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match Address::delete(id, token.sub, &pool).await {
+        Ok(0) => Err(address_not_found(id)),
+        Ok(r) => Ok((StatusCode::NO_CONTENT, Json(r))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn fetch_orders(Extension(pool): Extension<PgPool>, token: JwtToken) -> impl IntoResponse {
+    Order::find_by_user(token.sub, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+/// Other customers' orders look the same as ones that don't exist.
+async fn fetch_order(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match Order::find_by_id(id, &pool).await {
+        Ok(Some(order)) if order.user_id == Some(token.sub) => Ok(Json(order)),
+        Ok(_) => Err(order_error(OrderError::NotFound(id))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn fetch_invoice(
+    Extension(pool): Extension<PgPool>,
+    token: JwtToken,
+    Path(id): Path<i64>,
+    Query(params): Query<InvoiceParams>,
+) -> impl IntoResponse {
+    match Order::find_by_id(id, &pool).await {
+        Ok(Some(order)) if order.user_id == Some(token.sub) => {
+            invoice_response(id, params, &pool).await
+        }
+        Ok(_) => Err(order_error(OrderError::NotFound(id))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
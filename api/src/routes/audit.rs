@@ -0,0 +1,25 @@
+use axum::extract::Query;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+
+use crate::errors::ApiError;
+use crate::models::audit::{AuditEntry, AuditParams};
+use crate::openapi::{get, ApiRouter};
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new().route(
+        "/audit",
+        get(fetch_all, "List audit log entries").returns::<Vec<AuditEntry>>(),
+    )
+}
+
+async fn fetch_all(
+    Extension(pool): Extension<PgPool>,
+    Query(params): Query<AuditParams>,
+) -> impl IntoResponse {
+    AuditEntry::find(params, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
@@ -1,18 +1,55 @@
 use axum::extract::Path;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::{Extension, Json, Router};
-use sqlx::PgPool;
+use axum::{Extension, Json};
+use sqlx::{PgPool, Postgres, Transaction};
 use validator::Validate;
 
 use crate::errors::ApiError;
-use crate::models::category::{Category, CategoryInsert, CategoryUpdate};
+use crate::models::audit::AuditResource;
+use crate::models::authentication::JwtToken;
+use crate::models::category::{Category, CategoryInsert, CategoryUpdate, TrashedCategory};
+use crate::openapi::{get, post, ApiRouter};
 
-pub fn get_routes() -> Router {
-    Router::new()
-        .route("/category", get(fetch_all).post(create))
-        .route("/category/:id", get(fetch_one).patch(update).delete(delete))
+use super::{begin, commit, record_audit};
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/category",
+            get(fetch_all, "List categories as a tree").returns::<Vec<Category>>(),
+        )
+        .route(
+            "/category/:id",
+            get(fetch_one, "Get a category").returns::<Vec<Category>>(),
+        )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/category",
+            get(fetch_all, "List categories as a tree")
+                .returns::<Vec<Category>>()
+                .post(create, "Create a category")
+                .status(201)
+                .returns::<Category>()
+                .idempotent(),
+        )
+        .route(
+            "/category/:id",
+            get(fetch_one, "Get a category")
+                .returns::<Vec<Category>>()
+                .patch(update, "Update a category")
+                .status(202)
+                .returns::<Category>()
+                .delete(delete, "Move a category and its subtree to the trash")
+                .status(204),
+        )
+        .route(
+            "/category/:id/restore",
+            post(restore, "Restore a category from the trash").returns::<u64>(),
+        )
 }
 
 async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
@@ -31,20 +68,27 @@ async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> i
 
 async fn create(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Json(category): Json<CategoryInsert>,
 ) -> impl IntoResponse {
     if let Err(e) = category.validate() {
         return Err(ApiError::validation_error(e));
     }
 
-    Category::create(category, &pool)
+    let mut tx = begin(&pool).await?;
+    check_parent(None, category.parent_id, &mut tx).await?;
+    let category = Category::create(category, &mut tx)
         .await
-        .map(|r| (StatusCode::CREATED, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, AuditResource::Category(category.id), None).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::CREATED, Json(category)))
 }
 
 async fn update(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Path(id): Path<i64>,
     Json(category): Json<CategoryUpdate>,
 ) -> impl IntoResponse {
@@ -52,15 +96,103 @@ async fn update(
         return Err(ApiError::validation_error(e));
     }
 
-    Category::update(id, category, &pool)
+    let resource = AuditResource::Category(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(|r| (StatusCode::ACCEPTED, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    check_parent(Some(id), category.parent_id, &mut tx).await?;
+    let category = Category::update(id, category, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(category)))
 }
 
-async fn delete(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
-    Category::delete(id, &pool)
+async fn delete(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let mut tx = begin(&pool).await?;
+    let deleted = Category::delete(id, &mut tx)
         .await
-        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_trash_audit(&mut tx, &token, &deleted).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::NO_CONTENT, Json(deleted.len() as u64)))
+}
+
+async fn restore(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let mut tx = begin(&pool).await?;
+    let restored = Category::restore(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_trash_audit(&mut tx, &token, &restored).await?;
+    commit(tx).await?;
+
+    Ok(Json(restored.len() as u64))
+}
+
+/// Records every category a delete or restore cascaded to, not only the one
+/// it was asked for.
+async fn record_trash_audit(
+    tx: &mut Transaction<'_, Postgres>,
+    token: &Option<JwtToken>,
+    categories: &[TrashedCategory],
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    for category in categories {
+        record_audit(
+            tx,
+            token,
+            AuditResource::Category(category.id),
+            Some(category.before.clone()),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Categories can't be filed under one that's in the trash, nor under
+/// themselves or one of their subcategories.
+async fn check_parent(
+    id: Option<i64>,
+    parent_id: Option<i64>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+
+    match Category::exists(parent_id, &mut *tx).await {
+        Ok(true) => (),
+        Ok(false) => {
+            return Err(ApiError::bad_request(&format!(
+                "parent category {} not found",
+                parent_id
+            )))
+        }
+        Err(e) => return Err(ApiError::internal_server_error(&e)),
+    }
+
+    let Some(id) = id else {
+        return Ok(());
+    };
+    match Category::is_in_subtree(parent_id, id, &mut *tx).await {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(ApiError::bad_request(&format!(
+            "category {} can't be filed under itself or one of its subcategories",
+            id
+        ))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
 }
@@ -0,0 +1,89 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::currency::{Currency, CurrencyInsert, CurrencyUpdate};
+use crate::openapi::{get, ApiRouter};
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/currency",
+            get(fetch_all, "List currencies").returns::<Vec<Currency>>(),
+        )
+        .route(
+            "/currency/:code",
+            get(fetch_one, "Get a currency").returns::<Currency>(),
+        )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/currency",
+            get(fetch_all, "List currencies")
+                .returns::<Vec<Currency>>()
+                .post(create, "Add a currency")
+                .status(201)
+                .returns::<Currency>(),
+        )
+        .route(
+            "/currency/:code",
+            get(fetch_one, "Get a currency")
+                .returns::<Currency>()
+                .patch(update, "Update a currency")
+                .returns::<Currency>(),
+        )
+}
+
+async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    Currency::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_one(
+    Extension(pool): Extension<PgPool>,
+    Path(code): Path<String>,
+) -> impl IntoResponse {
+    match Currency::find_by_code(&code, &pool).await {
+        Ok(Some(currency)) => Ok(Json(currency)),
+        Ok(None) => Err(ApiError::not_found(&format!("currency {} not found", code))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn create(
+    Extension(pool): Extension<PgPool>,
+    Json(currency): Json<CurrencyInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = currency.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Currency::create(currency, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update(
+    Extension(pool): Extension<PgPool>,
+    Path(code): Path<String>,
+    Json(currency): Json<CurrencyUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = currency.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    match Currency::update(&code, currency, &pool).await {
+        Ok(Some(currency)) => Ok(Json(currency)),
+        Ok(None) => Err(ApiError::not_found(&format!("currency {} not found", code))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
@@ -0,0 +1,114 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::customer_group::{CustomerGroup, CustomerGroupInsert, CustomerGroupUpdate};
+use crate::openapi::{get, put, ApiRouter};
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/customer-group",
+            get(fetch_all, "List customer groups")
+                .returns::<Vec<CustomerGroup>>()
+                .post(create, "Create a customer group")
+                .status(201)
+                .returns::<CustomerGroup>(),
+        )
+        .route(
+            "/customer-group/:id",
+            get(fetch_one, "Get a customer group")
+                .returns::<CustomerGroup>()
+                .patch(update, "Update a customer group")
+                .returns::<CustomerGroup>()
+                .delete(delete, "Delete a customer group")
+                .status(204),
+        )
+        .route(
+            "/customer-group/:id/user/:user_id",
+            put(add_user, "Move a customer into the group")
+                .returns::<u64>()
+                .delete(remove_user, "Remove a customer from the group")
+                .status(204),
+        )
+}
+
+fn not_found(id: i64) -> (StatusCode, Json<ApiError>) {
+    ApiError::not_found(&format!("customer group {} not found", id))
+}
+
+async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    CustomerGroup::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    match CustomerGroup::find_by_id(id, &pool).await {
+        Ok(Some(group)) => Ok(Json(group)),
+        Ok(None) => Err(not_found(id)),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn create(
+    Extension(pool): Extension<PgPool>,
+    Json(group): Json<CustomerGroupInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = group.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    CustomerGroup::create(group, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    Json(group): Json<CustomerGroupUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = group.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    match CustomerGroup::update(id, group, &pool).await {
+        Ok(Some(group)) => Ok(Json(group)),
+        Ok(None) => Err(not_found(id)),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn delete(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    CustomerGroup::delete(id, &pool)
+        .await
+        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn add_user(
+    Extension(pool): Extension<PgPool>,
+    Path((id, user_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    CustomerGroup::add_user(id, user_id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn remove_user(
+    Extension(pool): Extension<PgPool>,
+    Path((id, user_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    CustomerGroup::remove_user(id, user_id, &pool)
+        .await
+        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
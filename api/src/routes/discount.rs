@@ -1,20 +1,51 @@
 use axum::extract::Path;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::{Extension, Json, Router};
+use axum::{Extension, Json};
 use sqlx::PgPool;
 use validator::Validate;
 
 use crate::errors::ApiError;
+use crate::models::audit::AuditResource;
+use crate::models::authentication::JwtToken;
 use crate::models::discount::{Discount, DiscountInsert, DiscountUpdate};
+use crate::openapi::{get, post, ApiRouter};
 
-pub fn get_routes() -> Router {
-    Router::new()
-        .route("/discount", get(fetch_all).post(create))
-        .route("/discount/:id", get(fetch_one).patch(update).delete(delete))
-        .route("/discount/:id/set-active", get(set_active))
-        .route("/discount/:id/set-inactive", get(set_inactive))
+use super::{begin, commit, record_audit};
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/discount",
+            get(fetch_all, "List discounts")
+                .returns::<Vec<Discount>>()
+                .post(create, "Create a discount")
+                .status(201)
+                .returns::<Discount>()
+                .idempotent(),
+        )
+        .route(
+            "/discount/:id",
+            get(fetch_one, "Get a discount")
+                .returns::<Discount>()
+                .patch(update, "Update a discount")
+                .status(202)
+                .returns::<Discount>()
+                .delete(delete, "Move a discount to the trash")
+                .status(204),
+        )
+        .route(
+            "/discount/:id/restore",
+            post(restore, "Restore a discount from the trash").returns::<u64>(),
+        )
+        .route(
+            "/discount/:id/set-active",
+            get(set_active, "Activate a discount").returns::<u64>(),
+        )
+        .route(
+            "/discount/:id/set-inactive",
+            get(set_inactive, "Deactivate a discount").returns::<u64>(),
+        )
 }
 
 async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
@@ -33,37 +64,66 @@ async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> i
 
 async fn create(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Json(discount): Json<DiscountInsert>,
 ) -> impl IntoResponse {
     if let Err(e) = discount.validate() {
         return Err(ApiError::validation_error(e));
     }
 
-    Discount::create(discount, &pool)
+    let mut tx = begin(&pool).await?;
+    let discount = Discount::create(discount, &mut tx)
         .await
-        .map(|r| (StatusCode::CREATED, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, AuditResource::Discount(discount.id), None).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::CREATED, Json(discount)))
 }
 
-async fn set_active(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
-    Discount::set_active(id, &pool)
+async fn set_active(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::Discount(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(|r| (StatusCode::OK, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let updated = Discount::set_active(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::OK, Json(updated)))
 }
 
 async fn set_inactive(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    Discount::set_inactive(id, &pool)
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::Discount(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(|r| (StatusCode::OK, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let updated = Discount::set_inactive(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::OK, Json(updated)))
 }
 
 async fn update(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Path(id): Path<i64>,
     Json(discount): Json<DiscountUpdate>,
 ) -> impl IntoResponse {
@@ -71,15 +131,57 @@ async fn update(
         return Err(ApiError::validation_error(e));
     }
 
-    Discount::update(id, discount, &pool)
+    let resource = AuditResource::Discount(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(|r| (StatusCode::ACCEPTED, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let discount = Discount::update(id, discount, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(discount)))
 }
 
-async fn delete(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
-    Discount::delete(id, &pool)
+async fn delete(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::Discount(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let deleted = Discount::delete(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::NO_CONTENT, Json(deleted)))
+}
+
+async fn restore(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::Discount(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let restored = Discount::restore(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(restored))
 }
@@ -0,0 +1,78 @@
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::models::authentication::AdminToken;
+use crate::{metrics, migrations};
+
+/// How long the readiness probe waits for the database.
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    database: String,
+    pending_migrations: Option<usize>,
+}
+
+/// Probes for the orchestrator, served outside `/api/v1`, the metrics need
+/// an admin token.
+pub fn get_probe_routes() -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/metrics", get(fetch_metrics))
+}
+
+/// The process is up, without looking at its dependencies.
+async fn health() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Whether the database answers and has every migration of the binary.
+async fn ready(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    let status = tokio::time::timeout(READY_TIMEOUT, migrations::status(&pool))
+        .await
+        .unwrap_or_else(|_| Err("timed out".to_string()));
+
+    let readiness = match status {
+        Ok(status) => {
+            let pending = status.iter().filter(|m| !m.applied).count();
+            Readiness {
+                ready: pending == 0,
+                database: "ok".to_string(),
+                pending_migrations: Some(pending),
+            }
+        }
+        Err(e) => Readiness {
+            ready: false,
+            database: e,
+            pending_migrations: None,
+        },
+    };
+
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(readiness))
+}
+
+async fn fetch_metrics(
+    _: AdminToken,
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<Config>,
+) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&pool, config.db_max_connections),
+    )
+}
@@ -1,11 +1,140 @@
+use axum::http::header::{CACHE_CONTROL, VARY};
+use axum::http::{HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::{middleware, Json};
 use serde::Deserialize;
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
 
+use crate::errors::ApiError;
+use crate::models::audit::{AuditEntry, AuditResource};
+use crate::models::authentication::{AdminToken, JwtToken};
+use crate::openapi::{self, ApiRouter, ApiSchema};
+use crate::{limits, metrics};
+
+pub mod account;
+pub mod audit;
 pub mod category;
+pub mod currency;
+pub mod customer_group;
 pub mod discount;
+pub mod health;
+pub mod order;
+pub mod order_return;
+pub mod payment;
 pub mod product;
 pub mod product_inventory;
+pub mod shipping;
+pub mod shop_profile;
+pub mod tax;
+pub mod trash;
+pub mod web_admin;
+
+/// The routes below `/api/v1`, the OpenAPI document is generated from them.
+pub fn api_routes() -> ApiRouter {
+    // Read-only catalog, the same for every shopper in a currency.
+    let storefront = ApiRouter::new()
+        .merge(category::get_routes())
+        .merge(product::get_routes())
+        .merge(currency::get_routes())
+        .merge(shop_profile::get_routes())
+        .merge(openapi::get_routes())
+        .layer(middleware::from_fn(public_cache));
+
+    let customer = ApiRouter::new()
+        .merge(account::get_routes())
+        .merge(order::get_routes())
+        .merge(payment::get_routes())
+        .merge(order_return::get_routes())
+        .merge(shipping::get_routes());
 
-#[derive(Deserialize)]
+    let admin = ApiRouter::new()
+        .merge(discount::get_admin_routes())
+        .merge(category::get_admin_routes())
+        .merge(product_inventory::get_admin_routes())
+        .merge(product::get_admin_routes())
+        .merge(order::get_admin_routes())
+        .merge(payment::get_admin_routes())
+        .merge(order_return::get_admin_routes())
+        .merge(shipping::get_admin_routes())
+        .merge(tax::get_admin_routes())
+        .merge(shop_profile::get_admin_routes())
+        .merge(currency::get_admin_routes())
+        .merge(customer_group::get_admin_routes())
+        .merge(audit::get_admin_routes())
+        .merge(trash::get_admin_routes())
+        .route_layer(middleware::from_fn(limits::limit_body))
+        .route_layer(middleware::from_extractor::<AdminToken>())
+        .route_layer(middleware::from_fn(metrics::record_route));
+
+    storefront
+        .merge(customer)
+        .route_layer(middleware::from_fn(limits::limit_body))
+        .route_layer(middleware::from_fn(metrics::record_route))
+        .nest("/admin", admin)
+        .layer(middleware::from_fn(limits::rate_limit))
+}
+
+#[derive(Deserialize, ApiSchema)]
 pub struct Params {
     category_id: Option<i64>,
 }
+
+/// Starts the transaction an admin change and its audit entry are written in.
+pub async fn begin(
+    pool: &PgPool,
+) -> Result<Transaction<'static, Postgres>, (StatusCode, Json<ApiError>)> {
+    pool.begin()
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e.to_string()))
+}
+
+pub async fn commit(tx: Transaction<'_, Postgres>) -> Result<(), (StatusCode, Json<ApiError>)> {
+    tx.commit()
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e.to_string()))
+}
+
+/// Records an admin change of `resource` in the audit log, comparing its
+/// current state with the one before the change. Runs in the transaction of
+/// the change, which fails along with the audit entry.
+pub async fn record_audit(
+    tx: &mut Transaction<'_, Postgres>,
+    token: &Option<JwtToken>,
+    resource: AuditResource<'_>,
+    before: Option<Value>,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    let after = resource
+        .snapshot(&mut *tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+
+    AuditEntry::record(
+        token.as_ref().map(|t| t.sub),
+        &resource,
+        before,
+        after,
+        &mut *tx,
+    )
+    .await
+    .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+/// Lets browsers and proxies cache successful storefront reads for a
+/// minute. Prices follow the selected currency, which comes from the
+/// `X-Currency` header or the customer's token.
+pub async fn public_cache<B>(request: Request<B>, next: Next<B>) -> Response {
+    let mut response = next.run(request).await;
+
+    if response.status().is_success() {
+        let headers = response.headers_mut();
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=60"),
+        );
+        headers.insert(VARY, HeaderValue::from_static("X-Currency, Authorization"));
+    }
+
+    response
+}
@@ -0,0 +1,333 @@
+use axum::extract::{Path, Query};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use serde::Deserialize;
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::authentication::OptionalJwtToken;
+use crate::models::currency::SelectedCurrency;
+use crate::models::invoice::Invoice;
+use crate::models::order::{
+    Order, OrderAccess, OrderError, OrderInsert, OrderQuote, OrderStatus, OrderStatusChange,
+    OrderTransition, PlacedOrder,
+};
+use crate::models::payment::Payment;
+use crate::models::tax::TaxSettings;
+use crate::openapi::{get, post, ApiRouter, ApiSchema};
+use crate::payments::DynPaymentProvider;
+
+use super::payment::payment_error;
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/order",
+            post(create, "Place an order")
+                .status(201)
+                .returns::<PlacedOrder>()
+                .idempotent(),
+        )
+        .route(
+            "/order/quote",
+            post(quote, "Price a cart without ordering").returns::<OrderQuote>(),
+        )
+        .route(
+            "/order/:id/invoice",
+            get(fetch_accessible_invoice, "Get the invoice of an order")
+                .produces("application/pdf")
+                .produces("application/xml")
+                .returns::<Invoice>(),
+        )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/order",
+            get(fetch_all, "List orders").returns::<Vec<Order>>(),
+        )
+        .route(
+            "/order/:id",
+            get(fetch_one, "Get an order").returns::<Order>(),
+        )
+        .route(
+            "/order/:id/history",
+            get(fetch_history, "List status changes of an order")
+                .returns::<Vec<OrderStatusChange>>(),
+        )
+        .route(
+            "/order/:id/invoice",
+            get(fetch_invoice, "Get the invoice of an order")
+                .produces("application/pdf")
+                .produces("application/xml")
+                .returns::<Invoice>(),
+        )
+        .route(
+            "/order/:id/pay",
+            post(pay, "Mark an order paid").returns::<Order>(),
+        )
+        .route(
+            "/order/:id/pick",
+            post(pick, "Start picking an order").returns::<Order>(),
+        )
+        .route(
+            "/order/:id/ship",
+            post(ship, "Mark an order shipped").returns::<Order>(),
+        )
+        .route(
+            "/order/:id/deliver",
+            post(deliver, "Mark an order delivered").returns::<Order>(),
+        )
+        .route(
+            "/order/:id/cancel",
+            post(cancel, "Cancel an order").returns::<Order>(),
+        )
+        .route(
+            "/order/:id/refund",
+            post(refund, "Refund an order").returns::<Order>(),
+        )
+}
+
+pub fn order_error(e: OrderError) -> (StatusCode, Json<ApiError>) {
+    match e {
+        OrderError::NotFound(_) => ApiError::not_found(&e.to_string()),
+        OrderError::ProductNotFound(_)
+        | OrderError::ProductNotPriced(_)
+        | OrderError::AddressNotFound(_) => ApiError::bad_request(&e.to_string()),
+        OrderError::OutOfStock(_)
+        | OrderError::ShippingUnavailable(_)
+        | OrderError::NoTaxRate { .. }
+        | OrderError::IllegalTransition { .. } => ApiError::conflict(&e.to_string()),
+        OrderError::Database(e) => ApiError::internal_server_error(&e),
+    }
+}
+
+async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    Order::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    match Order::find_by_id(id, &pool).await {
+        Ok(Some(order)) => Ok(Json(order)),
+        Ok(None) => Err(order_error(OrderError::NotFound(id))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn fetch_history(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    Order::history(id, &pool)
+        .await
+        .map(Json)
+        .map_err(order_error)
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct InvoiceParams {
+    /// `pdf` (default), `xml` for the UBL e-invoice or `json` for the
+    /// invoice record.
+    format: Option<String>,
+}
+
+/// Serves the invoice of an order in the requested format.
+pub async fn invoice_response(
+    id: i64,
+    params: InvoiceParams,
+    pool: &PgPool,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let not_issued = || ApiError::not_found(&format!("order {} has no invoice", id));
+    let format = params.format.as_deref().unwrap_or("pdf");
+
+    if format == "json" {
+        return match Invoice::find_by_order(id, pool).await {
+            Ok(Some(invoice)) => Ok(Json(invoice).into_response()),
+            Ok(None) => Err(not_issued()),
+            Err(e) => Err(ApiError::internal_server_error(&e)),
+        };
+    }
+
+    if format != "pdf" && format != "xml" {
+        return Err(ApiError::bad_request(&format!(
+            "unknown invoice format: {}",
+            format
+        )));
+    }
+
+    let document = Invoice::find_document(id, pool)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?
+        .ok_or_else(not_issued)?;
+
+    let (content_type, body) = match format {
+        "pdf" => ("application/pdf", document.pdf),
+        _ => ("application/xml", document.xml.into_bytes()),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", document.number, format),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn fetch_invoice(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    Query(params): Query<InvoiceParams>,
+) -> impl IntoResponse {
+    invoice_response(id, params, &pool).await
+}
+
+/// Customers download the invoices of their own orders, guests send the
+/// order's access token.
+async fn fetch_accessible_invoice(
+    Extension(pool): Extension<PgPool>,
+    OrderAccess(id): OrderAccess,
+    Query(params): Query<InvoiceParams>,
+) -> impl IntoResponse {
+    invoice_response(id, params, &pool).await
+}
+
+async fn quote(
+    Extension(pool): Extension<PgPool>,
+    Extension(tax_settings): Extension<TaxSettings>,
+    OptionalJwtToken(token): OptionalJwtToken,
+    SelectedCurrency(currency): SelectedCurrency,
+    Json(order): Json<OrderInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = order.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Order::quote(
+        &order,
+        token.map(|t| t.sub),
+        &currency,
+        &tax_settings,
+        &pool,
+    )
+    .await
+    .map(Json)
+    .map_err(order_error)
+}
+
+/// Guests can check out too, a token links the order to the customer.
+async fn create(
+    Extension(pool): Extension<PgPool>,
+    Extension(tax_settings): Extension<TaxSettings>,
+    OptionalJwtToken(token): OptionalJwtToken,
+    SelectedCurrency(currency): SelectedCurrency,
+    Json(order): Json<OrderInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = order.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Order::create(order, token.map(|t| t.sub), &currency, &tax_settings, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(order_error)
+}
+
+async fn transition(
+    pool: PgPool,
+    id: i64,
+    to: OrderStatus,
+    input: Option<Json<OrderTransition>>,
+) -> Result<Json<Order>, (StatusCode, Json<ApiError>)> {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Order::transition(id, to, input, &pool)
+        .await
+        .map(Json)
+        .map_err(order_error)
+}
+
+async fn pay(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<OrderTransition>>,
+) -> impl IntoResponse {
+    transition(pool, id, OrderStatus::Paid, input).await
+}
+
+async fn pick(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<OrderTransition>>,
+) -> impl IntoResponse {
+    transition(pool, id, OrderStatus::Picking, input).await
+}
+
+async fn ship(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<OrderTransition>>,
+) -> impl IntoResponse {
+    transition(pool, id, OrderStatus::Shipped, input).await
+}
+
+async fn deliver(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<OrderTransition>>,
+) -> impl IntoResponse {
+    transition(pool, id, OrderStatus::Delivered, input).await
+}
+
+/// Voids the authorized payments of the order, orders with captured money
+/// have to be refunded instead.
+async fn cancel(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    Path(id): Path<i64>,
+    input: Option<Json<OrderTransition>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Payment::cancel_order(id, input, provider.as_ref(), &pool)
+        .await
+        .map(Json)
+        .map_err(payment_error)
+}
+
+/// Refunds the payments of the order with the provider.
+async fn refund(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    Path(id): Path<i64>,
+    input: Option<Json<OrderTransition>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Payment::refund_order(id, input, provider.as_ref(), &pool)
+        .await
+        .map(Json)
+        .map_err(payment_error)
+}
@@ -0,0 +1,218 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::credit_note::CreditNote;
+use crate::models::order::OrderAccess;
+use crate::models::order_return::{
+    OrderReturn, ReturnDecision, ReturnError, ReturnInsert, ReturnReceive, ReturnRefund,
+};
+use crate::openapi::{get, post, ApiRouter};
+use crate::payments::DynPaymentProvider;
+
+use super::payment::payment_error;
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new().route(
+        "/order/:id/return",
+        post(create, "Request a return")
+            .status(201)
+            .returns::<OrderReturn>(),
+    )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/return",
+            get(fetch_all, "List returns").returns::<Vec<OrderReturn>>(),
+        )
+        .route(
+            "/return/:id",
+            get(fetch_one, "Get a return").returns::<OrderReturn>(),
+        )
+        .route(
+            "/return/:id/approve",
+            post(approve, "Approve a return").returns::<OrderReturn>(),
+        )
+        .route(
+            "/return/:id/reject",
+            post(reject, "Reject a return").returns::<OrderReturn>(),
+        )
+        .route(
+            "/return/:id/receive",
+            post(receive, "Receive returned goods").returns::<OrderReturn>(),
+        )
+        .route(
+            "/return/:id/refund",
+            post(refund, "Refund a return")
+                .status(201)
+                .returns::<CreditNote>(),
+        )
+        .route(
+            "/order/:id/return",
+            get(fetch_by_order, "List returns of an order").returns::<Vec<OrderReturn>>(),
+        )
+        .route(
+            "/order/:id/credit-note",
+            get(fetch_credit_notes, "List credit notes of an order").returns::<Vec<CreditNote>>(),
+        )
+        .route(
+            "/credit-note/:id",
+            get(fetch_credit_note, "Get a credit note").returns::<CreditNote>(),
+        )
+}
+
+fn return_error(e: ReturnError) -> (StatusCode, Json<ApiError>) {
+    match e {
+        ReturnError::NotFound(_) | ReturnError::OrderNotFound(_) => {
+            ApiError::not_found(&e.to_string())
+        }
+        ReturnError::OrderNotReturnable(_)
+        | ReturnError::IllegalTransition { .. }
+        | ReturnError::NoRefundablePayment(_) => ApiError::conflict(&e.to_string()),
+        ReturnError::InvalidLine(_) | ReturnError::InvalidAmount(_) => {
+            ApiError::bad_request(&e.to_string())
+        }
+        ReturnError::Payment(e) => payment_error(e),
+        ReturnError::Database(e) => ApiError::internal_server_error(&e),
+    }
+}
+
+async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    OrderReturn::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    match OrderReturn::find_by_id(id, &pool).await {
+        Ok(Some(r)) => Ok(Json(r)),
+        Ok(None) => Err(return_error(ReturnError::NotFound(id))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn fetch_by_order(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    OrderReturn::find_by_order(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+/// Customers return their own orders, guests send the order's access token.
+async fn create(
+    Extension(pool): Extension<PgPool>,
+    OrderAccess(id): OrderAccess,
+    Json(input): Json<ReturnInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    OrderReturn::create(id, input, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(return_error)
+}
+
+async fn approve(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<ReturnDecision>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    OrderReturn::approve(id, input, &pool)
+        .await
+        .map(Json)
+        .map_err(return_error)
+}
+
+async fn reject(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<ReturnDecision>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    OrderReturn::reject(id, input, &pool)
+        .await
+        .map(Json)
+        .map_err(return_error)
+}
+
+async fn receive(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    input: Option<Json<ReturnReceive>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    OrderReturn::receive(id, input, &pool)
+        .await
+        .map(Json)
+        .map_err(return_error)
+}
+
+async fn refund(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    Path(id): Path<i64>,
+    input: Option<Json<ReturnRefund>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    OrderReturn::refund(id, input, provider.as_ref(), &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(return_error)
+}
+
+async fn fetch_credit_notes(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    CreditNote::find_by_order(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_credit_note(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match CreditNote::find_by_id(id, &pool).await {
+        Ok(Some(c)) => Ok(Json(c)),
+        Ok(None) => Err(ApiError::not_found(&format!(
+            "credit note {} not found",
+            id
+        ))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
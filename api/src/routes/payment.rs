@@ -0,0 +1,171 @@
+use axum::body::Bytes;
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::order::OrderAccess;
+use crate::models::payment::{Payment, PaymentError, PaymentInsert, PaymentRefundInsert};
+use crate::openapi::{get, post, ApiRouter};
+use crate::payments::{DynPaymentProvider, ProviderError};
+
+use super::order::order_error;
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/order/:id/payment",
+            post(authorize, "Pay for an order")
+                .status(201)
+                .returns::<Payment>(),
+        )
+        .route(
+            "/payment/webhook",
+            post(webhook, "Payment provider notifications").status(204),
+        )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/order/:id/payment",
+            get(fetch_by_order, "List payments of an order").returns::<Vec<Payment>>(),
+        )
+        .route(
+            "/payment/:id",
+            get(fetch_one, "Get a payment").returns::<Payment>(),
+        )
+        .route(
+            "/payment/:id/capture",
+            post(capture, "Capture an authorized payment").returns::<Payment>(),
+        )
+        .route(
+            "/payment/:id/void",
+            post(void, "Void an authorized payment").returns::<Payment>(),
+        )
+        .route(
+            "/payment/:id/refund",
+            post(refund, "Refund a captured payment").returns::<Payment>(),
+        )
+}
+
+pub fn payment_error(e: PaymentError) -> (StatusCode, Json<ApiError>) {
+    match e {
+        PaymentError::NotFound(_) | PaymentError::UnknownReference(_) => {
+            ApiError::not_found(&e.to_string())
+        }
+        PaymentError::AlreadyAuthorized(_) | PaymentError::InvalidState { .. } => {
+            ApiError::conflict(&e.to_string())
+        }
+        PaymentError::InvalidAmount(_) => ApiError::bad_request(&e.to_string()),
+        PaymentError::Provider(ProviderError::Declined(_)) => {
+            ApiError::with_status(StatusCode::PAYMENT_REQUIRED, &e.to_string())
+        }
+        PaymentError::Provider(ProviderError::InvalidSignature) => {
+            ApiError::with_status(StatusCode::UNAUTHORIZED, &e.to_string())
+        }
+        PaymentError::Provider(ProviderError::InvalidPayload(_)) => {
+            ApiError::bad_request(&e.to_string())
+        }
+        PaymentError::Provider(ProviderError::Unavailable(_)) => {
+            ApiError::with_status(StatusCode::BAD_GATEWAY, &e.to_string())
+        }
+        PaymentError::Order(e) => order_error(e),
+        PaymentError::Database(e) => ApiError::internal_server_error(&e),
+    }
+}
+
+async fn fetch_by_order(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    Payment::find_by_order(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    match Payment::find_by_id(id, &pool).await {
+        Ok(Some(payment)) => Ok(Json(payment)),
+        Ok(None) => Err(payment_error(PaymentError::NotFound(id))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+/// Customers pay for their own orders, guests send the order's access token.
+async fn authorize(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    OrderAccess(id): OrderAccess,
+    Json(payment): Json<PaymentInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = payment.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Payment::authorize(id, payment, provider.as_ref(), &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(payment_error)
+}
+
+async fn capture(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    Payment::capture(id, provider.as_ref(), &pool)
+        .await
+        .map(Json)
+        .map_err(payment_error)
+}
+
+async fn void(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    Payment::void(id, provider.as_ref(), &pool)
+        .await
+        .map(Json)
+        .map_err(payment_error)
+}
+
+async fn refund(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    Path(id): Path<i64>,
+    input: Option<Json<PaymentRefundInsert>>,
+) -> impl IntoResponse {
+    let input = input.map(|Json(i)| i).unwrap_or_default();
+
+    if let Err(e) = input.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    Payment::refund(id, input, provider.as_ref(), &pool)
+        .await
+        .map(Json)
+        .map_err(payment_error)
+}
+
+async fn webhook(
+    Extension(pool): Extension<PgPool>,
+    Extension(provider): Extension<DynPaymentProvider>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let event = match provider.handle_webhook(&headers, &body) {
+        Ok(event) => event,
+        Err(e) => return Err(payment_error(e.into())),
+    };
+
+    Payment::handle_webhook(event, provider.as_ref(), &pool)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(payment_error)
+}
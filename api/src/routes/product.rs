@@ -1,33 +1,149 @@
 use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::{Extension, Json, Router};
+use axum::{Extension, Json};
 use sqlx::PgPool;
 use validator::Validate;
 
 use crate::errors::ApiError;
+use crate::models::audit::AuditResource;
+use crate::models::authentication::JwtToken;
+use crate::models::currency::{ProductPrice, ProductPriceUpdate, SelectedCurrency};
+use crate::models::price_history::PriceChange;
+use crate::models::price_tier::{PriceTier, PriceTierInsert};
 use crate::models::product::{Product, ProductInsert, ProductUpdate};
 use crate::models::product_inventory::{ProductInventory, ProductInventoryInsert};
+use crate::models::storefront::StorefrontProduct;
+use crate::openapi::{self, get, post, put, ApiRouter};
 
-use super::Params;
+use super::{begin, commit, record_audit, Params};
 
-pub fn get_routes() -> Router {
-    Router::new()
-        .route("/product", get(fetch_all).post(create))
-        .route("/product/query", get(fetch_by_category))
-        .route("/product/:id", get(fetch_one).patch(update).delete(delete))
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/product",
+            get(fetch_published, "List published products").returns::<Vec<StorefrontProduct>>(),
+        )
+        .route(
+            "/product/query",
+            get(
+                fetch_published_by_category,
+                "List published products of a category",
+            )
+            .returns::<Vec<StorefrontProduct>>(),
+        )
+        .route(
+            "/product/:id",
+            get(fetch_published_one, "Get a published product").returns::<StorefrontProduct>(),
+        )
 }
 
-async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
-    Product::find_all(&pool)
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/product",
+            get(fetch_all, "List products of every status")
+                .returns::<Vec<Product>>()
+                .post(create, "Create a product")
+                .status(201)
+                .returns::<Product>()
+                .idempotent(),
+        )
+        .route(
+            "/product/query",
+            get(fetch_by_category, "List products of a category").returns::<Vec<Product>>(),
+        )
+        .route(
+            "/product/:id",
+            get(fetch_one, "Get a product")
+                .returns::<Product>()
+                .patch(update, "Update a product")
+                .returns::<Product>()
+                .delete(delete, "Move a product to the trash")
+                .returns::<u64>(),
+        )
+        .route(
+            "/product/:id/restore",
+            post(restore, "Restore a product from the trash").returns::<u64>(),
+        )
+        .route(
+            "/product/:id/price",
+            get(fetch_prices, "List a product's price lists").returns::<Vec<ProductPrice>>(),
+        )
+        .route(
+            "/product/:id/price/:currency",
+            put(set_price, "Set a product's price in a currency")
+                .returns::<ProductPrice>()
+                .delete(delete_price, "Remove a product's price in a currency")
+                .status(204),
+        )
+        .route(
+            "/product/:id/price-history",
+            get(fetch_price_history, "List a product's price changes")
+                .returns::<Vec<PriceChange>>(),
+        )
+        .route(
+            "/product/:id/price-tier",
+            get(fetch_tiers, "List a product's price tiers")
+                .returns::<Vec<PriceTier>>()
+                .post(set_tier, "Set a price tier")
+                .returns::<PriceTier>(),
+        )
+        .route(
+            "/product/:id/price-tier/:tier_id",
+            openapi::delete(delete_tier, "Delete a price tier").status(204),
+        )
+}
+
+async fn fetch_published(
+    Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
+) -> impl IntoResponse {
+    StorefrontProduct::find_all(&currency, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_published_one(
+    Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match StorefrontProduct::find_by_id(id, &currency, &pool).await {
+        Ok(Some(product)) => Ok(Json(product)),
+        Ok(None) => Err(ApiError::not_found(&format!("product {} not found", id))),
+        Err(e) => Err(ApiError::internal_server_error(&e)),
+    }
+}
+
+async fn fetch_published_by_category(
+    Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
+    Query(params): Query<Params>,
+) -> impl IntoResponse {
+    StorefrontProduct::find_by_category(params.category_id, &currency, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_all(
+    Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
+) -> impl IntoResponse {
+    Product::find_all(&currency, &pool)
         .await
         .map(Json)
         .map_err(|e| ApiError::internal_server_error(&e))
 }
 
-async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
-    Product::find_by_id(id, &pool)
+async fn fetch_one(
+    Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    Product::find_by_id(id, &currency, &pool)
         .await
         .map(Json)
         .map_err(|e| ApiError::internal_server_error(&e))
@@ -35,9 +151,10 @@ async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> i
 
 pub async fn fetch_by_category(
     Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
     Query(params): Query<Params>,
 ) -> impl IntoResponse {
-    Product::find_by_category(params.category_id, &pool)
+    Product::find_by_category(params.category_id, &currency, &pool)
         .await
         .map(Json)
         .map_err(|e| ApiError::internal_server_error(&e))
@@ -45,30 +162,45 @@ pub async fn fetch_by_category(
 
 async fn create(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    SelectedCurrency(currency): SelectedCurrency,
     Json(mut product): Json<ProductInsert>,
 ) -> impl IntoResponse {
     if let Err(e) = product.validate() {
         return Err(ApiError::validation_error(e));
     }
 
-    let inventory = match ProductInventory::create(ProductInventoryInsert::new(0), &pool)
+    let mut tx = begin(&pool).await?;
+    let inventory = match ProductInventory::create(ProductInventoryInsert::new(0), &mut tx)
         .await
         .map_err(|e| ApiError::internal_server_error(&e))
     {
         Ok(i) => i,
         Err(e) => return Err(e),
     };
+    record_audit(
+        &mut tx,
+        &token,
+        AuditResource::ProductInventory(inventory.id),
+        None,
+    )
+    .await?;
 
     product.inventory_id = Some(inventory.id);
 
-    Product::create(product, &pool)
+    let product = Product::create(product, &currency, &mut tx)
         .await
-        .map(|r| (StatusCode::CREATED, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, AuditResource::Product(product.id), None).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::CREATED, Json(product)))
 }
 
 async fn update(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    SelectedCurrency(currency): SelectedCurrency,
     Path(id): Path<i64>,
     Json(product): Json<ProductUpdate>,
 ) -> impl IntoResponse {
@@ -76,15 +208,179 @@ async fn update(
         return Err(ApiError::validation_error(e));
     }
 
-    Product::update(id, product, &pool)
+    let resource = AuditResource::Product(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let product = Product::update(id, product, &currency, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(product))
+}
+
+async fn delete(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::Product(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let deleted = Product::delete(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(deleted))
+}
+
+async fn fetch_prices(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    ProductPrice::find_by_product(id, &pool)
         .await
         .map(Json)
         .map_err(|e| ApiError::internal_server_error(&e))
 }
 
-async fn delete(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
-    Product::delete(id, &pool)
+async fn set_price(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path((id, currency)): Path<(i64, String)>,
+    Json(price): Json<ProductPriceUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = price.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    let resource = AuditResource::ProductPrice(id, &currency);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let price = ProductPrice::set(id, &currency, price, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(price))
+}
+
+async fn delete_price(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path((id, currency)): Path<(i64, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::ProductPrice(id, &currency);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let deleted = ProductPrice::delete(id, &currency, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::NO_CONTENT, Json(deleted)))
+}
+
+async fn fetch_price_history(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    PriceChange::find_by_product(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_tiers(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    PriceTier::find_by_product(id, &pool)
         .await
         .map(Json)
         .map_err(|e| ApiError::internal_server_error(&e))
 }
+
+async fn set_tier(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+    Json(tier): Json<PriceTierInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = tier.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    let mut tx = begin(&pool).await?;
+    let before = match PriceTier::find_matching(id, &tier, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?
+    {
+        Some(tier_id) => AuditResource::PriceTier(tier_id)
+            .snapshot(&mut tx)
+            .await
+            .map_err(|e| ApiError::internal_server_error(&e))?,
+        None => None,
+    };
+    let tier = PriceTier::set(id, tier, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, AuditResource::PriceTier(tier.id), before).await?;
+    commit(tx).await?;
+
+    Ok(Json(tier))
+}
+
+async fn delete_tier(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path((id, tier_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::PriceTier(tier_id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let deleted = PriceTier::delete(id, tier_id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::NO_CONTENT, Json(deleted)))
+}
+
+async fn restore(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::Product(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let restored = Product::restore(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(restored))
+}
@@ -1,22 +1,45 @@
 use axum::extract::Path;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::{Extension, Json, Router};
+use axum::{Extension, Json};
 use sqlx::PgPool;
 use validator::Validate;
 
 use crate::errors::ApiError;
+use crate::models::audit::AuditResource;
+use crate::models::authentication::JwtToken;
+use crate::models::inventory_movement::InventoryMovement;
 use crate::models::product_inventory::{
     ProductInventory, ProductInventoryInsert, ProductInventoryUpdate,
 };
 
-pub fn get_routes() -> Router {
-    Router::new()
-        .route("/inventory", get(fetch_all).post(create))
+use crate::openapi::{get, ApiRouter};
+
+use super::{begin, commit, record_audit};
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/inventory",
+            get(fetch_all, "List inventories")
+                .returns::<Vec<ProductInventory>>()
+                .post(create, "Create an inventory")
+                .status(201)
+                .returns::<ProductInventory>()
+                .idempotent(),
+        )
         .route(
             "/inventory/:id",
-            get(fetch_one).patch(update).delete(delete),
+            get(fetch_one, "Get an inventory")
+                .returns::<ProductInventory>()
+                .patch(update, "Update an inventory")
+                .returns::<ProductInventory>()
+                .delete(delete, "Delete an inventory")
+                .returns::<u64>(),
+        )
+        .route(
+            "/inventory/:id/movements",
+            get(fetch_movements, "List stock movements").returns::<Vec<InventoryMovement>>(),
         )
 }
 
@@ -34,22 +57,44 @@ async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> i
         .map_err(|e| ApiError::internal_server_error(&e))
 }
 
+async fn fetch_movements(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    InventoryMovement::find_by_inventory(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
 async fn create(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Json(inventory): Json<ProductInventoryInsert>,
 ) -> impl IntoResponse {
     if let Err(e) = inventory.validate() {
         return Err(ApiError::validation_error(e));
     }
 
-    ProductInventory::create(inventory, &pool)
+    let mut tx = begin(&pool).await?;
+    let inventory = ProductInventory::create(inventory, &mut tx)
         .await
-        .map(|r| (StatusCode::CREATED, Json(r)))
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(
+        &mut tx,
+        &token,
+        AuditResource::ProductInventory(inventory.id),
+        None,
+    )
+    .await?;
+    commit(tx).await?;
+
+    Ok((StatusCode::CREATED, Json(inventory)))
 }
 
 async fn update(
     Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
     Path(id): Path<i64>,
     Json(inventory): Json<ProductInventoryUpdate>,
 ) -> impl IntoResponse {
@@ -57,15 +102,37 @@ async fn update(
         return Err(ApiError::validation_error(e));
     }
 
-    ProductInventory::update(id, inventory, &pool)
+    let resource = AuditResource::ProductInventory(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(Json)
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let inventory = ProductInventory::update(id, inventory, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(inventory))
 }
 
-async fn delete(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
-    ProductInventory::delete(id, &pool)
+async fn delete(
+    Extension(pool): Extension<PgPool>,
+    token: Option<JwtToken>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let resource = AuditResource::ProductInventory(id);
+    let mut tx = begin(&pool).await?;
+    let before = resource
+        .snapshot(&mut tx)
         .await
-        .map(Json)
-        .map_err(|e| ApiError::internal_server_error(&e))
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    let deleted = ProductInventory::delete(id, &mut tx)
+        .await
+        .map_err(|e| ApiError::internal_server_error(&e))?;
+    record_audit(&mut tx, &token, resource, before).await?;
+    commit(tx).await?;
+
+    Ok(Json(deleted))
 }
@@ -0,0 +1,184 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::currency::SelectedCurrency;
+use crate::models::shipping::{
+    ShippingMethod, ShippingMethodInsert, ShippingMethodUpdate, ShippingQuote,
+    ShippingQuoteRequest, ShippingZone, ShippingZoneInsert, ShippingZoneUpdate,
+};
+use crate::openapi::{get, post, ApiRouter};
+
+use super::order::order_error;
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new().route(
+        "/shipping/quote",
+        post(quote, "Quote shipping for a cart").returns::<Vec<ShippingQuote>>(),
+    )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/shipping/zone",
+            get(fetch_zones, "List shipping zones")
+                .returns::<Vec<ShippingZone>>()
+                .post(create_zone, "Create a shipping zone")
+                .status(201)
+                .returns::<ShippingZone>(),
+        )
+        .route(
+            "/shipping/zone/:id",
+            get(fetch_zone, "Get a shipping zone")
+                .returns::<ShippingZone>()
+                .patch(update_zone, "Update a shipping zone")
+                .status(202)
+                .returns::<ShippingZone>()
+                .delete(delete_zone, "Delete a shipping zone")
+                .status(204),
+        )
+        .route(
+            "/shipping/method",
+            get(fetch_methods, "List shipping methods")
+                .returns::<Vec<ShippingMethod>>()
+                .post(create_method, "Create a shipping method")
+                .status(201)
+                .returns::<ShippingMethod>(),
+        )
+        .route(
+            "/shipping/method/:id",
+            get(fetch_method, "Get a shipping method")
+                .returns::<ShippingMethod>()
+                .patch(update_method, "Update a shipping method")
+                .status(202)
+                .returns::<ShippingMethod>()
+                .delete(delete_method, "Delete a shipping method")
+                .status(204),
+        )
+}
+
+async fn quote(
+    Extension(pool): Extension<PgPool>,
+    SelectedCurrency(currency): SelectedCurrency,
+    Json(cart): Json<ShippingQuoteRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = cart.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    ShippingMethod::quote(cart, &currency, &pool)
+        .await
+        .map(Json)
+        .map_err(order_error)
+}
+
+async fn fetch_zones(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    ShippingZone::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_zone(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    ShippingZone::find_by_id(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn create_zone(
+    Extension(pool): Extension<PgPool>,
+    Json(zone): Json<ShippingZoneInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = zone.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    ShippingZone::create(zone, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update_zone(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    Json(zone): Json<ShippingZoneUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = zone.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    ShippingZone::update(id, zone, &pool)
+        .await
+        .map(|r| (StatusCode::ACCEPTED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn delete_zone(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    ShippingZone::delete(id, &pool)
+        .await
+        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_methods(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    ShippingMethod::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_method(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    ShippingMethod::find_by_id(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn create_method(
+    Extension(pool): Extension<PgPool>,
+    Json(method): Json<ShippingMethodInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = method.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    ShippingMethod::create(method, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update_method(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    Json(method): Json<ShippingMethodUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = method.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    ShippingMethod::update(id, method, &pool)
+        .await
+        .map(|r| (StatusCode::ACCEPTED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn delete_method(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    ShippingMethod::delete(id, &pool)
+        .await
+        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
@@ -0,0 +1,46 @@
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::shop_profile::{ShopProfile, ShopProfileUpdate};
+use crate::openapi::{get, ApiRouter};
+
+pub fn get_routes() -> ApiRouter {
+    ApiRouter::new().route(
+        "/shop/profile",
+        get(fetch, "Get the shop's profile").returns::<ShopProfile>(),
+    )
+}
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new().route(
+        "/shop/profile",
+        get(fetch, "Get the shop's profile")
+            .returns::<ShopProfile>()
+            .patch(update, "Update the shop's profile")
+            .returns::<ShopProfile>(),
+    )
+}
+
+async fn fetch(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    ShopProfile::find(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update(
+    Extension(pool): Extension<PgPool>,
+    Json(profile): Json<ShopProfileUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = profile.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    ShopProfile::update(profile, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
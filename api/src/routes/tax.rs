@@ -0,0 +1,81 @@
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+use validator::Validate;
+
+use crate::errors::ApiError;
+use crate::models::tax::{TaxRate, TaxRateInsert, TaxRateUpdate};
+use crate::openapi::{get, ApiRouter};
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .route(
+            "/tax/rate",
+            get(fetch_all, "List tax rates")
+                .returns::<Vec<TaxRate>>()
+                .post(create, "Create a tax rate")
+                .status(201)
+                .returns::<TaxRate>(),
+        )
+        .route(
+            "/tax/rate/:id",
+            get(fetch_one, "Get a tax rate")
+                .returns::<TaxRate>()
+                .patch(update, "Update a tax rate")
+                .returns::<TaxRate>()
+                .delete(delete, "Delete a tax rate")
+                .status(204),
+        )
+}
+
+async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    TaxRate::find_all(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn fetch_one(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    TaxRate::find_by_id(id, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn create(
+    Extension(pool): Extension<PgPool>,
+    Json(rate): Json<TaxRateInsert>,
+) -> impl IntoResponse {
+    if let Err(e) = rate.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    TaxRate::create(rate, &pool)
+        .await
+        .map(|r| (StatusCode::CREATED, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn update(
+    Extension(pool): Extension<PgPool>,
+    Path(id): Path<i64>,
+    Json(rate): Json<TaxRateUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = rate.validate() {
+        return Err(ApiError::validation_error(e));
+    }
+
+    TaxRate::update(id, rate, &pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
+
+async fn delete(Extension(pool): Extension<PgPool>, Path(id): Path<i64>) -> impl IntoResponse {
+    TaxRate::delete(id, &pool)
+        .await
+        .map(|r| (StatusCode::NO_CONTENT, Json(r)))
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
@@ -0,0 +1,21 @@
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use sqlx::PgPool;
+
+use crate::errors::ApiError;
+use crate::models::trash::Trash;
+use crate::openapi::{get, ApiRouter};
+
+pub fn get_admin_routes() -> ApiRouter {
+    ApiRouter::new().route(
+        "/trash",
+        get(fetch_all, "List the trash").returns::<Trash>(),
+    )
+}
+
+async fn fetch_all(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
+    Trash::find(&pool)
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::internal_server_error(&e))
+}
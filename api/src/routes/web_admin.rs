@@ -0,0 +1,121 @@
+use axum::http::{Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get_service, MethodRouter};
+use std::io;
+use std::path::Path;
+use tower_http::services::{ServeDir, ServeFile};
+
+/// Serves the built web admin from `dir`, as the fallback of every other
+/// route. Paths that aren't files get `index.html` so that the client side
+/// router can take over, paths under `/api` are left alone.
+pub fn service(dir: &Path) -> MethodRouter {
+    let index = ServeFile::new(dir.join("index.html"));
+
+    get_service(ServeDir::new(dir).fallback(index))
+        .handle_error(|e: io::Error| async move {
+            tracing::error!("Can't serve the web admin: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+        .layer(middleware::from_fn(skip_api))
+}
+
+async fn skip_api<B>(request: Request<B>, next: Next<B>) -> Response {
+    let path = request.uri().path();
+    if path == "/api" || path.starts_with("/api/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use std::path::PathBuf;
+    use tower::ServiceExt;
+
+    const INDEX: &str = "<!doctype html><title>Admin</title>";
+
+    /// A built web admin of its own for every test, removed when it's
+    /// dropped.
+    struct AdminDir(PathBuf);
+
+    impl AdminDir {
+        fn new(name: &str) -> AdminDir {
+            let dir = std::env::temp_dir().join(format!(
+                "crabbyshop-web-admin-{}-{}",
+                std::process::id(),
+                name
+            ));
+            std::fs::create_dir_all(dir.join("assets")).unwrap();
+            std::fs::write(dir.join("index.html"), INDEX).unwrap();
+            std::fs::write(dir.join("assets/app.js"), "console.log('admin')").unwrap();
+            AdminDir(dir)
+        }
+    }
+
+    impl Drop for AdminDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn get_page(dir: &AdminDir, path: &str) -> (StatusCode, String) {
+        let api = Router::new().route("/product", get(|| async { "products" }));
+        let app = Router::new().nest("/api/v1", api).fallback(service(&dir.0));
+
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn serves_files_of_the_build() {
+        let dir = AdminDir::new("files");
+
+        assert_eq!(
+            get_page(&dir, "/").await,
+            (StatusCode::OK, INDEX.to_string())
+        );
+        assert_eq!(
+            get_page(&dir, "/assets/app.js").await,
+            (StatusCode::OK, "console.log('admin')".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_index_for_client_side_routes() {
+        let dir = AdminDir::new("fallback");
+
+        for path in ["/products", "/products/42/edit", "/apis"] {
+            assert_eq!(
+                get_page(&dir, path).await,
+                (StatusCode::OK, INDEX.to_string()),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn leaves_api_paths_to_the_api() {
+        let dir = AdminDir::new("api");
+
+        assert_eq!(
+            get_page(&dir, "/api/v1/product").await,
+            (StatusCode::OK, "products".to_string())
+        );
+        for path in ["/api", "/api/v1/unknown", "/api/v2/product"] {
+            let (status, body) = get_page(&dir, path).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", path);
+            assert_ne!(body, INDEX, "{}", path);
+        }
+    }
+}
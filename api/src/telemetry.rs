@@ -0,0 +1,159 @@
+//! Traces of requests and the model calls they make, exported to an
+//! OpenTelemetry collector over OTLP/HTTP.
+//!
+//! Model spans are named like `Order::create` and carry the rows their
+//! statement read or wrote, recorded with [`RecordRows`].
+
+use axum::http::{HeaderMap, Request};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self, Tracer, TracerProvider};
+use opentelemetry::sdk::{runtime, Resource};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::{SpanExporterBuilder, WithExportConfig};
+use sqlx::postgres::PgQueryResult;
+use std::time::Duration;
+use tracing::{Metadata, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Span attributes of the rows a model method read or wrote.
+pub const ROWS_RETURNED: &str = "db.rows_returned";
+pub const ROWS_AFFECTED: &str = "db.rows_affected";
+
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends the recorded spans to the collector in batches.
+pub struct Exporter {
+    provider: TracerProvider,
+}
+
+impl Exporter {
+    /// Exports the spans still queued and stops.
+    pub async fn shutdown(self) {
+        // Flushing blocks on the export task, which runs on the runtime.
+        let flush = tokio::task::spawn_blocking(move || {
+            self.provider.force_flush();
+        });
+        if tokio::time::timeout(EXPORT_TIMEOUT, flush).await.is_err() {
+            tracing::warn!("Gave up exporting the last traces");
+        }
+    }
+}
+
+/// A layer recording spans and the exporter sending them to the collector at
+/// `endpoint`, like `http://localhost:4318`. Has to be called on the runtime.
+pub fn pipeline<S>(
+    endpoint: &str,
+    service_name: &str,
+) -> Result<(OpenTelemetryLayer<S, Tracer>, Exporter), String>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .with_timeout(EXPORT_TIMEOUT);
+    let exporter = SpanExporterBuilder::from(exporter)
+        .build_span_exporter()
+        .map_err(|e| format!("Invalid OTLP endpoint {}: {}", endpoint, e))?;
+
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_config(trace::config().with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            service_name.to_string(),
+        )])))
+        .build();
+    let tracer = provider.versioned_tracer(
+        "crabbyshop",
+        Some(env!("CARGO_PKG_VERSION")),
+        None::<&str>,
+        None,
+    );
+
+    Ok((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        Exporter { provider },
+    ))
+}
+
+/// Our own spans and events. Those of other crates, like the HTTP client
+/// exporting the spans, are left out.
+pub fn is_exported(metadata: &Metadata<'_>) -> bool {
+    metadata.target().starts_with("crabbyshop")
+}
+
+/// Continues the trace of a request's `traceparent` header on `span`.
+pub fn continue_trace<B>(span: &Span, request: &Request<B>) {
+    if request.headers().contains_key(TRACEPARENT) {
+        span.set_parent(remote_context(request.headers()));
+    }
+}
+
+/// The context of a W3C `traceparent` header, like
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`. Empty when the
+/// header is missing or invalid.
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// The rows a statement read or wrote, as a span attribute.
+pub trait RowCount {
+    fn row_count(&self) -> (&'static str, i64);
+}
+
+impl<T> RowCount for Vec<T> {
+    fn row_count(&self) -> (&'static str, i64) {
+        (ROWS_RETURNED, self.len() as i64)
+    }
+}
+
+impl<T> RowCount for Option<T> {
+    fn row_count(&self) -> (&'static str, i64) {
+        (ROWS_RETURNED, self.is_some().into())
+    }
+}
+
+impl RowCount for PgQueryResult {
+    fn row_count(&self) -> (&'static str, i64) {
+        (ROWS_AFFECTED, self.rows_affected() as i64)
+    }
+}
+
+/// Records the rows of a statement on the span of the model method running
+/// it, like `.fetch_all(pool).await.record_rows()`.
+pub trait RecordRows {
+    fn record_rows(self) -> Self;
+}
+
+impl<T: RowCount, E> RecordRows for Result<T, E> {
+    fn record_rows(self) -> Self {
+        if let Ok(result) = &self {
+            let (key, rows) = result.row_count();
+            Span::current().set_attribute(key, rows);
+        }
+        self
+    }
+}
+
+/// Records the rows of several statements on the current span, as the
+/// attribute of a later statement would replace that of an earlier one.
+pub fn record_rows(key: &'static str, rows: u64) {
+    Span::current().set_attribute(key, rows as i64);
+}
@@ -0,0 +1,211 @@
+//! Moves category trees in and out of the trash through the admin API:
+//! categories can't be filed under their own subtree, cycles don't keep a
+//! delete from finishing and every category a delete or restore cascades to
+//! gets an audit entry. Needs the database in `DATABASE_URL`.
+
+use clap::Parser;
+use crabbyshop::auth::JwtKeys;
+use crabbyshop::config::Config;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Body, Client, Method, Request, StatusCode};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+
+const JWT_SECRET: &str = "category-test-jwt-secret";
+
+struct Api {
+    base: String,
+    authorization: String,
+    pool: PgPool,
+}
+
+impl Api {
+    async fn start() -> Api {
+        dotenv::dotenv().ok();
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL isn't set");
+        let pool = PgPool::connect(&database_url).await.unwrap();
+        let config = Config::try_parse_from([
+            "crabbyshop",
+            "--database-url",
+            &database_url,
+            "--jwt-secret",
+            JWT_SECRET,
+            "--payment-webhook-secret",
+            "category-test-webhook-secret",
+            "--rate-limit-per-ip",
+            "0",
+            "--rate-limit-per-user",
+            "0",
+        ])
+        .unwrap();
+        let app = crabbyshop::create_app(&config, pool.clone()).await;
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+        let base = format!("http://{}/api/v1/admin", server.local_addr());
+        tokio::spawn(server);
+
+        // Audit entries point at their actor, who has to exist.
+        let admin_id: i64 = sqlx::query_scalar(
+            r#"
+				INSERT INTO app_user (email, password_hash, user_role)
+				VALUES ($1, '', '{admin}')
+				RETURNING id;
+			"#,
+        )
+        .bind(format!(
+            "category-test-{}@example.com",
+            chrono::Utc::now().timestamp_nanos()
+        ))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let token = JwtKeys::from_secret(JWT_SECRET.as_bytes())
+            .issue(admin_id, vec!["admin".to_string()])
+            .unwrap()
+            .token;
+
+        Api {
+            base,
+            authorization: format!("Bearer {}", token),
+            pool,
+        }
+    }
+
+    async fn call(&self, method: Method, path: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base, path))
+            .header(CONTENT_TYPE, "application/json")
+            .header(AUTHORIZATION, &self.authorization)
+            .body(Body::from(serde_json::to_vec(&body).unwrap()))
+            .unwrap();
+
+        let response = Client::new().request(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn category(&self, parent_id: Option<i64>) -> i64 {
+        let (status, category) = self
+            .call(
+                Method::POST,
+                "/category",
+                json!({ "name": "Category test", "parent_id": parent_id }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", category);
+
+        category["id"].as_i64().unwrap()
+    }
+
+    async fn move_to(&self, id: i64, parent_id: i64) -> StatusCode {
+        self.call(
+            Method::PATCH,
+            &format!("/category/{}", id),
+            json!({ "parent_id": parent_id }),
+        )
+        .await
+        .0
+    }
+
+    /// The audit actions of a category, newest first.
+    async fn audit(&self, id: i64) -> Vec<Value> {
+        let (_, entries) = self
+            .call(
+                Method::GET,
+                &format!("/audit?resource_type=category&resource_id={}", id),
+                Value::Null,
+            )
+            .await;
+
+        entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["action"].clone())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn rejects_filing_a_category_under_its_own_subtree() {
+    let api = Api::start().await;
+    let root = api.category(None).await;
+    let child = api.category(Some(root)).await;
+    let grandchild = api.category(Some(child)).await;
+
+    assert_eq!(api.move_to(root, root).await, StatusCode::BAD_REQUEST);
+    assert_eq!(api.move_to(root, grandchild).await, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        api.move_to(child, grandchild).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    let other = api.category(None).await;
+    assert_eq!(api.move_to(child, other).await, StatusCode::ACCEPTED);
+    assert_eq!(api.move_to(root, grandchild).await, StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn audits_every_category_of_a_trashed_tree() {
+    let api = Api::start().await;
+    let root = api.category(None).await;
+    let child = api.category(Some(root)).await;
+    let grandchild = api.category(Some(child)).await;
+
+    let (status, _) = api
+        .call(Method::DELETE, &format!("/category/{}", root), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    for id in [root, child, grandchild] {
+        assert_eq!(api.audit(id).await[0], "delete", "category {}", id);
+    }
+
+    let (status, restored) = api
+        .call(
+            Method::POST,
+            &format!("/category/{}/restore", grandchild),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(restored, 3);
+    for id in [root, child, grandchild] {
+        assert_eq!(api.audit(id).await[0], "restore", "category {}", id);
+    }
+}
+
+#[tokio::test]
+async fn trashes_a_cycle_made_before_parents_were_checked() {
+    let api = Api::start().await;
+    let root = api.category(None).await;
+    let child = api.category(Some(root)).await;
+    sqlx::query("UPDATE category SET parent_id = $1 WHERE id = $2")
+        .bind(child)
+        .bind(root)
+        .execute(&api.pool)
+        .await
+        .unwrap();
+
+    let (status, deleted) = api
+        .call(Method::DELETE, &format!("/category/{}", root), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{}", deleted);
+    for id in [root, child] {
+        assert_eq!(api.audit(id).await[0], "delete", "category {}", id);
+    }
+
+    let (status, restored) = api
+        .call(
+            Method::POST,
+            &format!("/category/{}/restore", child),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(restored, 2);
+}
@@ -0,0 +1,629 @@
+//! Drives checkout end to end against the mock payment provider: a guest
+//! order is authorized and captured, its invoice downloaded, partly refunded
+//! at the gateway as reported by webhook, and refunded in full through the
+//! API. A return is refunded in parts, orders are cancelled and refunded
+//! together with their payments and guests' retries are answered by
+//! `Idempotency-Key`. Needs the database in `DATABASE_URL`.
+
+use clap::Parser;
+use crabbyshop::auth::JwtKeys;
+use crabbyshop::config::Config;
+use crabbyshop::payments::mock::SIGNATURE_HEADER;
+use crabbyshop::payments::MockProvider;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Body, Client, Method, Request, StatusCode};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+const WEBHOOK_SECRET: &str = "checkout-test-webhook-secret";
+const JWT_SECRET: &str = "checkout-test-jwt-secret";
+
+struct Api {
+    base: String,
+    admin: String,
+}
+
+impl Api {
+    async fn start(pool: PgPool, database_url: &str) -> Api {
+        Api::start_with(pool, database_url, &[]).await
+    }
+
+    async fn start_with(pool: PgPool, database_url: &str, args: &[&str]) -> Api {
+        let config = Config::try_parse_from(
+            [
+                "crabbyshop",
+                "--database-url",
+                database_url,
+                "--jwt-secret",
+                JWT_SECRET,
+                "--payment-webhook-secret",
+                WEBHOOK_SECRET,
+                "--rate-limit-per-ip",
+                "0",
+                "--rate-limit-per-user",
+                "0",
+            ]
+            .iter()
+            .chain(args),
+        )
+        .unwrap();
+        let app = crabbyshop::create_app(&config, pool).await;
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+        let base = format!("http://{}/api/v1", server.local_addr());
+        tokio::spawn(server);
+
+        let admin = JwtKeys::from_secret(JWT_SECRET.as_bytes())
+            .issue(0, vec!["admin".to_string()])
+            .unwrap()
+            .token;
+
+        Api { base, admin }
+    }
+
+    async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &Value,
+    ) -> (StatusCode, Value) {
+        let body = serde_json::to_vec(body).unwrap();
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base, path))
+            .header(CONTENT_TYPE, "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        let response = Client::new()
+            .request(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        self.call(Method::POST, path, &[], &body).await
+    }
+
+    /// Acts on a guest order with the access token handed out for it.
+    async fn guest(&self, path: &str, order: &Value, body: Value) -> (StatusCode, Value) {
+        let token = order["access_token"].as_str().unwrap();
+        self.call(Method::POST, path, &[("X-Order-Token", token)], &body)
+            .await
+    }
+
+    async fn admin(&self, method: Method, path: &str, body: Value) -> (StatusCode, Value) {
+        let authorization = format!("Bearer {}", self.admin);
+        self.call(
+            method,
+            &format!("/admin{}", path),
+            &[(AUTHORIZATION.as_str(), &authorization)],
+            &body,
+        )
+        .await
+    }
+
+    /// Delivers a webhook signed like the mock gateway signs them.
+    async fn webhook(&self, event: Value) -> StatusCode {
+        let body = serde_json::to_vec(&event).unwrap();
+        let signature = MockProvider::new(WEBHOOK_SECRET.to_string()).sign(&body);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("{}/payment/webhook", self.base))
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .body(Body::from(body))
+            .unwrap();
+
+        Client::new().request(request).await.unwrap().status()
+    }
+}
+
+fn decimal(value: &Value) -> Decimal {
+    Decimal::from_str(value.as_str().unwrap()).unwrap()
+}
+
+/// A published product with stock of its own, so that runs don't interfere.
+async fn product(pool: &PgPool) -> i64 {
+    let sku = format!("checkout-test-{}", chrono::Utc::now().timestamp_nanos());
+
+    sqlx::query_scalar(
+        r#"
+			WITH inventory AS (
+				INSERT INTO product_inventory (quantity) VALUES (10) RETURNING id
+			)
+			INSERT INTO product (name, sku, price, inventory_id, status)
+			SELECT 'Checkout test', $1, 12.20, id, 'published' FROM inventory
+			RETURNING id;
+		"#,
+    )
+    .bind(sku)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+/// Orders two of a new product as a guest and authorizes the payment.
+async fn checkout() -> (Api, Value, Value) {
+    dotenv::dotenv().ok();
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL isn't set");
+    let pool = PgPool::connect(&database_url).await.unwrap();
+    let product_id = product(&pool).await;
+    let api = Api::start(pool, &database_url).await;
+
+    let items = json!({ "items": [{ "product_id": product_id, "quantity": 2 }] });
+
+    // A broken token isn't mistaken for a guest.
+    let (status, _) = api
+        .call(
+            Method::POST,
+            "/order",
+            &[(AUTHORIZATION.as_str(), "Bearer expired")],
+            &items,
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, order) = api.post("/order", items).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", order);
+
+    let path = format!("/order/{}/payment", order["id"]);
+    let source = json!({ "source": "tok_visa" });
+
+    // Only the guest who placed the order can pay for it.
+    let (status, _) = api.post(&path, source.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = api
+        .call(
+            Method::POST,
+            &path,
+            &[("X-Order-Token", "guessed")],
+            &source,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, payment) = api.guest(&path, &order, source).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", payment);
+    assert_eq!(payment["status"], "authorized");
+    assert_eq!(
+        decimal(&payment["amount"]),
+        decimal(&order["total"]["amount"])
+    );
+
+    // The authorization has to be captured or voided before another one.
+    let (status, _) = api
+        .guest(&path, &order, json!({ "source": "tok_visa" }))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    (api, order, payment)
+}
+
+#[tokio::test]
+async fn checks_out_with_the_mock_provider() {
+    let (api, order, payment) = checkout().await;
+    let order_id = order["id"].as_i64().unwrap();
+    let total = decimal(&order["total"]["amount"]);
+    let payment_id = payment["id"].as_i64().unwrap();
+    let reference = payment["reference"].as_str().unwrap().to_string();
+
+    let refunded = |event_id: &str, refund_reference: &str, amount: &str| {
+        json!({
+            "id": event_id,
+            "type": "refund.succeeded",
+            "reference": reference,
+            "refund_reference": refund_reference,
+            "amount": amount,
+        })
+    };
+    let event_id = |name: &str| format!("{}-{}", reference, name);
+
+    // Nothing was captured yet, there's nothing to refund.
+    let status = api
+        .webhook(refunded(&event_id("early"), &event_id("early"), "1.00"))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, payment) = api
+        .admin(
+            Method::POST,
+            &format!("/payment/{}/capture", payment_id),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", payment);
+    assert_eq!(payment["status"], "captured");
+    assert_eq!(decimal(&payment["captured_amount"]), total);
+
+    // The guest downloads the invoice issued for the paid order.
+    let invoice_path = format!("/order/{}/invoice?format=json", order_id);
+    let (status, _) = api
+        .call(Method::GET, &invoice_path, &[], &Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let token = order["access_token"].as_str().unwrap();
+    let (status, invoice) = api
+        .call(
+            Method::GET,
+            &invoice_path,
+            &[("X-Order-Token", token)],
+            &Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", invoice);
+    assert_eq!(invoice["order_id"], order_id);
+
+    let (_, order) = api
+        .admin(Method::GET, &format!("/order/{}", order_id), Value::Null)
+        .await;
+    assert_eq!(order["status"], "paid");
+
+    // A refund made at the gateway, delivered twice.
+    let partial = refunded(&event_id("partial"), &event_id("partial"), "2.20");
+    assert_eq!(api.webhook(partial.clone()).await, StatusCode::NO_CONTENT);
+    assert_eq!(api.webhook(partial).await, StatusCode::NO_CONTENT);
+
+    let (_, payment) = api
+        .admin(
+            Method::GET,
+            &format!("/payment/{}", payment_id),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(payment["status"], "partially_refunded");
+    assert_eq!(
+        decimal(&payment["refunded_amount"]),
+        Decimal::from_str("2.20").unwrap()
+    );
+
+    let too_much = (total * Decimal::from(2)).to_string();
+    let status = api
+        .webhook(refunded(
+            &event_id("excess"),
+            &event_id("excess"),
+            &too_much,
+        ))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let forged = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{}/payment/webhook", api.base))
+        .header(SIGNATURE_HEADER, "00")
+        .body(Body::from(
+            refunded(&event_id("forged"), &event_id("forged"), "1.00").to_string(),
+        ))
+        .unwrap();
+    let status = Client::new().request(forged).await.unwrap().status();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The rest through the API.
+    let (status, payment) = api
+        .admin(
+            Method::POST,
+            &format!("/payment/{}/refund", payment_id),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", payment);
+    assert_eq!(payment["status"], "refunded");
+    assert_eq!(decimal(&payment["refunded_amount"]), total);
+
+    let (status, _) = api
+        .admin(
+            Method::POST,
+            &format!("/payment/{}/refund", payment_id),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, order) = api
+        .admin(Method::GET, &format!("/order/{}", order_id), Value::Null)
+        .await;
+    assert_eq!(order["status"], "refunded");
+
+    // Every refund got a credit note.
+    let (_, credit_notes) = api
+        .admin(
+            Method::GET,
+            &format!("/order/{}/credit-note", order_id),
+            Value::Null,
+        )
+        .await;
+    let credited: Decimal = credit_notes
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| decimal(&c["amount"]))
+        .sum();
+    assert_eq!(credit_notes.as_array().unwrap().len(), 2);
+    assert_eq!(credited, total);
+}
+
+#[tokio::test]
+async fn refunds_a_return_in_parts() {
+    let (api, order, payment) = checkout().await;
+    let order_id = order["id"].as_i64().unwrap();
+    let item = &order["items"][0];
+    let value = decimal(&item["net_amount"]["amount"]) + decimal(&item["tax_amount"]["amount"]);
+
+    for action in ["payment/{}/capture", "order/{}/pick", "order/{}/ship"] {
+        let id = if action.starts_with("payment") {
+            &payment["id"]
+        } else {
+            &order["id"]
+        };
+        let path = format!("/{}", action.replace("{}", &id.to_string()));
+        let (status, body) = api.admin(Method::POST, &path, Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", path, body);
+    }
+
+    let (status, order_return) = api
+        .guest(
+            &format!("/order/{}/return", order_id),
+            &order,
+            json!({ "lines": [{ "order_item_id": item["id"], "quantity": 2, "reason": "too big" }] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", order_return);
+    let return_id = order_return["id"].as_i64().unwrap();
+    let line_id = &order_return["lines"][0]["id"];
+    let path = |action: &str| format!("/return/{}/{}", return_id, action);
+
+    let (status, _) = api.admin(Method::POST, &path("approve"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let twice = json!({ "lines": [
+        { "line_id": line_id, "condition": "restocked" },
+        { "line_id": line_id, "condition": "damaged" },
+    ] });
+    let (status, _) = api.admin(Method::POST, &path("receive"), twice).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = api.admin(Method::POST, &path("receive"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = api
+        .admin(Method::POST, &path("refund"), json!({ "amount": "-1.00" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, first) = api
+        .admin(Method::POST, &path("refund"), json!({ "amount": "5.00" }))
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", first);
+    assert_eq!(first["return_id"], return_id);
+    assert_eq!(
+        decimal(&first["amount"]),
+        Decimal::from_str("5.00").unwrap()
+    );
+
+    let (_, order_return) = api
+        .admin(Method::GET, &format!("/return/{}", return_id), Value::Null)
+        .await;
+    assert_eq!(order_return["status"], "received");
+
+    let (status, rest) = api.admin(Method::POST, &path("refund"), Value::Null).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", rest);
+    assert_eq!(
+        decimal(&rest["amount"]),
+        value - Decimal::from_str("5.00").unwrap()
+    );
+
+    let (_, order_return) = api
+        .admin(Method::GET, &format!("/return/{}", return_id), Value::Null)
+        .await;
+    assert_eq!(order_return["status"], "refunded");
+
+    let (status, _) = api.admin(Method::POST, &path("refund"), Value::Null).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn cancels_and_refunds_orders_through_the_provider() {
+    let (api, order, payment) = checkout().await;
+    let path = |action: &str| format!("/order/{}/{}", order["id"], action);
+    let payment_path = format!("/payment/{}", payment["id"]);
+
+    let (status, cancelled) = api.admin(Method::POST, &path("cancel"), Value::Null).await;
+    assert_eq!(status, StatusCode::OK, "{}", cancelled);
+    assert_eq!(cancelled["status"], "cancelled");
+    let (_, payment) = api.admin(Method::GET, &payment_path, Value::Null).await;
+    assert_eq!(payment["status"], "voided");
+
+    let (api, order, payment) = checkout().await;
+    let path = |action: &str| format!("/order/{}/{}", order["id"], action);
+    let payment_path = format!("/payment/{}", payment["id"]);
+    let (status, _) = api
+        .admin(
+            Method::POST,
+            &format!("{}/capture", payment_path),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Captured money isn't left behind by a cancellation.
+    let (status, _) = api.admin(Method::POST, &path("cancel"), Value::Null).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, refunded) = api
+        .admin(
+            Method::POST,
+            &path("refund"),
+            json!({ "note": "out of stock" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", refunded);
+    assert_eq!(refunded["status"], "refunded");
+    let (_, payment) = api.admin(Method::GET, &payment_path, Value::Null).await;
+    assert_eq!(payment["status"], "refunded");
+    assert_eq!(
+        decimal(&payment["refunded_amount"]),
+        decimal(&order["total"]["amount"])
+    );
+}
+
+#[tokio::test]
+async fn acknowledges_captures_the_order_can_no_longer_take() {
+    let (api, order, payment) = checkout().await;
+    let reference = payment["reference"].as_str().unwrap();
+
+    // Marked paid by hand while the gateway captured the payment.
+    let (status, _) = api
+        .admin(
+            Method::POST,
+            &format!("/order/{}/pay", order["id"]),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let captured = json!({
+        "id": format!("{}-captured", reference),
+        "type": "payment.captured",
+        "reference": reference,
+    });
+    assert_eq!(api.webhook(captured).await, StatusCode::NO_CONTENT);
+
+    let (_, payment) = api
+        .admin(
+            Method::GET,
+            &format!("/payment/{}", payment["id"]),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(payment["status"], "captured");
+    let (_, order) = api
+        .admin(Method::GET, &format!("/order/{}", order["id"]), Value::Null)
+        .await;
+    assert_eq!(order["status"], "paid");
+}
+
+#[tokio::test]
+async fn refunds_returns_in_the_minor_units_of_the_order_currency() {
+    dotenv::dotenv().ok();
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL isn't set");
+    let pool = PgPool::connect(&database_url).await.unwrap();
+    let product_id = product(&pool).await;
+
+    // A currency without minor units, the product costing 1 in it before
+    // tax, so that a third of the line has a fraction.
+    sqlx::query(
+        r#"
+			INSERT INTO currency (code, name, minor_units, exchange_rate)
+			VALUES ('XTS', 'Checkout test', 0, 100)
+			ON CONFLICT (code) DO UPDATE SET minor_units = 0, active = true;
+		"#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO product_price (product_id, currency, price) VALUES ($1, 'XTS', 1)")
+        .bind(product_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let api = Api::start_with(pool, &database_url, &["--prices-include-tax", "false"]).await;
+
+    let (status, order) = api
+        .call(
+            Method::POST,
+            "/order",
+            &[("X-Currency", "XTS")],
+            &json!({ "items": [{ "product_id": product_id, "quantity": 3 }] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", order);
+    let (status, payment) = api
+        .guest(
+            &format!("/order/{}/payment", order["id"]),
+            &order,
+            json!({ "source": "tok_visa" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", payment);
+
+    for path in [
+        format!("/payment/{}/capture", payment["id"]),
+        format!("/order/{}/pick", order["id"]),
+        format!("/order/{}/ship", order["id"]),
+    ] {
+        let (status, body) = api.admin(Method::POST, &path, Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", path, body);
+    }
+
+    let item = &order["items"][0];
+    let (status, order_return) = api
+        .guest(
+            &format!("/order/{}/return", order["id"]),
+            &order,
+            json!({ "lines": [{ "order_item_id": item["id"], "quantity": 1, "reason": "too big" }] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", order_return);
+    let path = |action: &str| format!("/return/{}/{}", order_return["id"], action);
+    for action in ["approve", "receive"] {
+        let (status, _) = api.admin(Method::POST, &path(action), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, refund) = api.admin(Method::POST, &path("refund"), Value::Null).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", refund);
+    let line_total =
+        decimal(&item["net_amount"]["amount"]) + decimal(&item["tax_amount"]["amount"]);
+    assert_eq!(
+        decimal(&refund["amount"]),
+        (line_total / Decimal::from(3)).round()
+    );
+}
+
+#[tokio::test]
+async fn replays_guest_orders_placed_with_the_same_idempotency_key() {
+    dotenv::dotenv().ok();
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL isn't set");
+    let pool = PgPool::connect(&database_url).await.unwrap();
+    let product_id = product(&pool).await;
+    let api = Api::start(pool, &database_url).await;
+    let items = json!({ "items": [{ "product_id": product_id, "quantity": 1 }] });
+
+    // Guests share one namespace, their keys have to be hard to guess.
+    let (status, _) = api
+        .call(
+            Method::POST,
+            "/order",
+            &[("Idempotency-Key", "order-1")],
+            &items,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let nanos = chrono::Utc::now().timestamp_nanos() as u64;
+    let key = format!(
+        "{:08x}-0000-4000-8000-{:012x}",
+        std::process::id(),
+        nanos & 0xffff_ffff_ffff
+    );
+    let (status, first) = api
+        .call(Method::POST, "/order", &[("Idempotency-Key", &key)], &items)
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", first);
+    let (status, retried) = api
+        .call(Method::POST, "/order", &[("Idempotency-Key", &key)], &items)
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", retried);
+    assert_eq!(retried["id"], first["id"]);
+}
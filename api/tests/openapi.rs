@@ -0,0 +1,276 @@
+//! Checks the OpenAPI document generated from the route registrations: the
+//! request bodies, parameters and responses of the handlers, and that the
+//! docs UI is served from the binary.
+
+use clap::Parser;
+use crabbyshop::config::Config;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Client, StatusCode};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::net::SocketAddr;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// Every operation of the document as `(method, path, operation)`.
+fn operations(spec: &Value) -> Vec<(String, String, Value)> {
+    let mut operations = Vec::new();
+
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for (method, operation) in item.as_object().unwrap() {
+            assert!(METHODS.contains(&method.as_str()), "{} {}", method, path);
+            operations.push((method.clone(), path.clone(), operation.clone()));
+        }
+    }
+
+    operations
+}
+
+fn header_parameter<'a>(operation: &'a Value, name: &str) -> Option<&'a Value> {
+    operation["parameters"]
+        .as_array()?
+        .iter()
+        .find(|p| p["in"] == "header" && p["name"] == name)
+}
+
+fn reference_name(schema: &Value) -> Option<&str> {
+    schema["$ref"].as_str()?.rsplit('/').next()
+}
+
+fn collect_references<'a>(value: &'a Value, references: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(reference)) = object.get("$ref") {
+                references.push(reference);
+            }
+            object
+                .values()
+                .for_each(|v| collect_references(v, references));
+        }
+        Value::Array(array) => array.iter().for_each(|v| collect_references(v, references)),
+        _ => {}
+    }
+}
+
+#[test]
+fn documents_request_bodies_and_query_parameters() {
+    let spec = crabbyshop::openapi::spec();
+    let paths = &spec["paths"];
+
+    let body = &paths["/order"]["post"]["requestBody"];
+    assert_eq!(body["required"], true);
+    assert_eq!(
+        reference_name(&body["content"]["application/json"]["schema"]),
+        Some("OrderInsert")
+    );
+
+    let body = &paths["/admin/order/{id}/pay"]["post"]["requestBody"];
+    assert_eq!(body["required"], false);
+    assert_eq!(
+        reference_name(&body["content"]["application/json"]["schema"]),
+        Some("OrderTransition")
+    );
+
+    assert!(paths["/product/{id}"]["get"]["requestBody"].is_null());
+
+    let parameters = &paths["/product/query"]["get"]["parameters"];
+    assert!(parameters
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p["in"] == "query" && p["name"] == "category_id"));
+    assert!(header_parameter(&paths["/product/query"]["get"], "X-Currency").is_some());
+
+    let parameters = &paths["/admin/product/{id}/price/{currency}"]["put"]["parameters"];
+    assert_eq!(parameters[0]["schema"]["type"], "integer");
+    assert_eq!(parameters[1]["schema"]["type"], "string");
+}
+
+#[test]
+fn documents_responses() {
+    let spec = crabbyshop::openapi::spec();
+
+    for (method, path, operation) in operations(&spec) {
+        let responses = operation["responses"].as_object().unwrap();
+        let (status, success) = responses
+            .iter()
+            .find(|(status, _)| status.starts_with('2'))
+            .unwrap_or_else(|| panic!("{} {}: no success response", method, path));
+
+        if status == "204" {
+            assert!(success["content"].is_null(), "{} {}", method, path);
+        } else {
+            assert!(
+                success["content"].is_object(),
+                "{} {}: undocumented response",
+                method,
+                path
+            );
+        }
+        assert!(responses.contains_key("default"), "{} {}", method, path);
+    }
+
+    let placed = &spec["paths"]["/order"]["post"]["responses"]["201"];
+    assert_eq!(
+        reference_name(&placed["content"]["application/json"]["schema"]),
+        Some("PlacedOrder")
+    );
+
+    let placed = &spec["components"]["schemas"]["PlacedOrder"]["allOf"];
+    assert_eq!(reference_name(&placed[0]), Some("Order"));
+    assert!(placed[1]["properties"]["access_token"].is_object());
+    assert!(placed[1]["required"].is_null());
+
+    let invoice = &spec["paths"]["/admin/order/{id}/invoice"]["get"]["responses"]["200"];
+    assert_eq!(
+        invoice["content"]["application/pdf"]["schema"]["format"],
+        "binary"
+    );
+}
+
+#[test]
+fn resolves_every_reference() {
+    let spec = crabbyshop::openapi::spec();
+    let schemas = &spec["components"]["schemas"];
+    let mut references = Vec::new();
+    collect_references(&spec, &mut references);
+
+    assert!(!references.is_empty());
+    for reference in references {
+        let name = reference
+            .strip_prefix("#/components/schemas/")
+            .unwrap_or_else(|| panic!("unexpected reference {}", reference));
+        assert!(schemas[name].is_object(), "{} isn't documented", name);
+    }
+}
+
+#[test]
+fn documents_validator_constraints() {
+    let spec = crabbyshop::openapi::spec();
+    let schemas = &spec["components"]["schemas"];
+
+    let insert = &schemas["ProductInsert"];
+    assert_eq!(insert["required"], json!(["name"]));
+    assert_eq!(insert["properties"]["name"]["maxLength"], 128);
+    assert_eq!(insert["properties"]["description"]["maxLength"], 500);
+
+    let item = &schemas["OrderItemInsert"]["properties"]["quantity"];
+    assert_eq!(item["minimum"], 1);
+    assert_eq!(item["maximum"], i32::MAX);
+
+    assert_eq!(schemas["OrderInsert"]["properties"]["items"]["minItems"], 1);
+    assert_eq!(
+        schemas["TaxClass"]["enum"],
+        json!(["standard", "reduced", "zero"])
+    );
+}
+
+#[test]
+fn documents_authentication() {
+    let spec = crabbyshop::openapi::spec();
+
+    for (method, path, operation) in operations(&spec) {
+        if path.starts_with("/admin/") {
+            assert_eq!(
+                operation["security"],
+                json!([{ "bearer": [] }]),
+                "{} {}",
+                method,
+                path
+            );
+            assert!(operation["responses"]["403"].is_object());
+        }
+    }
+
+    assert_eq!(
+        spec["paths"]["/account"]["get"]["security"],
+        json!([{ "bearer": [] }])
+    );
+    assert!(spec["paths"]["/product"]["get"]["security"].is_null());
+    assert!(spec["paths"]["/order"]["post"]["security"].is_null());
+}
+
+#[test]
+fn documents_order_access() {
+    let spec = crabbyshop::openapi::spec();
+    let checked = [
+        ("get", "/order/{id}/invoice"),
+        ("post", "/order/{id}/payment"),
+        ("post", "/order/{id}/return"),
+    ];
+
+    for (method, path, operation) in operations(&spec) {
+        let expected = checked.contains(&(method.as_str(), path.as_str()));
+        assert_eq!(
+            header_parameter(&operation, "X-Order-Token").is_some(),
+            expected,
+            "{} {}: X-Order-Token",
+            method,
+            path
+        );
+        if expected {
+            assert_eq!(operation["security"], json!([{ "bearer": [] }, {}]));
+        }
+    }
+}
+
+#[test]
+fn documents_idempotency_keys() {
+    let spec = crabbyshop::openapi::spec();
+    let idempotent = [
+        "/order",
+        "/admin/product",
+        "/admin/category",
+        "/admin/discount",
+        "/admin/inventory",
+    ];
+
+    for (method, path, operation) in operations(&spec) {
+        let expected = method == "post" && idempotent.contains(&path.as_str());
+        assert_eq!(
+            header_parameter(&operation, "Idempotency-Key").is_some(),
+            expected,
+            "{} {}: Idempotency-Key",
+            method,
+            path
+        );
+    }
+}
+
+#[tokio::test]
+async fn serves_the_docs_from_the_binary() {
+    let config = Config::try_parse_from([
+        "crabbyshop",
+        "--database-url",
+        "postgres://localhost/crabbyshop",
+        "--jwt-secret",
+        "openapi-test-jwt-secret",
+        "--payment-webhook-secret",
+        "openapi-test-webhook-secret",
+    ])
+    .unwrap();
+    // The docs don't touch the database.
+    let pool = PgPool::connect_lazy(&config.database_url).unwrap();
+    let app = crabbyshop::create_app(&config, pool).await;
+
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+    let base = format!("http://{}/api/v1", server.local_addr());
+    tokio::spawn(server);
+
+    for (path, content_type) in [
+        ("/docs", "text/html"),
+        ("/docs/swagger-ui-bundle.js", "text/javascript"),
+        ("/docs/swagger-ui.css", "text/css"),
+        ("/openapi.json", "application/json"),
+    ] {
+        let response = Client::new()
+            .get(format!("{}{}", base, path).parse().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK, "{}", path);
+        let header = response.headers()[CONTENT_TYPE].to_str().unwrap();
+        assert!(header.starts_with(content_type), "{}: {}", path, header);
+    }
+}
@@ -0,0 +1,83 @@
+//! Checks the price history the database keeps for the lowest price shown
+//! next to a discount. Needs the database in `DATABASE_URL`.
+
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+
+async fn pool() -> PgPool {
+    dotenv::dotenv().ok();
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL isn't set");
+    PgPool::connect(&database_url).await.unwrap()
+}
+
+async fn lowest_price(product_id: i64, pool: &PgPool) -> Decimal {
+    sqlx::query_scalar("SELECT product_lowest_price($1, NULL, 30)")
+        .bind(product_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn compares_discounts_with_the_price_before_they_started() {
+    let pool = pool().await;
+
+    let discount_id: i64 = sqlx::query_scalar(
+        "INSERT INTO discount (name, discount_percent, active) VALUES ('Pricing test', 20, false) RETURNING id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let product_id: i64 = sqlx::query_scalar(
+        "INSERT INTO product (name, price, discount_id) VALUES ('Pricing test', 100, $1) RETURNING id",
+    )
+    .bind(discount_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    sqlx::query("UPDATE discount SET active = true WHERE id = $1")
+        .bind(discount_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(lowest_price(product_id, &pool).await, Decimal::from(100));
+
+    // A deeper cut is still compared with the price before the first one.
+    sqlx::query("UPDATE discount SET discount_percent = 30 WHERE id = $1")
+        .bind(discount_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(lowest_price(product_id, &pool).await, Decimal::from(100));
+
+    let history: Vec<(Decimal, Option<Decimal>)> = sqlx::query_as(
+        "SELECT price, discount_percent FROM product_price_history WHERE product_id = $1 ORDER BY id",
+    )
+    .bind(product_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        history,
+        vec![
+            (Decimal::from(100), None),
+            (Decimal::from(80), Some(Decimal::from(20))),
+            (Decimal::from(70), Some(Decimal::from(30))),
+        ]
+    );
+
+    // Once the discount ended, the next one is compared with what shoppers
+    // paid during it.
+    sqlx::query("UPDATE discount SET active = false WHERE id = $1")
+        .bind(discount_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE discount SET active = true, discount_percent = 10 WHERE id = $1")
+        .bind(discount_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(lowest_price(product_id, &pool).await, Decimal::from(70));
+}
@@ -0,0 +1,125 @@
+//! Exports traces to a stand-in collector: spans keep the trace of an
+//! incoming `traceparent`, nest under their parents and model spans carry
+//! the rows of their statements.
+
+use axum::body::Bytes;
+use axum::http::Request;
+use axum::routing::post;
+use axum::{Extension, Router};
+use crabbyshop::logging;
+use crabbyshop::telemetry::{self, RecordRows, TRACEPARENT};
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use opentelemetry_proto::tonic::common::v1::KeyValue;
+use opentelemetry_proto::tonic::trace::v1::span::SpanKind;
+use prost::Message;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const REMOTE_PARENT_ID: &str = "00f067aa0ba902b7";
+
+/// Listens like an OTLP/HTTP collector, handing over every export.
+async fn collector() -> (String, mpsc::UnboundedReceiver<ExportTraceServiceRequest>) {
+    let (exports, received) = mpsc::unbounded_channel();
+    let app = Router::new()
+        .route(
+            "/v1/traces",
+            post(
+                |Extension(exports): Extension<
+                    mpsc::UnboundedSender<ExportTraceServiceRequest>,
+                >,
+                 body: Bytes| async move {
+                    exports
+                        .send(ExportTraceServiceRequest::decode(body).unwrap())
+                        .unwrap();
+                },
+            ),
+        )
+        .layer(Extension(exports));
+
+    let server =
+        axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+    let endpoint = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+
+    (endpoint, received)
+}
+
+fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a Value> {
+    attributes
+        .iter()
+        .find(|a| a.key == key)
+        .and_then(|a| a.value.as_ref()?.value.as_ref())
+}
+
+#[tokio::test]
+async fn exports_spans_to_the_collector() {
+    let (endpoint, mut received) = collector().await;
+    let (layer, exporter) = telemetry::pipeline(&endpoint, "crabbyshop-test").unwrap();
+    let subscriber =
+        tracing_subscriber::registry().with(layer.with_filter(filter_fn(telemetry::is_exported)));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let request = Request::builder()
+            .uri("/api/v1/account")
+            .header(
+                TRACEPARENT,
+                format!("00-{}-{}-01", TRACE_ID, REMOTE_PARENT_ID),
+            )
+            .body(())
+            .unwrap();
+        let request = logging::make_request_span(&request);
+        let _request = request.enter();
+        request.record("user_id", 4);
+
+        let model = tracing::info_span!(target: "crabbyshop::models", "User::find_by_id");
+        let _model = model.enter();
+        Ok::<_, ()>(Some("user")).record_rows().unwrap();
+
+        // Other crates' spans stay out of the trace.
+        let _other = tracing::info_span!(target: "hyper::client", "connect").entered();
+    });
+    exporter.shutdown().await;
+
+    let export = received.recv().await.unwrap();
+    let resource = export.resource_spans[0].resource.as_ref().unwrap();
+    assert_eq!(
+        attribute(&resource.attributes, "service.name"),
+        Some(&Value::StringValue("crabbyshop-test".to_string()))
+    );
+
+    let spans: Vec<_> = export
+        .resource_spans
+        .iter()
+        .flat_map(|r| &r.scope_spans)
+        .flat_map(|s| &s.spans)
+        .collect();
+    let span = |name: &str| *spans.iter().find(|s| s.name == name).unwrap();
+    assert_eq!(spans.len(), 2);
+    assert!(spans
+        .iter()
+        .all(|s| s.trace_id == hex::decode(TRACE_ID).unwrap()));
+
+    let request = span("request");
+    assert_eq!(
+        request.parent_span_id,
+        hex::decode(REMOTE_PARENT_ID).unwrap()
+    );
+    assert_eq!(request.kind, SpanKind::Server as i32);
+    assert_eq!(
+        attribute(&request.attributes, "user_id"),
+        Some(&Value::IntValue(4))
+    );
+
+    let model = span("User::find_by_id");
+    assert_eq!(model.parent_span_id, request.span_id);
+    assert_eq!(model.kind, SpanKind::Internal as i32);
+    assert_eq!(
+        attribute(&model.attributes, telemetry::ROWS_RETURNED),
+        Some(&Value::IntValue(1))
+    );
+}